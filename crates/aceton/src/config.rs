@@ -1,6 +1,7 @@
 use std::time::Duration;
 
-use aceton_arbitrage::ArbitragerConfig;
+use aceton_arbitrage::{ArbitragerConfig, WalletConfig};
+use aceton_utils::RateLimitConfig;
 use anyhow::{anyhow, Context};
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DefaultOnNull};
@@ -13,21 +14,59 @@ pub struct AcetonConfig {
     #[serde_as(as = "DefaultOnNull")]
     pub ton: TonConfig,
     pub arbitrage: ArbitragerConfig,
+    #[serde(default)]
+    pub wallet: WalletConfig,
+    #[serde(default)]
+    pub dedust: DedustConfig,
+    #[serde(default)]
+    pub stonfi: StonfiConfig,
+}
+
+impl AcetonConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for (name, limits) in [
+            ("ton.limits", &self.ton.limits),
+            ("dedust.api_limits", &self.dedust.api_limits),
+        ] {
+            if limits.concurrency == 0 || !limits.rate.is_finite() || limits.rate <= 0.0 {
+                return Err(anyhow!("{name}: concurrency and rate must be positive"));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct TonConfig {
     pub config: Url,
+    /// Limits of get-method calls to liteservers
+    #[serde(default)]
+    pub limits: RateLimitConfig,
 }
 
 impl Default for TonConfig {
     fn default() -> Self {
         Self {
             config: "https://ton.org/global-config.json".parse().unwrap(),
+            limits: Default::default(),
         }
     }
 }
 
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct DedustConfig {
+    /// Limits of requests to DeDust HTTP API
+    pub api_limits: RateLimitConfig,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct StonfiConfig {
+    /// Whether to search for cycles through STON.fi pools as well
+    pub enabled: bool,
+}
+
 impl TonConfig {
     pub fn config(&self) -> anyhow::Result<TonClientBuilder> {
         Ok(match self.config.scheme() {
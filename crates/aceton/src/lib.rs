@@ -1,24 +1,41 @@
 pub mod config;
 
+pub use aceton_arbitrage::{ArbitragerWallet, WalletConfig};
+
+use std::sync::Arc;
+
+use aceton_utils::RateLimiter;
 use anyhow::Context;
-use ton_contracts::wallet::{mnemonic::Keypair, Wallet};
 use tracing::info;
 
 use aceton_arbitrage::Arbitrager;
+use aceton_core::MultiDex;
 use aceton_dedust::{DeDust, DEDUST_FACTORY_MAINNET_ADDRESS};
+use aceton_stonfi::StonFi;
 
 use self::config::AcetonConfig;
 
+/// Arbitrager over DEXes enabled in config
+enum DexArbitrager {
+    DeDust(Arbitrager<DeDust>),
+    DeDustStonfi(Arbitrager<MultiDex<DeDust, StonFi>>),
+}
+
 pub struct Aceton {
-    arbitrager: Arbitrager<DeDust>,
+    arbitrager: DexArbitrager,
 }
 
 impl Aceton {
-    pub async fn new(cfg: AcetonConfig, key_pair: Keypair) -> anyhow::Result<Self> {
-        let wallet = Wallet::derive_default(key_pair).context("wallet")?;
-        info!(wallet.address = %wallet.address());
+    pub async fn new(cfg: AcetonConfig, wallet: ArbitragerWallet) -> anyhow::Result<Self> {
+        info!(
+            wallet.address = %wallet.address(),
+            wallet.version = ?wallet.signer().map(|wallet| wallet.version()),
+            dry_run = cfg.arbitrage.dry_run,
+        );
 
+        cfg.validate().context("config")?;
         let http_client = reqwest::Client::new();
+        let ton_limiter = Arc::new(RateLimiter::new(&cfg.ton.limits));
 
         info!("creating TON client...");
         let mut ton_client = cfg.ton.config()?.build().await?;
@@ -26,19 +43,39 @@ impl Aceton {
         ton_client.ready().await?;
         info!("TOM client ready");
 
-        let arbitrager = Arbitrager::new(
-            cfg.arbitrage,
+        let dedust = DeDust::new(
             ton_client.clone(),
-            DeDust::new(ton_client, DEDUST_FACTORY_MAINNET_ADDRESS, http_client),
-            wallet,
+            DEDUST_FACTORY_MAINNET_ADDRESS,
+            http_client.clone(),
         )
-        .await?;
+        .with_ton_limiter(ton_limiter.clone())
+        .with_api_limiter(Arc::new(RateLimiter::new(&cfg.dedust.api_limits)));
+        let arbitrager = if cfg.stonfi.enabled {
+            info!("STON.fi enabled");
+            DexArbitrager::DeDustStonfi(
+                Arbitrager::new(
+                    cfg.arbitrage,
+                    ton_client.clone(),
+                    MultiDex::new(
+                        dedust,
+                        StonFi::mainnet(ton_client, http_client).with_ton_limiter(ton_limiter),
+                    ),
+                    wallet,
+                )
+                .await?,
+            )
+        } else {
+            DexArbitrager::DeDust(Arbitrager::new(cfg.arbitrage, ton_client, dedust, wallet).await?)
+        };
 
         Ok(Self { arbitrager })
     }
 
-    pub async fn run(mut self) -> anyhow::Result<()> {
+    pub async fn run(self) -> anyhow::Result<()> {
         info!("running...");
-        self.arbitrager.run().await
+        match self.arbitrager {
+            DexArbitrager::DeDust(mut arbitrager) => arbitrager.run().await,
+            DexArbitrager::DeDustStonfi(mut arbitrager) => arbitrager.run().await,
+        }
     }
 }
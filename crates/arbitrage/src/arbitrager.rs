@@ -2,59 +2,146 @@ use core::{
     cmp,
     fmt::Debug,
     hash::{self, Hash},
+    mem,
 };
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{hash_map::Entry, HashMap, HashSet},
     sync::{
         atomic::{self, AtomicU64},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use aceton_core::{
-    ton_utils::{contract::TonContract, wallet::WalletI},
-    Asset, Dex, DexBody, DexPool, SwapPath,
+    ton_utils::{
+        blocks::BlockSubscription,
+        contract::TonContract,
+        jetton::{JettonMasterI, JettonWalletI},
+    },
+    Asset, Dex, DexBody, DexPool, RiskModel, SwapPath,
 };
 use anyhow::{anyhow, Context};
 use base64::{engine::general_purpose::STANDARD, Engine};
-use chrono::{Local, TimeDelta, Utc};
-use futures::{future, stream::FuturesUnordered, try_join, TryStreamExt};
-use lazy_static::lazy_static;
-use num::{rational::Ratio, BigUint, One, ToPrimitive};
+use chrono::Utc;
+use futures::{lock::Mutex, stream::FuturesUnordered, StreamExt};
+use itertools::Either;
+use num::{rational::Ratio, BigUint, FromPrimitive, One, ToPrimitive};
 use petgraph::{
     graph::{EdgeIndex, NodeIndex},
-    visit::{
-        DfsPostOrder, EdgeFiltered, EdgeRef, FilterEdge, GraphBase, IntoEdgeReferences, IntoEdges,
-    },
+    visit::{DfsPostOrder, EdgeRef},
     Directed, Graph,
 };
-use tlb::CellSerializeExt;
+use tlb::{CellSerialize, CellSerializeExt};
 use tlb_ton::{
     BagOfCells, BoC, CommonMsgInfo, CurrencyCollection, ExtraCurrencyCollection, InternalMsgInfo,
     Message, MsgAddress,
 };
-use ton_contracts::wallet::{v4r2::V4R2, Wallet, WalletOpSendMessage};
 use tonlibjson_client::ton::TonClient;
 use tracing::{debug, info, instrument, warn};
 
-use aceton_graph_utils::NegativeCycles;
+use aceton_graph_utils::{par_negative_cycles, CycleIndex, NegativeCycleFinder, Spfa, Tarjan};
+
+use crate::{
+    executor::MESSAGE_TTL, ArbitragerConfig, ArbitragerWallet, Backoff, CycleSearch, Executor,
+    OperationError, OperationResultExt, PendingSwap, PoolUpdates, SendOutcome, SwapGroup,
+    SwapTracker, TonTransport, WalletTransfer, SEND_MODE_IGNORE_ERRORS,
+    SEND_MODE_PAY_FEES_SEPARATELY,
+};
+
+type G = Graph<Asset, f64, Directed>;
+
+/// Delays to retry failed operations with
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// How often to check transfers in flight when no more can be sent
+const SEND_POLL_INTERVAL: Duration = Duration::from_secs(2);
 
-use crate::ArbitragerConfig;
+/// How often to check for a new masterchain block, which are produced
+/// every ~5 seconds
+const BLOCK_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
-lazy_static! {
-    static ref KEEP_MIN_TON: BigUint = 2_000_000_000u64.into(); // 2 TON
+/// Swap of a single cycle added to the wallet transfer
+struct PlannedSwap {
+    query_id: u64,
+    cycle: String,
+    asset_in: Asset,
+    amount_in: BigUint,
+    amount_out: BigUint,
+    /// Estimated profit after gas
+    profit: BigUint,
+    /// TON attached to messages of the swap
+    ton_value: BigUint,
+    /// Recepient and value of the message, if the swap is sent in a
+    /// single message and can be tracked by `query_id`
+    tracked: Option<(MsgAddress, BigUint)>,
 }
 
-type G = Graph<Asset, f64, Directed>;
+impl PlannedSwap {
+    /// Amounts of base assets spent by the swap, where TON attached to
+    /// messages of jetton swaps is spent on top of their `amount_in`
+    fn spent(&self) -> impl Iterator<Item = (Asset, &BigUint)> {
+        let ton = (self.asset_in != Asset::Native).then_some((Asset::Native, &self.ton_value));
+        [(self.asset_in, &self.amount_in)].into_iter().chain(ton)
+    }
+}
+
+/// Transfer sent to the wallet which is not processed yet
+struct InFlightTransfer<ID> {
+    /// Swaps to track once the transfer is processed
+    swaps: Vec<PendingSwap>,
+    /// Pools the transfer swaps through, which should not be used by
+    /// other transfers until this one is processed
+    pools: Vec<ID>,
+    /// base_asset -> total amount to be spent
+    amounts_in: HashMap<Asset, BigUint>,
+}
+
+/// Hypothetical profit of swaps which would have been sent in dry run
+#[derive(Default)]
+struct DryRunPnl {
+    trades: u64,
+    /// base_asset -> total profit
+    profit: HashMap<Asset, BigUint>,
+    /// (cycle, amount_in) of the last trade, so that the same
+    /// opportunity is not counted again while pools stay unchanged
+    last: Option<(String, BigUint)>,
+}
+
+impl DryRunPnl {
+    fn record(&mut self, asset: Asset, cycle: String, amount_in: BigUint, profit: BigUint) {
+        let key = (cycle, amount_in);
+        if self.last.as_ref() == Some(&key) {
+            debug!("same opportunity as the last one, skipping");
+            return;
+        }
+        self.trades += 1;
+        let total_profit = self.profit.entry(asset).or_default();
+        *total_profit += &profit;
+        info!(
+            monotonic_counter.arbitrage_dry_run_trades = 1u64,
+            histogram.arbitrage_dry_run_profit = profit.to_f64().unwrap(),
+            %asset,
+        );
+        info!(
+            trades = self.trades,
+            %asset,
+            %profit,
+            %total_profit,
+            "dry run: hypothetical trade",
+        );
+        self.last = Some(key);
+    }
+}
 
 pub struct Arbitrager<D>
 where
     D: Dex,
 {
     cfg: ArbitragerConfig,
-    dex: D,
+    dex: Arc<D>,
     g: G,
 
     /// asset -> node_index
@@ -66,10 +153,35 @@ where
     /// pool_id -> (pool, edge_indexes)
     pools: HashMap<<D::Pool as DexPool>::ID, (D::Pool, [EdgeIndex; 2])>,
 
+    /// pool_id -> time when reserves were last seen changed
+    reserves_updated_at: HashMap<<D::Pool as DexPool>::ID, Instant>,
+
+    /// pool_ids which failed to update, so that their reserves are
+    /// unknown and they are skipped until updated successfully
+    stale_pools: HashSet<<D::Pool as DexPool>::ID>,
+
+    /// base_asset -> index of cycles through it, set when cycles are
+    /// searched incrementally
+    cycle_indexes: Vec<(Asset, CycleIndex<EdgeIndex>)>,
+    /// Edges which costs changed since the cycle index was last updated
+    changed_edges: HashSet<EdgeIndex>,
+
+    risk_model: Box<dyn RiskModel + Send + Sync>,
+
+    /// jetton_master -> our jetton_wallet
+    jetton_wallets: Mutex<HashMap<MsgAddress, MsgAddress>>,
+
+    /// Query ID of the next swap, which is matched with its payout
     query_id: AtomicU64,
+    executor: Executor<TonTransport, InFlightTransfer<<D::Pool as DexPool>::ID>>,
+    tracker: Arc<SwapTracker<D, TonContract>>,
 
     ton: TonClient,
-    wallet: Wallet<V4R2>,
+    wallet: ArbitragerWallet,
+    /// Set when pools are updated by following new blocks
+    blocks: Option<BlockSubscription<TonClient>>,
+
+    dry_run_pnl: DryRunPnl,
 }
 
 impl<D> Arbitrager<D>
@@ -82,25 +194,61 @@ where
         cfg: ArbitragerConfig,
         ton: TonClient,
         dex: D,
-        wallet: Wallet<V4R2>,
+        wallet: impl Into<ArbitragerWallet>,
     ) -> anyhow::Result<Self> {
-        let base_asset = cfg.base_asset;
+        cfg.validate().context("config")?;
+        let wallet = wallet.into();
+        check_wallet(&wallet, cfg.dry_run)?;
+        let base_assets: Vec<_> = cfg.base_assets.iter().map(|base| base.asset).collect();
         info!("resolving DEX pools...");
         let pools = dex.get_pools().await.context("DEX")?;
 
+        let dex = Arc::new(dex);
+        let tracker = SwapTracker::new(
+            dex.clone(),
+            TonContract::new(ton.clone(), wallet.address()),
+            cfg.track_timeout,
+        );
+
+        // payouts of swaps sent before restart should not be matched
+        // with new ones
+        let query_id = AtomicU64::new(Utc::now().timestamp_millis() as u64);
+        let executor = Executor::new(
+            TonTransport::new(ton.clone(), wallet.address(), wallet.is_highload()),
+            wallet.is_highload(),
+            cfg.max_in_flight,
+        )
+        .await
+        .context("executor")?;
+
+        let blocks =
+            (cfg.pool_updates == PoolUpdates::Blocks).then(|| BlockSubscription::new(ton.clone()));
+
         let mut s = Self {
+            risk_model: Box::new(cfg.risk.model.clone()),
+            tracker: Arc::new(tracker),
             cfg,
             dex,
             ton,
             wallet,
+            blocks,
             g: Graph::new(),
             asset2node: Default::default(),
             edge2pool: Default::default(),
             pools: Default::default(),
-            query_id: Default::default(),
+            reserves_updated_at: Default::default(),
+            stale_pools: Default::default(),
+            cycle_indexes: Vec::new(),
+            changed_edges: Default::default(),
+            jetton_wallets: Default::default(),
+            query_id,
+            executor,
+            dry_run_pnl: Default::default(),
         };
         info!(pools_count = pools.len(), "building DEX graph...");
-        s.add_asset(base_asset);
+        for asset in &base_assets {
+            s.add_asset(*asset);
+        }
         s.add_pools(pools);
         // info!("removing branches...");
         // s.remove_branches();
@@ -110,9 +258,28 @@ where
             pool_count = s.pool_count(),
             "DEX graph ready",
         );
+        if let (CycleSearch::Incremental, Some(max_length)) = (s.cfg.cycle_search, s.cfg.max_length)
+        {
+            info!("indexing cycles...");
+            let mut indexes = Vec::new();
+            for asset in base_assets {
+                let mut index = CycleIndex::new(&s.g, s.asset2node[&asset], max_length);
+                index.update_all(|e| s.edge_cost(e));
+                info!(%asset, cycle_count = index.len(), "cycles indexed");
+                indexes.push((asset, index));
+            }
+            s.cycle_indexes = indexes;
+        }
         Ok(s)
     }
 
+    /// Replaces the default [`RiskModel`] configured in
+    /// [`RiskConfig`](crate::RiskConfig)
+    pub fn with_risk_model(mut self, risk_model: impl RiskModel + Send + Sync + 'static) -> Self {
+        self.risk_model = Box::new(risk_model);
+        self
+    }
+
     fn add_asset(&mut self, asset: Asset) -> NodeIndex {
         *self
             .asset2node
@@ -166,21 +333,12 @@ where
     //     self.g.retain_nodes(|_, node| keep.contains(&node))
     // }
 
-    async fn wallet_seqno(&self) -> anyhow::Result<u32> {
-        let wallet = TonContract::new(self.ton.clone(), self.wallet.address());
-        wallet.seqno().await
+    pub fn base_assets(&self) -> impl Iterator<Item = Asset> + '_ {
+        self.cfg.base_assets.iter().map(|base| base.asset)
     }
 
-    pub fn base_asset(&self) -> Asset {
-        self.cfg.base_asset
-    }
-
-    fn base_asset_id(&self) -> NodeIndex {
-        self.asset2node[&self.base_asset()]
-    }
-
-    pub async fn base_asset_balance(&self) -> anyhow::Result<BigUint> {
-        match self.base_asset() {
+    pub async fn asset_balance(&self, asset: Asset) -> anyhow::Result<BigUint> {
+        match asset {
             Asset::Native => {
                 let state = self
                     .ton
@@ -189,7 +347,13 @@ where
                 Ok((state.balance as u64).into())
             }
             Asset::Jetton(master) => {
-                todo!()
+                let jetton_wallet =
+                    TonContract::new(self.ton.clone(), self.jetton_wallet_address(master).await?);
+                Ok(jetton_wallet
+                    .get_wallet_data()
+                    .await
+                    .context("jetton wallet")?
+                    .balance)
             }
             Asset::ExtraCurrency { .. } => {
                 return Err(anyhow!("extra currencies are not supported"))
@@ -197,252 +361,848 @@ where
         }
     }
 
-    fn filter_pools(
-        &self,
-    ) -> EdgeFiltered<&G, impl FilterEdge<<&G as IntoEdgeReferences>::EdgeRef>> {
-        EdgeFiltered::from_fn(&self.g, |edge: <&G as IntoEdgeReferences>::EdgeRef| {
-            // check that -log is finite
-            edge.weight().is_finite()
+    #[instrument(skip(self))]
+    async fn jetton_wallet_address(&self, master: MsgAddress) -> anyhow::Result<MsgAddress> {
+        let mut jetton_wallets = self.jetton_wallets.lock().await;
+        Ok(match jetton_wallets.entry(master) {
+            Entry::Occupied(entry) => *entry.get(),
+            Entry::Vacant(entry) => {
+                let master = TonContract::new(self.ton.clone(), master);
+                let address = master
+                    .get_wallet_address(self.wallet.address())
+                    .await
+                    .context("jetton master")?;
+                debug!(%address, "resolved jetton wallet address");
+                *entry.insert(address)
+            }
         })
     }
 
-    fn profitable_cycles<'a, G1>(&'a self, g: G1) -> impl Iterator<Item = SwapPath<&D::Pool>>
-    where
-        G1: IntoEdges<
-            NodeId = <&'a G as GraphBase>::NodeId,
-            EdgeRef = <&'a G as IntoEdgeReferences>::EdgeRef,
-        >,
-    {
-        NegativeCycles::new(
-            g,
-            self.base_asset_id(),
-            |edge| *edge.weight(),
+    fn is_edge_usable(&self, e: EdgeIndex) -> bool {
+        // check that -log is finite
+        self.g[e].is_finite() && !self.stale_pools.contains(&self.edge2pool[e.index()])
+    }
+
+    /// Cost of the edge for the cycle index, where unusable edges are
+    /// excluded by infinite cost
+    fn edge_cost(&self, e: EdgeIndex) -> f64 {
+        if self.is_edge_usable(e) {
+            self.g[e]
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    /// Searches for negative cycles through the base asset on a blocking
+    /// thread against a snapshot of edge costs, so that long searches do
+    /// not block the runtime
+    async fn profitable_cycles(
+        &self,
+        base_asset: Asset,
+    ) -> anyhow::Result<impl Iterator<Item = SwapPath<&D::Pool>>> {
+        let g: Graph<(), f64, Directed> = self.g.map(|_, _| (), |e, _| self.edge_cost(e));
+        let start = self.asset2node[&base_asset];
+        let (cycle_search, max_length, max_cycles, dfs_pruning) = (
+            self.cfg.cycle_search,
             self.cfg.max_length,
-        )
-        .map(|pools| {
-            let mut p = SwapPath::new(self.base_asset());
-            p.extend(
-                pools
-                    .into_iter()
-                    .map(|e| &self.pools[&self.edge2pool[e.id().index()]].0),
-            );
-            p
+            self.cfg.max_cycles,
+            self.cfg.dfs_pruning,
+        );
+        let cycles = tokio::task::spawn_blocking(move || {
+            let cycles = match cycle_search {
+                CycleSearch::Spfa => Spfa {
+                    max_length,
+                    max_cycles,
+                }
+                .negative_cycles(&g, start, |edge| *edge.weight()),
+                CycleSearch::Tarjan => Tarjan {
+                    max_length,
+                    max_cycles,
+                }
+                .negative_cycles(&g, start, |edge| *edge.weight()),
+                // incremental search goes through the cycle index instead,
+                // unless it is missing
+                CycleSearch::Dfs | CycleSearch::Incremental => {
+                    let (found, stats) = par_negative_cycles(
+                        &g,
+                        start,
+                        |edge| *edge.weight(),
+                        max_length,
+                        dfs_pruning,
+                    );
+                    debug!(
+                        histogram.arbitrage_dfs_visited = stats.visited as f64,
+                        monotonic_counter.arbitrage_dfs_pruned = stats.pruned,
+                        monotonic_counter.arbitrage_dfs_skipped = stats.skipped,
+                        cycles = found.len(),
+                        "dfs cycle search",
+                    );
+                    found
+                }
+            };
+            cycles
+                .into_iter()
+                .map(|path| path.into_iter().map(|e| e.id()).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
         })
+        .await
+        .context("cycle search")?;
+        Ok(cycles
+            .into_iter()
+            .map(move |path| self.swap_path(base_asset, path)))
+    }
+
+    /// Negative cycles from the cycle index, which should be updated
+    /// with [`Self::update_cycle_indexes`] before
+    fn indexed_cycles<'a>(
+        &'a self,
+        base_asset: Asset,
+        index: &'a CycleIndex<EdgeIndex>,
+    ) -> impl Iterator<Item = SwapPath<&D::Pool>> {
+        index
+            .negative_cycles()
+            .map(move |path| self.swap_path(base_asset, path.iter().copied()))
+    }
+
+    fn swap_path(
+        &self,
+        base_asset: Asset,
+        edges: impl IntoIterator<Item = EdgeIndex>,
+    ) -> SwapPath<&D::Pool> {
+        let mut p = SwapPath::new(base_asset);
+        p.extend(
+            edges
+                .into_iter()
+                .map(|e| &self.pools[&self.edge2pool[e.index()]].0),
+        );
+        p
     }
 
+    /// Re-evaluates indexed cycles through edges changed since the last
+    /// call
+    fn update_cycle_indexes(&mut self) {
+        let mut indexes = mem::take(&mut self.cycle_indexes);
+        let changed = mem::take(&mut self.changed_edges);
+        for (asset, index) in &mut indexes {
+            let evaluated = index.update(changed.iter().copied(), |e| self.edge_cost(e));
+            debug!(
+                %asset,
+                changed_edges = changed.len(),
+                evaluated,
+                "cycle index updated",
+            );
+        }
+        self.cycle_indexes = indexes;
+    }
+
+    /// Updates reserves of pools which might have changed since the
+    /// previous call and reweights edges of those which did
     async fn update_pools(&mut self) -> anyhow::Result<()> {
+        let Some(blocks) = &mut self.blocks else {
+            return self.update_pools_where(|_| true).await;
+        };
+        let changed = loop {
+            if let Some(changed) = blocks.poll().await? {
+                break changed;
+            }
+            tokio::time::sleep(BLOCK_POLL_INTERVAL).await;
+        };
+        let Some(accounts) = changed.accounts else {
+            info!(
+                seqno = changed.seqno,
+                "changed accounts are unknown, updating all pools..."
+            );
+            return self.update_pools_where(|_| true).await;
+        };
+        debug!(
+            seqno = changed.seqno,
+            accounts = accounts.len(),
+            "new masterchain block",
+        );
+        self.update_pools_where(|pool| accounts.contains(&pool.address()))
+            .await
+    }
+
+    /// Updates pools matching the filter along with stale ones.
+    /// Pools which fail to update are marked stale instead of failing
+    /// the whole update.
+    async fn update_pools_where(
+        &mut self,
+        filter: impl Fn(&D::Pool) -> bool,
+    ) -> anyhow::Result<()> {
         let mut updated_pools = self
             .pools
             .iter_mut()
+            .filter(|(pool_id, (pool, _))| self.stale_pools.contains(*pool_id) || filter(pool))
             .map(|(pool_id, (pool, edges))| {
                 let dex = &self.dex;
                 async move {
-                    if !dex.update_pool(pool).await? {
-                        return Ok(None);
-                    }
-                    return anyhow::Ok(Some((pool_id, &*pool, *edges)));
+                    let updated = dex.update_pool(pool).await;
+                    (pool_id, &*pool, *edges, updated)
                 }
             })
-            .collect::<FuturesUnordered<_>>()
-            .try_filter_map(future::ok);
+            .collect::<FuturesUnordered<_>>();
 
-        while let Some((pool_id, pool, edges)) = updated_pools.try_next().await? {
+        while let Some((pool_id, pool, edges, updated)) = updated_pools.next().await {
+            match updated {
+                Ok(updated) => {
+                    let was_stale = self.stale_pools.remove(pool_id);
+                    if was_stale && !self.cycle_indexes.is_empty() {
+                        self.changed_edges.extend(edges);
+                    }
+                    if !updated {
+                        continue;
+                    }
+                }
+                Err(err) => {
+                    if self.stale_pools.insert(pool_id.clone()) {
+                        if !self.cycle_indexes.is_empty() {
+                            self.changed_edges.extend(edges);
+                        }
+                        warn!(
+                            monotonic_counter.arbitrage_stale_pools = 1u64,
+                            pool.address = %pool.address(),
+                            ?err,
+                            "failed to update pool, marked as stale",
+                        );
+                    }
+                    continue;
+                }
+            }
+            self.reserves_updated_at
+                .insert(pool_id.clone(), Instant::now());
             for e in edges {
                 let (index_in, _index_out) = self.g.edge_endpoints(e).unwrap();
                 self.g[e] = -pool.rate_with_fees(self.g[index_in]).log2();
             }
+            if !self.cycle_indexes.is_empty() {
+                self.changed_edges.extend(edges);
+            }
         }
         Ok(())
     }
 
-    fn make_steps(
-        &self,
-        _amount_in: &BigUint,
-        path: &SwapPath<&D::Pool>,
-    ) -> anyhow::Result<<D::Pool as DexPool>::Step> {
-        let mut pools = path.iter_pools();
-
-        let first = pools.next().context("empty path")?;
-
-        let rest = pools
-            .collect::<Vec<_>>()
-            .into_iter()
-            .rev()
-            .fold(None, |next, pool| Some(pool.make_step(None, next)));
+    fn reserves_age(&self, pool: &D::Pool) -> Option<Duration> {
+        self.reserves_updated_at
+            .get(&pool.id())
+            .map(Instant::elapsed)
+    }
 
-        // TODO: amount_out_min
-        let root = first.make_step(None, rest);
-        Ok(root)
+    /// Rate to convert amounts of `from` into `to` through usable pools,
+    /// see [`conversion_rate`]
+    fn conversion_rate(&self, from: Asset, to: Asset) -> Option<f64> {
+        let (from, to) = (*self.asset2node.get(&from)?, *self.asset2node.get(&to)?);
+        conversion_rate(&self.g, from, to, |e| self.is_edge_usable(e))
     }
 
     async fn make_body(
         &self,
+        query_id: u64,
         amount_in: &BigUint,
         path: &SwapPath<&D::Pool>,
-    ) -> anyhow::Result<DexBody<D::Body>> {
+    ) -> anyhow::Result<Vec<DexBody<D::Body>>> {
         let asset_in = path.asset_in();
-        if asset_in != self.base_asset() {
+        if self.cfg.base_asset(asset_in).is_none() {
             return Err(anyhow!("{asset_in} is not base asset"));
         }
-        let steps = self.make_steps(amount_in, path)?;
+        let min_amount_out = match asset_in {
+            // gas is paid in the same asset
+            Asset::Native => amount_in + self.dex.estimate_gas(path),
+            _ => amount_in.clone(),
+        };
+        let steps = make_steps(&self.cfg.slippage, amount_in, &min_amount_out, path)?;
         self.dex
             .make_body(
-                self.query_id.fetch_add(1, atomic::Ordering::SeqCst),
-                self.base_asset(),
+                query_id,
+                self.wallet.address(),
+                asset_in,
                 amount_in.clone(),
                 steps,
             )
             .await
     }
 
-    fn make_message(
-        &self,
-        dst: MsgAddress,
-        grams: BigUint,
-        body: D::Body,
-    ) -> anyhow::Result<Message<D::Body>> {
-        Ok(Message {
-            info: CommonMsgInfo::Internal(InternalMsgInfo {
-                ihr_disabled: true,
-                bounce: true,
-                bounced: false,
-                src: MsgAddress::NULL,
-                dst,
-                value: CurrencyCollection {
-                    grams,
-                    other: ExtraCurrencyCollection,
-                },
-                ihr_fee: BigUint::ZERO,
-                fwd_fee: BigUint::ZERO,
-                created_lt: 0,
-                created_at: None,
-            }),
-            init: None,
-            body,
-        })
+    /// Logs BoC of the transfer instead of sending it and records
+    /// hypothetical profit of each swap
+    fn dry_run(
+        &mut self,
+        seqno: u32,
+        transfer: WalletTransfer,
+        swaps: Vec<PlannedSwap>,
+    ) -> anyhow::Result<()> {
+        let (bocs, kind) = dry_run_bocs(&self.wallet, seqno, transfer)?;
+        for boc in bocs {
+            info!(
+                boc = STANDARD.encode(boc),
+                boc.kind = kind,
+                "dry run: message is not sent",
+            );
+        }
+        for swap in swaps {
+            self.dry_run_pnl
+                .record(swap.asset_in, swap.cycle, swap.amount_in, swap.profit);
+        }
+        Ok(())
     }
 
-    async fn send_external_message(
+    /// Builds messages of the swap through `cycle` and adds them to the
+    /// transfer, returns `None` if the swap is not worth sending, does
+    /// not fit into the transfer or attaches more TON than `ton_budget`
+    async fn plan_swap(
         &self,
-        seqno: u32,
-        message: Message<D::Body>,
-    ) -> anyhow::Result<()> {
-        let now = Local::now().with_timezone(&Utc);
-        let expire_at = now + TimeDelta::seconds(60);
-
-        let msg = self.wallet.create_external_message(
-            expire_at,
-            seqno,
-            [WalletOpSendMessage {
-                mode: 3,
-                message: message.normalize()?,
-            }],
-            false,
-        )?;
+        transfer: &mut WalletTransfer,
+        cycle: &SwapPath<&D::Pool>,
+        amount_in: BigUint,
+        amount_out: BigUint,
+        risk: f64,
+        ton_budget: Option<&BigUint>,
+    ) -> anyhow::Result<Option<PlannedSwap>> {
+        let asset_in = cycle.asset_in();
+        let query_id = self.query_id.fetch_add(1, atomic::Ordering::SeqCst);
+        let bodies = self.make_body(query_id, &amount_in, cycle).await?;
+        if bodies.len() > transfer.remaining() {
+            info!(messages = bodies.len(), "not enough room in the transfer");
+            return Ok(None);
+        }
+        let ton_value: BigUint = bodies.iter().map(|body| &body.value).sum();
+        // amount_in of TON swaps is already limited by the budget
+        if asset_in != Asset::Native && ton_budget.is_some_and(|budget| ton_value > *budget) {
+            info!(%ton_value, "not enough TON to attach to messages");
+            return Ok(None);
+        }
+        let gas: BigUint = bodies.iter().map(|body| &body.gas).sum();
+        // gas is paid in TON, while profit is in units of asset_in
+        let gas = match asset_in {
+            Asset::Native => gas,
+            _ => {
+                let Some(rate) = self.conversion_rate(Asset::Native, asset_in) else {
+                    info!(%asset_in, "no rate to convert gas into, skipping");
+                    return Ok(None);
+                };
+                BigUint::from_f64((gas.to_f64().unwrap() * rate).ceil()).unwrap_or_default()
+            }
+        };
 
-        let boc = BagOfCells::from_root(msg.to_cell()?);
-        let packed = boc.pack(true)?;
+        let mut profit = &amount_out - &amount_in;
+        if profit <= &gas + self.cfg.min_profit(asset_in) {
+            info!(%profit, %gas, "profit does not cover gas");
+            return Ok(None);
+        }
+        profit -= &gas;
 
-        let tx_hash = self
-            .ton
-            .send_message_returning_hash(STANDARD.encode(packed).as_str())
-            .await?;
-        let decoded_tx_hash = STANDARD.decode(tx_hash)?;
-        warn!(tx.hash = hex::encode(decoded_tx_hash), "sent tx");
+        let profit_ratio = Ratio::new(profit.clone(), amount_in.clone());
+        let profit_rate = profit_ratio.to_f64().unwrap();
+        if profit_ratio < self.cfg.min_profit_rate {
+            info!(
+                profit_rate_percent = format!("{:.2}", profit_rate * 100.0),
+                "too small profit percent"
+            );
+            return Ok(None);
+        }
+        info!(
+            %amount_in,
+            %amount_out,
+            profit_rate_percent = format!("{:.2}", profit_rate * 100.0),
+            risk,
+            %cycle,
+            "adding cycle to the transfer",
+        );
+
+        let tracked = push_swap(transfer, bodies)?;
+        Ok(Some(PlannedSwap {
+            query_id,
+            cycle: cycle.to_string(),
+            asset_in,
+            amount_in,
+            amount_out,
+            profit,
+            ton_value,
+            tracked,
+        }))
+    }
+
+    /// Starts tracking swaps of processed transfers
+    async fn handle_sent_transfers(&mut self) -> anyhow::Result<()>
+    where
+        D: Send + Sync + 'static,
+    {
+        for outcome in self.executor.poll().await? {
+            let send = match outcome {
+                SendOutcome::Confirmed(send) => send,
+                SendOutcome::Expired(send) => {
+                    warn!(
+                        monotonic_counter.arbitrage_dropped_messages = 1u64,
+                        seqno = send.seqno,
+                        "transfer expired and dropped",
+                    );
+                    continue;
+                }
+            };
+            info!(seqno = send.seqno, "transfer processed");
+            if send.payload.swaps.is_empty() {
+                continue;
+            }
+            let group = SwapGroup {
+                seqno: send.seqno,
+                swaps: send.payload.swaps,
+            };
+            let tracker = self.tracker.clone();
+            tokio::spawn(async move {
+                match tracker.track_group(group).await {
+                    Ok(report) => report.emit(),
+                    Err(err) => warn!(?err, "failed to track swaps"),
+                }
+            });
+        }
         Ok(())
     }
 
+    /// Runs until a [fatal](crate::Fatal) error or an operation exhausts its
+    /// retries, retrying failed operations with exponential backoff
     pub async fn run(&mut self) -> anyhow::Result<()>
     where
+        D: Send + Sync + 'static,
         D::Pool: Debug,
     {
         info!("starting main loop...");
+        let mut backoff = Backoff::new(MIN_RETRY_DELAY, MAX_RETRY_DELAY);
         loop {
-            info!("updating pools reserves...");
-            self.update_pools().await?;
-            info!("pools reserves updated");
-
-            let (seqno, base_asset_balance) =
-                try_join!(self.wallet_seqno(), self.base_asset_balance())?;
+            let Err(err) = self.iteration().await else {
+                backoff.reset();
+                continue;
+            };
+            let kind = err.kind();
+            warn!(
+                monotonic_counter.arbitrage_failures = 1u64,
+                operation = err.operation,
+                ?kind,
+                error = format!("{:#}", err.source),
+                "operation failed",
+            );
+            let Some(delay) = backoff.retry(&err) else {
+                return Err(err.source.context(err.operation));
+            };
             info!(
-                seqno,
-                base_asset = %self.base_asset(),
-                base_asset.balance = %base_asset_balance,
+                operation = err.operation,
+                attempt = backoff.failures(err.operation),
+                ?delay,
+                "retrying...",
             );
+            tokio::time::sleep(delay).await;
+        }
+    }
 
-            let amount_in = (self.cfg.amount_in_balance_coef()
-                * if self.base_asset() == Asset::Native {
-                    if &base_asset_balance < &*KEEP_MIN_TON {
-                        warn!("too small balance");
-                        continue;
-                    }
-                    base_asset_balance - &*KEEP_MIN_TON
-                } else {
-                    base_asset_balance
-                })
-            .to_integer();
+    async fn iteration(&mut self) -> Result<(), OperationError>
+    where
+        D: Send + Sync + 'static,
+        D::Pool: Debug,
+    {
+        self.handle_sent_transfers().await.op("poll_transfers")?;
+        if self.executor.is_full() {
+            debug!("waiting for transfers in flight to be processed...");
+            tokio::time::sleep(SEND_POLL_INTERVAL).await;
+            return Ok(());
+        }
 
-            info!(%amount_in, "looking for profitable cycles...");
-            let filtered_pools = self.filter_pools();
-            let profitable_cycles = self.profitable_cycles(&filtered_pools);
+        info!("updating pools reserves...");
+        self.update_pools().await.op("update_pools")?;
+        info!("pools reserves updated");
 
-            let Some(cycle) =
-                profitable_cycles.max_by_key(|cycle| cycle.estimate_swap_out(amount_in.clone()))
-            else {
-                info!("no profitable cycles");
-                continue;
+        // base_asset -> max amount to put into swaps
+        let mut budgets = HashMap::new();
+        for base in &self.cfg.base_assets {
+            let asset = base.asset;
+            let balance = self.asset_balance(asset).await.op("balance")?;
+            // transfers in flight have not spent their amounts yet
+            let reserved: BigUint = self
+                .executor
+                .in_flight()
+                .filter_map(|transfer| transfer.amounts_in.get(&asset))
+                .sum();
+            info!(
+                base_asset = %asset,
+                base_asset.balance = %balance,
+                base_asset.reserved = %reserved,
+            );
+            let max_amount_in = if balance < reserved {
+                warn!(
+                    base_asset = %asset,
+                    "balance is less than reserved by transfers in flight",
+                );
+                None
+            } else {
+                let max_amount_in = self.cfg.max_amount_in(base, balance - reserved);
+                if max_amount_in.is_none() {
+                    warn!(base_asset = %asset, "too small balance");
+                }
+                max_amount_in
             };
+            // TON budget also limits TON attached to jetton swaps, so that
+            // it is kept even when exhausted
+            if let Some(max_amount_in) =
+                max_amount_in.or_else(|| (asset == Asset::Native).then(BigUint::default))
+            {
+                budgets.insert(asset, max_amount_in);
+            }
+        }
+        if budgets.is_empty() {
+            return Ok(());
+        }
 
-            let amount_out = cycle.estimate_swap_out(amount_in.clone());
-            if amount_out < amount_in {
-                info!(%amount_in, "the best cycle is unprofitable");
+        self.update_cycle_indexes();
+        // profits in different base assets are compared in units of the
+        // first one
+        let common_asset = self.cfg.base_assets[0].asset;
+        let mut candidates = Vec::new();
+        for (&asset, max_amount_in) in &budgets {
+            if *max_amount_in == BigUint::ZERO {
                 continue;
             }
+            let Some(rate) = self.conversion_rate(asset, common_asset) else {
+                warn!(
+                    base_asset = %asset,
+                    %common_asset,
+                    "no rate to compare profits, skipping",
+                );
+                continue;
+            };
+            info!(
+                base_asset = %asset,
+                %max_amount_in,
+                "looking for profitable cycles...",
+            );
+            let profitable_cycles = match self.cycle_indexes.iter().find(|(a, _)| *a == asset) {
+                Some((_, index)) => Either::Left(self.indexed_cycles(asset, index)),
+                None => Either::Right(self.profitable_cycles(asset).await.op("find_cycles")?),
+            };
+            candidates.extend(
+                profitable_cycles
+                    .map(|cycle| {
+                        let amount_in = cycle.optimal_amount_in(max_amount_in);
+                        let amount_out = cycle.estimate_swap_out(amount_in.clone());
+                        (cycle, amount_in, amount_out)
+                    })
+                    .filter(|(_, amount_in, amount_out)| amount_out > amount_in)
+                    .map(|(cycle, amount_in, amount_out)| {
+                        let risk = cycle
+                            .risk(&self.risk_model, &amount_in, |pool| self.reserves_age(pool));
+                        (cycle, amount_in, amount_out, rate, risk)
+                    })
+                    .filter(|(.., risk)| self.cfg.risk.max.map_or(true, |max| *risk <= max)),
+            );
+        }
+        if candidates.is_empty() {
+            info!("no profitable cycles");
+            return Ok(());
+        }
+        info!(cycles = candidates.len(), "found profitable cycles!");
+        sort_candidates(&mut candidates, self.cfg.risk.rank);
 
-            info!("found profitable cycle!");
-
-            let DexBody { dst, gas, body } = self.make_body(&amount_in, &cycle).await?;
-
-            let mut profit = &amount_out - &amount_in;
-            if profit <= &gas + BigUint::from(100_000_000u64) {
-                info!(%profit, %gas, "profit does not cover gas");
+        let mut transfer = WalletTransfer::new(self.wallet.max_messages());
+        let mut planned = Vec::new();
+        // swaps through the same pool would affect each other
+        let mut used_pools: HashSet<_> = self
+            .executor
+            .in_flight()
+            .flat_map(|transfer| transfer.pools.iter().cloned())
+            .collect();
+        let mut transfer_pools = Vec::new();
+        for (cycle, mut amount_in, mut amount_out, _, risk) in candidates {
+            if planned.len() >= self.cfg.max_cycles_per_transfer || transfer.remaining() == 0 {
+                break;
+            }
+            if cycle
+                .iter_pools()
+                .any(|pool| used_pools.contains(&pool.id()))
+            {
                 continue;
             }
-            profit -= &gas;
+            let budget = &budgets[&cycle.asset_in()];
+            if amount_in > *budget {
+                amount_in = cycle.optimal_amount_in(budget);
+                amount_out = cycle.estimate_swap_out(amount_in.clone());
+                if amount_out <= amount_in {
+                    continue;
+                }
+            }
 
-            let profit_rate = Ratio::new(profit, amount_in.clone()).to_f64().unwrap();
-            if profit_rate.to_f64().unwrap() < 0.05 {
-                info!(
-                    profit_rate_percent = format!("{:.2}", profit_rate * 100.0),
-                    "too small profit percent"
-                );
+            let Some(swap) = self
+                .plan_swap(
+                    &mut transfer,
+                    &cycle,
+                    amount_in,
+                    amount_out,
+                    risk,
+                    budgets.get(&Asset::Native),
+                )
+                .await
+                .op("plan_swap")?
+            else {
                 continue;
+            };
+            for (asset, amount) in swap.spent() {
+                if let Some(budget) = budgets.get_mut(&asset) {
+                    *budget -= amount;
+                }
             }
-            info!(
-                %amount_in,
-                %amount_out,
-                profit_rate_percent = format!("{:.2}", profit_rate * 100.0),
-                %cycle,
-                "found most profitable cycle",
-            );
+            transfer_pools.extend(cycle.iter_pools().map(|pool| pool.id()));
+            used_pools.extend(cycle.iter_pools().map(|pool| pool.id()));
+            planned.push(swap);
+        }
+        if planned.is_empty() {
+            return Ok(());
+        }
+        info!(
+            cycles = planned.len(),
+            messages = transfer.len(),
+            "sending transfer",
+        );
 
-            self.send_external_message(
-                seqno,
-                self.make_message(
+        if self.cfg.dry_run {
+            let seqno = self.executor.next_seqno().await.op("seqno")?;
+            self.dry_run(seqno, transfer, planned).op("dry_run")?;
+            return Ok(());
+        }
+
+        let after_lt = self
+            .tracker
+            .last_transaction_lt()
+            .await
+            .op("last_transaction_lt")?;
+        let mut amounts_in = HashMap::<_, BigUint>::new();
+        for swap in &planned {
+            for (asset, amount) in swap.spent() {
+                *amounts_in.entry(asset).or_default() += amount;
+            }
+        }
+        let planned_len = planned.len();
+        let swaps = planned
+            .into_iter()
+            .filter_map(|swap| {
+                let (dst, value) = swap.tracked?;
+                Some(PendingSwap {
+                    query_id: swap.query_id,
+                    after_lt,
+                    asset_in: swap.asset_in,
+                    amount_in: swap.amount_in,
+                    estimated_amount_out: swap.amount_out,
                     dst,
-                    gas + if matches!(self.base_asset(), Asset::Native) {
-                        amount_in
-                    } else {
-                        BigUint::ZERO
-                    },
-                    body,
-                )?,
+                    value,
+                })
+            })
+            .collect::<Vec<_>>();
+        if swaps.len() < planned_len {
+            debug!(
+                untracked = planned_len - swaps.len(),
+                "multi-message swaps are not tracked"
+            );
+        }
+
+        let wallet = self
+            .wallet
+            .signer()
+            .context("watch-only wallet cannot sign messages")
+            .fatal()
+            .op("send")?;
+        let seqno = self
+            .executor
+            .submit(
+                wallet,
+                transfer,
+                InFlightTransfer {
+                    swaps,
+                    pools: transfer_pools,
+                    amounts_in,
+                },
             )
-            .await?;
-            info!("sleeping for 60 seconds...");
-            tokio::time::sleep(Duration::from_secs(60)).await;
+            .await
+            .op("send")?;
+        info!(seqno, "transfer sent");
+        Ok(())
+    }
+}
+
+/// Watch-only wallet cannot sign messages, so that it can only be used
+/// in dry run
+fn check_wallet(wallet: &ArbitragerWallet, dry_run: bool) -> anyhow::Result<()> {
+    if wallet.signer().is_none() && !dry_run {
+        return Err(anyhow!("watch-only wallet can only be used in dry run"));
+    }
+    Ok(())
+}
+
+/// BoCs which would have been sent for the transfer along with their kind.
+/// For watch-only wallet, BoCs of internal messages are returned, since
+/// external one cannot be signed.
+fn dry_run_bocs(
+    wallet: &ArbitragerWallet,
+    seqno: u32,
+    transfer: WalletTransfer,
+) -> anyhow::Result<(Vec<Vec<u8>>, &'static str)> {
+    Ok(match wallet.signer() {
+        Some(wallet) => (
+            vec![
+                wallet
+                    .sign_transfer(seqno, MESSAGE_TTL, transfer.into_messages())
+                    // signing would fail the same way on retry
+                    .fatal()?
+                    .0,
+            ],
+            "external",
+        ),
+        None => (
+            transfer
+                .messages()
+                .iter()
+                .map(|op| {
+                    BagOfCells::from_root(op.message.to_cell()?)
+                        .pack(true)
+                        .map_err(Into::into)
+                })
+                .collect::<anyhow::Result<_>>()?,
+            "internal",
+        ),
+    })
+}
+
+/// Adds messages of the swap to the transfer. Returns recipient and value
+/// of the message if the swap is sent in a single one, since only such
+/// swaps can be tracked by `query_id`.
+fn push_swap<B>(
+    transfer: &mut WalletTransfer,
+    bodies: Vec<DexBody<B>>,
+) -> anyhow::Result<Option<(MsgAddress, BigUint)>>
+where
+    B: CellSerialize,
+{
+    let tracked = match bodies.as_slice() {
+        [body] => Some((body.dst, body.value.clone())),
+        _ => None,
+    };
+    for body in bodies {
+        // failure of one swap should not prevent others from being sent
+        transfer.push(
+            SEND_MODE_PAY_FEES_SEPARATELY | SEND_MODE_IGNORE_ERRORS,
+            make_message(body.dst, body.value, body.body),
+        )?;
+    }
+    Ok(tracked)
+}
+
+fn make_message<B>(dst: MsgAddress, grams: BigUint, body: B) -> Message<B> {
+    Message {
+        info: CommonMsgInfo::Internal(InternalMsgInfo {
+            ihr_disabled: true,
+            bounce: true,
+            bounced: false,
+            src: MsgAddress::NULL,
+            dst,
+            value: CurrencyCollection {
+                grams,
+                other: ExtraCurrencyCollection,
+            },
+            ihr_fee: BigUint::ZERO,
+            fwd_fee: BigUint::ZERO,
+            created_lt: 0,
+            created_at: None,
+        }),
+        init: None,
+        body,
+    }
+}
+
+/// Rate to convert amounts of `from` into `to` along the path with the
+/// fewest hops through usable edges and the best rate among such paths,
+/// `None` if there is no path at all
+fn conversion_rate(
+    g: &G,
+    from: NodeIndex,
+    to: NodeIndex,
+    is_edge_usable: impl Fn(EdgeIndex) -> bool,
+) -> Option<f64> {
+    // node -> min cost of reaching it
+    let mut costs: HashMap<NodeIndex, f64> = HashMap::from([(from, 0.0)]);
+    let mut layer = vec![from];
+    while !layer.is_empty() {
+        if let Some(cost) = costs.get(&to) {
+            return Some((-cost).exp2());
+        }
+        let mut next = HashMap::<NodeIndex, f64>::new();
+        for node in layer {
+            for edge in g.edges(node) {
+                if costs.contains_key(&edge.target()) || !is_edge_usable(edge.id()) {
+                    continue;
+                }
+                let cost = costs[&node] + edge.weight();
+                next.entry(edge.target())
+                    .and_modify(|best| *best = best.min(cost))
+                    .or_insert(cost);
+            }
         }
+        layer = next.keys().copied().collect();
+        costs.extend(next);
     }
+    None
+}
+
+/// Cycle along with its `amount_in`, `amount_out`, rate to convert
+/// amounts of its base asset into common units and risk
+type Candidate<C> = (C, BigUint, BigUint, f64, f64);
+
+/// Score of the cycle with `profit` converted into common units with
+/// `rate`, discounted by `risk` if `rank_risk`
+fn cycle_score(profit: &BigUint, rate: f64, risk: f64, rank_risk: bool) -> f64 {
+    let profit = profit.to_f64().unwrap() * rate;
+    if rank_risk {
+        return profit / (1.0 + risk);
+    }
+    profit
+}
+
+/// Sorts candidates from the most profitable one
+fn sort_candidates<C>(candidates: &mut [Candidate<C>], rank_risk: bool) {
+    let score = |(_, amount_in, amount_out, rate, risk): &Candidate<C>| {
+        cycle_score(&(amount_out - amount_in), *rate, *risk, rank_risk)
+    };
+    candidates.sort_by(|c1, c2| score(c2).total_cmp(&score(c1)));
+}
+
+/// Builds swap steps with `amount_out_min` of each hop set to its
+/// expected output reduced by `slippage`.
+/// The last hop is also required to give not less than `min_amount_out`,
+/// so that unprofitable execution is rejected.
+fn make_steps<P>(
+    slippage: &Ratio<BigUint>,
+    amount_in: &BigUint,
+    min_amount_out: &BigUint,
+    path: &SwapPath<P>,
+) -> anyhow::Result<P::Step>
+where
+    P: DexPool,
+{
+    let keep = Ratio::from_integer(BigUint::one()) - slippage;
+    let mut limits = path
+        .iter_steps()
+        .scan(amount_in.clone(), |amount, step| {
+            *amount = step.estimate_swap_out(amount);
+            Some((&keep * &*amount).to_integer())
+        })
+        .collect::<Vec<_>>();
+
+    let last = limits.last_mut().context("empty path")?;
+    if &*last < min_amount_out {
+        *last = min_amount_out.clone();
+    }
+
+    path.iter_pools()
+        .zip(limits)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .fold(None, |next, (pool, limit)| {
+            Some(pool.make_step(Some(limit), next))
+        })
+        .context("empty path")
 }
 
 #[derive(Debug, Clone, Copy, Eq)]
@@ -474,3 +1234,348 @@ impl Hash for EdgeKey {
         self.sorted().hash(state)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use aceton_core::{
+        testing::{MockDex, MockPool, MockStep},
+        MultiBody, MultiDex, MultiPool,
+    };
+    use chrono::DateTime;
+    use tlb::unpack_bytes;
+    use ton_contracts::wallet::WalletOpSendMessage;
+
+    use crate::{WalletSigner, WalletVersion, MAX_WALLET_MESSAGES};
+
+    use super::*;
+
+    const JETTON_A: Asset = Asset::Jetton(MsgAddress {
+        workchain_id: 0,
+        address: [1; 32],
+    });
+    const JETTON_B: Asset = Asset::Jetton(MsgAddress {
+        workchain_id: 0,
+        address: [2; 32],
+    });
+
+    fn cycle() -> SwapPath<MockPool> {
+        let mut p = SwapPath::new(Asset::Native);
+        p.extend([
+            MockPool::new(1, [Asset::Native, JETTON_A], [10_000, 20_000]),
+            MockPool::new(2, [JETTON_A, JETTON_B], [30_000, 25_000]),
+            MockPool::new(3, [JETTON_B, Asset::Native], [5_000, 8_000]),
+        ]);
+        p
+    }
+
+    /// `amount_out_min` of each hop
+    fn limits(step: MockStep) -> Vec<BigUint> {
+        step.into_iter().map(|(_, limit)| limit.unwrap()).collect()
+    }
+
+    #[test]
+    fn make_steps_limits_each_hop() {
+        let path = cycle();
+        let amount_in = BigUint::from(1_000u32);
+        let slippage = Ratio::new(1u32.into(), 100u32.into());
+
+        // expected outputs of each hop: 1813, 1420, 1765
+        let steps = make_steps(&slippage, &amount_in, &BigUint::ZERO, &path).unwrap();
+        assert_eq!(limits(steps), [1794u32, 1405, 1747].map(BigUint::from));
+
+        let no_slippage = Ratio::from_integer(BigUint::ZERO);
+        let steps = make_steps(&no_slippage, &amount_in, &BigUint::ZERO, &path).unwrap();
+        assert_eq!(limits(steps), [1813u32, 1420, 1765].map(BigUint::from));
+    }
+
+    #[test]
+    fn make_steps_raises_last_hop_limit() {
+        let path = cycle();
+        let amount_in = BigUint::from(1_000u32);
+        let slippage = Ratio::new(1u32.into(), 100u32.into());
+
+        let steps = make_steps(&slippage, &amount_in, &BigUint::from(1_760u32), &path).unwrap();
+        assert_eq!(limits(steps), [1794u32, 1405, 1760].map(BigUint::from));
+
+        // lower minimum does not loosen the limit
+        let steps = make_steps(&slippage, &amount_in, &BigUint::from(1_000u32), &path).unwrap();
+        assert_eq!(limits(steps), [1794u32, 1405, 1747].map(BigUint::from));
+    }
+
+    #[test]
+    fn make_steps_empty_path() {
+        let path = SwapPath::<MockPool>::new(Asset::Native);
+        let slippage = Ratio::from_integer(BigUint::ZERO);
+        assert!(make_steps(&slippage, &BigUint::one(), &BigUint::ZERO, &path).is_err());
+    }
+
+    /// The same cycle as [`cycle`] with the middle hop on another DEX
+    fn cross_dex_cycle() -> SwapPath<MultiPool<MockPool, MockPool>> {
+        let mut p = SwapPath::new(Asset::Native);
+        p.extend(cycle().iter_pools().enumerate().map(|(i, pool)| match i {
+            1 => MultiPool::Right(pool.clone()),
+            _ => MultiPool::Left(pool.clone()),
+        }));
+        p
+    }
+
+    #[tokio::test]
+    async fn cross_dex_swap_per_leg() {
+        let dex = MultiDex::new(MockDex::new(10), MockDex::new(1000));
+        let path = cross_dex_cycle();
+        let amount_in = BigUint::from(1_000u32);
+        let slippage = Ratio::new(1u32.into(), 100u32.into());
+
+        let steps = make_steps(&slippage, &amount_in, &BigUint::ZERO, &path).unwrap();
+        let bodies = dex
+            .make_body(1, MsgAddress::NULL, Asset::Native, amount_in, steps)
+            .await
+            .unwrap();
+
+        let legs: Vec<_> = bodies
+            .into_iter()
+            .map(|body| {
+                let (MultiBody::Left(swap) | MultiBody::Right(swap)) = body.body;
+                (swap.asset_in, swap.amount_in, limits(swap.step), body.value)
+            })
+            .collect();
+        // each leg swaps min output of the previous one
+        assert_eq!(
+            legs,
+            [
+                (Asset::Native, 1000u32, vec![1794u32], 1010u32),
+                (JETTON_A, 1794, vec![1405], 1000),
+                (JETTON_B, 1405, vec![1747], 10),
+            ]
+            .map(|(asset_in, amount_in, limits, value)| (
+                asset_in,
+                amount_in.into(),
+                limits.into_iter().map(Into::into).collect(),
+                value.into(),
+            )),
+        );
+    }
+
+    /// Value of each message in the transfer
+    fn values(transfer: &WalletTransfer) -> Vec<BigUint> {
+        transfer
+            .messages()
+            .iter()
+            .map(|op| match &op.message.info {
+                CommonMsgInfo::Internal(info) => info.value.grams.clone(),
+                _ => unreachable!("swaps are sent by internal messages"),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn bundle_mixes_dexes() {
+        let dex = MultiDex::new(MockDex::new(10), MockDex::new(1000));
+        let amount_in = BigUint::from(1_000u32);
+        let no_slippage = Ratio::from_integer(BigUint::ZERO);
+        let mut single_dex = SwapPath::new(Asset::Native);
+        single_dex.extend(cycle().iter_pools().cloned().map(MultiPool::Left));
+
+        let mut transfer = WalletTransfer::new(MAX_WALLET_MESSAGES);
+        let mut tracked = Vec::new();
+        for (query_id, path) in [(1, single_dex), (2, cross_dex_cycle())] {
+            let steps = make_steps(&no_slippage, &amount_in, &BigUint::ZERO, &path).unwrap();
+            let bodies = dex
+                .make_body(
+                    query_id,
+                    MsgAddress::NULL,
+                    Asset::Native,
+                    amount_in.clone(),
+                    steps,
+                )
+                .await
+                .unwrap();
+            tracked.push(push_swap(&mut transfer, bodies).unwrap());
+        }
+
+        // only the single-message swap can be tracked by query_id
+        assert_eq!(tracked, [Some((MsgAddress::NULL, 1030u32.into())), None]);
+        assert_eq!(
+            values(&transfer),
+            [1030u32, 1010, 1000, 10].map(BigUint::from)
+        );
+        assert_eq!(transfer.remaining(), 0);
+    }
+
+    /// Signs transfers by packing their seqno and number of messages
+    struct FakeSigner;
+
+    impl WalletSigner for FakeSigner {
+        fn version(&self) -> WalletVersion {
+            WalletVersion::V4R2
+        }
+
+        fn address(&self) -> MsgAddress {
+            MsgAddress::NULL
+        }
+
+        fn max_messages(&self) -> usize {
+            MAX_WALLET_MESSAGES
+        }
+
+        fn sign_transfer(
+            &self,
+            seqno: u32,
+            _ttl: Duration,
+            messages: Vec<WalletOpSendMessage>,
+        ) -> anyhow::Result<(Vec<u8>, DateTime<Utc>)> {
+            let mut boc = seqno.to_be_bytes().to_vec();
+            boc.push(messages.len() as u8);
+            Ok((boc, Utc::now()))
+        }
+    }
+
+    fn transfer(values: &[u32]) -> WalletTransfer {
+        let mut transfer = WalletTransfer::new(MAX_WALLET_MESSAGES);
+        for &value in values {
+            transfer
+                .push(
+                    SEND_MODE_PAY_FEES_SEPARATELY,
+                    make_message(MsgAddress::NULL, value.into(), ()),
+                )
+                .unwrap();
+        }
+        transfer
+    }
+
+    #[test]
+    fn watch_only_requires_dry_run() {
+        let watch_only = ArbitragerWallet::WatchOnly(MsgAddress::NULL);
+        assert!(check_wallet(&watch_only, false).is_err());
+        assert!(check_wallet(&watch_only, true).is_ok());
+
+        let signer = ArbitragerWallet::from(FakeSigner);
+        assert!(check_wallet(&signer, false).is_ok());
+        assert!(check_wallet(&signer, true).is_ok());
+    }
+
+    #[test]
+    fn dry_run_signs_external_message() {
+        let wallet = ArbitragerWallet::from(FakeSigner);
+        let (bocs, kind) = dry_run_bocs(&wallet, 7, transfer(&[1, 2])).unwrap();
+        assert_eq!(kind, "external");
+        assert_eq!(bocs, [vec![0u8, 0, 0, 7, 2]]);
+    }
+
+    #[test]
+    fn dry_run_packs_internal_messages_of_watch_only_wallet() {
+        let wallet = ArbitragerWallet::WatchOnly(MsgAddress::NULL);
+        let transfer = transfer(&[1, 2]);
+        let messages = transfer
+            .messages()
+            .iter()
+            .map(|op| op.message.to_cell().unwrap())
+            .collect::<Vec<_>>();
+
+        let (bocs, kind) = dry_run_bocs(&wallet, 7, transfer).unwrap();
+        assert_eq!(kind, "internal");
+        assert_eq!(bocs.len(), 2);
+        for (boc, message) in bocs.into_iter().zip(messages) {
+            let boc: BoC = unpack_bytes(boc).unwrap();
+            assert_eq!(**boc.single_root().unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn dry_run_pnl_skips_repeated_opportunity() {
+        let mut pnl = DryRunPnl::default();
+        let mut record = |asset, cycle: &str, amount_in: u32, profit: u32| {
+            pnl.record(asset, cycle.to_string(), amount_in.into(), profit.into())
+        };
+        record(Asset::Native, "a", 1_000, 10);
+        // pools stay unchanged, so that the same opportunity is found again
+        record(Asset::Native, "a", 1_000, 10);
+        record(Asset::Native, "a", 2_000, 15);
+        record(JETTON_A, "b", 1_000, 3);
+        record(Asset::Native, "a", 2_000, 15);
+
+        assert_eq!(pnl.trades, 4);
+        assert_eq!(pnl.profit[&Asset::Native], 40u32.into());
+        assert_eq!(pnl.profit[&JETTON_A], 3u32.into());
+        assert_eq!(pnl.last, Some(("a".to_string(), 2_000u32.into())));
+    }
+
+    /// Cost of the edge converting amounts with `rate`
+    fn cost(rate: f64) -> f64 {
+        -rate.log2()
+    }
+
+    #[test]
+    fn conversion_rate_fewest_hops() {
+        let mut g = G::new();
+        let [a, b, c, d, e] = [(); 5].map(|_| g.add_node(Asset::Native));
+        // directly and through c with a better rate
+        let direct = g.add_edge(a, b, cost(2.0));
+        g.add_edge(a, c, cost(4.0));
+        g.add_edge(c, b, cost(4.0));
+        // through b or c, where the latter gives a better rate
+        g.add_edge(b, d, cost(3.0));
+        g.add_edge(c, d, cost(2.0));
+
+        let usable = |_| true;
+        assert_eq!(conversion_rate(&g, a, a, usable), Some(1.0));
+        assert_eq!(conversion_rate(&g, a, b, usable), Some(2.0));
+        assert_eq!(conversion_rate(&g, a, d, usable), Some(8.0));
+        assert_eq!(conversion_rate(&g, a, e, usable), None);
+        // edges are directed
+        assert_eq!(conversion_rate(&g, b, a, usable), None);
+
+        assert_eq!(conversion_rate(&g, a, b, |e| e != direct), Some(16.0));
+    }
+
+    #[test]
+    fn sort_candidates_by_converted_profit() {
+        // profit of 100 in the common asset and of 60 in another one,
+        // which is worth twice as much, but is riskier
+        let mut candidates: Vec<Candidate<&str>> = vec![
+            ("common", 1000u32.into(), 1100u32.into(), 1.0, 0.0),
+            ("other", 1000u32.into(), 1060u32.into(), 2.0, 1.0),
+        ];
+        let order = |candidates: &[Candidate<&'static str>]| -> Vec<&str> {
+            candidates.iter().map(|(cycle, ..)| *cycle).collect()
+        };
+
+        sort_candidates(&mut candidates, false);
+        assert_eq!(order(&candidates), ["other", "common"]);
+        // 60 * 2 / (1 + 1) = 60
+        sort_candidates(&mut candidates, true);
+        assert_eq!(order(&candidates), ["common", "other"]);
+    }
+
+    fn planned_swap(asset_in: Asset, amount_in: u32, ton_value: u32) -> PlannedSwap {
+        PlannedSwap {
+            query_id: 0,
+            cycle: String::new(),
+            asset_in,
+            amount_in: amount_in.into(),
+            amount_out: amount_in.into(),
+            profit: BigUint::ZERO,
+            ton_value: ton_value.into(),
+            tracked: None,
+        }
+    }
+
+    #[test]
+    fn planned_swap_spends_attached_ton() {
+        // attached TON includes amount_in of TON swaps
+        let swap = planned_swap(Asset::Native, 1000, 1100);
+        assert_eq!(
+            swap.spent().collect::<Vec<_>>(),
+            [(Asset::Native, &BigUint::from(1000u32))],
+        );
+
+        let swap = planned_swap(JETTON_A, 1000, 300);
+        assert_eq!(
+            swap.spent().collect::<Vec<_>>(),
+            [
+                (JETTON_A, &BigUint::from(1000u32)),
+                (Asset::Native, &BigUint::from(300u32)),
+            ],
+        );
+    }
+}
@@ -1,18 +1,414 @@
-use aceton_core::Asset;
-use num::{rational::Ratio, BigUint};
-use serde::Deserialize;
+use core::time::Duration;
+use std::collections::HashSet;
 
+use aceton_core::{Asset, WeightedRiskModel};
+use aceton_utils::{DecimalFloatStrAsRatio, Percent};
+use anyhow::anyhow;
+use num::{rational::Ratio, BigUint, One, Zero};
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr, DurationSeconds};
 
+#[serde_as]
 #[derive(Deserialize)]
 pub struct ArbitragerConfig {
-    pub base_asset: Asset,
+    /// Assets to start cycles from, profits of which are compared in
+    /// units of the first one
+    pub base_assets: Vec<BaseAssetConfig>,
     pub max_length: Option<usize>,
-    // #[serde_as(as = "DecimalFloatStrAsRatio")]
-    // pub amount_in_balance_coef: Ratio<BigUint>,
+    /// Max part of the available balance to put into a single swap
+    #[serde_as(as = "DecimalFloatStrAsRatio")]
+    #[serde(default = "ArbitragerConfig::default_amount_in_balance_coef")]
+    pub amount_in_balance_coef: Ratio<BigUint>,
+    /// Amount of nanoTONs to always keep on the wallet when base asset
+    /// is native, unless overridden by its `keep_min`
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(default = "ArbitragerConfig::default_keep_min_ton")]
+    pub keep_min_ton: BigUint,
+    /// Min profit after gas, in base asset units, unless overridden by
+    /// its `min_profit`
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(default = "ArbitragerConfig::default_min_profit")]
+    pub min_profit: BigUint,
+    /// Min profit after gas relative to amount_in, in percents
+    #[serde_as(as = "Percent<DecimalFloatStrAsRatio>")]
+    #[serde(default = "ArbitragerConfig::default_min_profit_rate")]
+    pub min_profit_rate: Ratio<BigUint>,
+    /// Max allowed deviation of each hop's output from the expected one,
+    /// in percents
+    #[serde_as(as = "Percent<DecimalFloatStrAsRatio>")]
+    #[serde(default = "ArbitragerConfig::default_slippage")]
+    pub slippage: Ratio<BigUint>,
+    /// Seconds to wait for the outcome of a sent swap
+    #[serde_as(as = "DurationSeconds<u64>")]
+    #[serde(default = "ArbitragerConfig::default_track_timeout")]
+    pub track_timeout: Duration,
+    /// Max number of independent cycles to send in a single wallet
+    /// transfer, each of them takes one or more of its messages, which
+    /// number is limited by the wallet version
+    #[serde(default = "ArbitragerConfig::default_max_cycles_per_transfer")]
+    pub max_cycles_per_transfer: usize,
+    /// Max number of transfers sent but not yet processed by highload
+    /// wallet, seqno wallets always have at most one
+    #[serde(default = "ArbitragerConfig::default_max_in_flight")]
+    pub max_in_flight: usize,
+    /// How to find out which pools should be updated
+    #[serde(default)]
+    pub pool_updates: PoolUpdates,
+    /// How to look for profitable cycles
+    #[serde(default)]
+    pub cycle_search: CycleSearch,
+    /// Max number of cycles found on each iteration by `spfa` and
+    /// `tarjan` cycle searches
+    #[serde(default = "ArbitragerConfig::default_max_cycles")]
+    pub max_cycles: usize,
+    /// Cut off branches of `dfs` cycle search which can not become
+    /// negative within `max_length`
+    #[serde(default = "ArbitragerConfig::default_dfs_pruning")]
+    pub dfs_pruning: bool,
+    #[serde(default)]
+    pub risk: RiskConfig,
+    /// Run without sending any messages, only log what would have been
+    /// sent and track hypothetical profit
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 impl ArbitragerConfig {
-    pub fn amount_in_balance_coef(&self) -> Ratio<BigUint> {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.base_assets.is_empty() {
+            return Err(anyhow!("at least one base asset is required"));
+        }
+        let mut assets = HashSet::new();
+        for base in &self.base_assets {
+            if !assets.insert(base.asset) {
+                return Err(anyhow!("duplicate base asset: {}", base.asset));
+            }
+            if base
+                .amount_in_balance_coef
+                .as_ref()
+                .is_some_and(|coef| coef.is_zero() || *coef > Ratio::one())
+            {
+                return Err(anyhow!(
+                    "amount_in_balance_coef of {} must be in (0, 1]",
+                    base.asset
+                ));
+            }
+        }
+        if self.max_length.is_some_and(|max_length| max_length < 2) {
+            return Err(anyhow!("max_length must be at least 2"));
+        }
+        if self.cycle_search == CycleSearch::Incremental && self.max_length.is_none() {
+            return Err(anyhow!("incremental cycle_search requires max_length"));
+        }
+        if self.amount_in_balance_coef.is_zero() || self.amount_in_balance_coef > Ratio::one() {
+            return Err(anyhow!("amount_in_balance_coef must be in (0, 1]"));
+        }
+        if self.slippage >= Ratio::one() {
+            return Err(anyhow!("slippage must be less than 100%"));
+        }
+        if self.min_profit_rate >= Ratio::one() {
+            return Err(anyhow!("min_profit_rate must be less than 100%"));
+        }
+        if self.max_cycles_per_transfer == 0 {
+            return Err(anyhow!("max_cycles_per_transfer must be positive"));
+        }
+        if self.max_cycles == 0 {
+            return Err(anyhow!("max_cycles must be positive"));
+        }
+        if self.max_in_flight == 0 {
+            return Err(anyhow!("max_in_flight must be positive"));
+        }
+        if self.risk.max.is_some_and(|max| max.is_nan() || max < 0.0) {
+            return Err(anyhow!("risk.max must be non-negative"));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn base_asset(&self, asset: Asset) -> Option<&BaseAssetConfig> {
+        self.base_assets.iter().find(|base| base.asset == asset)
+    }
+
+    /// Max amount of the base asset to put into swaps out of its
+    /// `available` balance, `None` if the balance is too small
+    pub(crate) fn max_amount_in(
+        &self,
+        base: &BaseAssetConfig,
+        available: BigUint,
+    ) -> Option<BigUint> {
+        let keep_min = base
+            .keep_min
+            .as_ref()
+            .or((base.asset == Asset::Native).then_some(&self.keep_min_ton));
+        let available = match keep_min {
+            Some(keep_min) if available < *keep_min => return None,
+            Some(keep_min) => available - keep_min,
+            None => available,
+        };
+        let coef = base
+            .amount_in_balance_coef
+            .as_ref()
+            .unwrap_or(&self.amount_in_balance_coef);
+        Some((coef.clone() * available).to_integer())
+    }
+
+    pub(crate) fn min_profit(&self, asset: Asset) -> &BigUint {
+        self.base_asset(asset)
+            .and_then(|base| base.min_profit.as_ref())
+            .unwrap_or(&self.min_profit)
+    }
+
+    fn default_amount_in_balance_coef() -> Ratio<BigUint> {
         Ratio::new(7u32.into(), 10u32.into())
     }
+
+    fn default_keep_min_ton() -> BigUint {
+        // 2 TON
+        2_000_000_000u64.into()
+    }
+
+    fn default_min_profit() -> BigUint {
+        // 0.1 TON
+        100_000_000u64.into()
+    }
+
+    fn default_min_profit_rate() -> Ratio<BigUint> {
+        // 5%
+        Ratio::new(5u32.into(), 100u32.into())
+    }
+
+    fn default_slippage() -> Ratio<BigUint> {
+        // 1%
+        Ratio::new(1u32.into(), 100u32.into())
+    }
+
+    fn default_track_timeout() -> Duration {
+        Duration::from_secs(180)
+    }
+
+    fn default_max_cycles_per_transfer() -> usize {
+        1
+    }
+
+    fn default_max_in_flight() -> usize {
+        4
+    }
+
+    fn default_max_cycles() -> usize {
+        16
+    }
+
+    fn default_dfs_pruning() -> bool {
+        true
+    }
+}
+
+/// Base asset along with rules for its balance, which override common
+/// ones from [`ArbitragerConfig`]
+#[serde_as]
+#[derive(Deserialize)]
+pub struct BaseAssetConfig {
+    #[serde(flatten)]
+    pub asset: Asset,
+    #[serde_as(as = "Option<DecimalFloatStrAsRatio>")]
+    pub amount_in_balance_coef: Option<Ratio<BigUint>>,
+    /// Amount to always keep on the wallet, defaults to `keep_min_ton`
+    /// for native asset and to zero for others
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub keep_min: Option<BigUint>,
+    /// Min profit after gas, in units of this asset
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub min_profit: Option<BigUint>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolUpdates {
+    /// Fetch reserves of all pools on each iteration
+    #[default]
+    Poll,
+    /// Follow new blocks and fetch reserves only of pools which had
+    /// transactions in them
+    Blocks,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CycleSearch {
+    /// Search the whole graph from each base asset on each iteration
+    #[default]
+    Dfs,
+    /// Index all cycles through base assets up to `max_length` once
+    /// and re-evaluate only those through updated pools
+    Incremental,
+    /// Find edge-disjoint cycles one by one with queue-based Bellman-Ford
+    /// in polynomial time, so that `max_length` can be large or unset
+    Spfa,
+    /// The same as `spfa`, but with Tarjan's subtree disassembly, which
+    /// detects cycles earlier
+    Tarjan,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct RiskConfig {
+    /// Cycles with risk score above this threshold are skipped
+    pub max: Option<f64>,
+    /// Rank cycles by profit discounted by risk, i.e. `profit / (1 + risk)`,
+    /// instead of raw profit
+    pub rank: bool,
+    /// Parameters of the default risk model
+    #[serde(flatten)]
+    pub model: WeightedRiskModel,
+}
+
+#[cfg(test)]
+mod tests {
+    use tlb_ton::MsgAddress;
+
+    use super::*;
+
+    fn config() -> ArbitragerConfig {
+        ArbitragerConfig {
+            base_assets: vec![BaseAssetConfig {
+                asset: Asset::Native,
+                amount_in_balance_coef: None,
+                keep_min: None,
+                min_profit: None,
+            }],
+            max_length: Some(3),
+            amount_in_balance_coef: ArbitragerConfig::default_amount_in_balance_coef(),
+            keep_min_ton: ArbitragerConfig::default_keep_min_ton(),
+            min_profit: ArbitragerConfig::default_min_profit(),
+            min_profit_rate: ArbitragerConfig::default_min_profit_rate(),
+            slippage: ArbitragerConfig::default_slippage(),
+            track_timeout: ArbitragerConfig::default_track_timeout(),
+            max_cycles_per_transfer: ArbitragerConfig::default_max_cycles_per_transfer(),
+            max_in_flight: ArbitragerConfig::default_max_in_flight(),
+            pool_updates: PoolUpdates::default(),
+            cycle_search: CycleSearch::default(),
+            max_cycles: ArbitragerConfig::default_max_cycles(),
+            dfs_pruning: ArbitragerConfig::default_dfs_pruning(),
+            risk: RiskConfig::default(),
+            dry_run: false,
+        }
+    }
+
+    /// `percent / 100`
+    fn percent(percent: u32) -> Ratio<BigUint> {
+        Ratio::new(percent.into(), 100u32.into())
+    }
+
+    #[test]
+    fn validate_defaults() {
+        assert!(config().validate().is_ok());
+    }
+
+    #[test]
+    fn validate_amount_in_balance_coef() {
+        for (coef, valid) in [(0, false), (1, true), (99, true), (100, true), (101, false)] {
+            let mut cfg = config();
+            cfg.amount_in_balance_coef = percent(coef);
+            assert_eq!(cfg.validate().is_ok(), valid, "{coef}%");
+
+            let mut cfg = config();
+            cfg.base_assets[0].amount_in_balance_coef = Some(percent(coef));
+            assert_eq!(cfg.validate().is_ok(), valid, "base asset {coef}%");
+        }
+    }
+
+    fn jetton() -> Asset {
+        Asset::Jetton(MsgAddress::NULL)
+    }
+
+    fn base_asset(asset: Asset) -> BaseAssetConfig {
+        BaseAssetConfig {
+            asset,
+            amount_in_balance_coef: None,
+            keep_min: None,
+            min_profit: None,
+        }
+    }
+
+    #[test]
+    fn validate_base_assets() {
+        let mut cfg = config();
+        cfg.base_assets.clear();
+        assert!(cfg.validate().is_err());
+
+        let mut cfg = config();
+        cfg.base_assets.push(base_asset(jetton()));
+        assert!(cfg.validate().is_ok());
+        cfg.base_assets.push(base_asset(jetton()));
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn max_amount_in_defaults() {
+        let cfg = config();
+        let native = &cfg.base_assets[0];
+        // 70% of what is left after 2 TON
+        assert_eq!(
+            cfg.max_amount_in(native, 12_000_000_000u64.into()),
+            Some(7_000_000_000u64.into()),
+        );
+        assert_eq!(
+            cfg.max_amount_in(native, 2_000_000_000u64.into()),
+            Some(BigUint::ZERO),
+        );
+        assert_eq!(cfg.max_amount_in(native, 1_999_999_999u64.into()), None);
+        // nothing is kept of jettons by default
+        assert_eq!(
+            cfg.max_amount_in(&base_asset(jetton()), 1000u32.into()),
+            Some(700u32.into()),
+        );
+    }
+
+    #[test]
+    fn max_amount_in_overrides() {
+        let cfg = config();
+        let mut native = base_asset(Asset::Native);
+        native.keep_min = Some(1000u32.into());
+        native.amount_in_balance_coef = Some(percent(50));
+        assert_eq!(
+            cfg.max_amount_in(&native, 3000u32.into()),
+            Some(1000u32.into())
+        );
+        assert_eq!(cfg.max_amount_in(&native, 999u32.into()), None);
+
+        let mut base = base_asset(jetton());
+        base.keep_min = Some(100u32.into());
+        assert_eq!(
+            cfg.max_amount_in(&base, 1100u32.into()),
+            Some(700u32.into())
+        );
+        assert_eq!(cfg.max_amount_in(&base, 99u32.into()), None);
+    }
+
+    #[test]
+    fn min_profit_override() {
+        let mut cfg = config();
+        let mut base = base_asset(jetton());
+        base.min_profit = Some(5u32.into());
+        cfg.base_assets.push(base);
+        assert_eq!(cfg.min_profit(Asset::Native), &cfg.min_profit);
+        assert_eq!(cfg.min_profit(jetton()), &BigUint::from(5u32));
+    }
+
+    #[test]
+    fn validate_slippage() {
+        for (slippage, valid) in [(0, true), (1, true), (99, true), (100, false), (101, false)] {
+            let mut cfg = config();
+            cfg.slippage = percent(slippage);
+            assert_eq!(cfg.validate().is_ok(), valid, "{slippage}%");
+        }
+    }
+
+    #[test]
+    fn validate_min_profit_rate() {
+        for (rate, valid) in [(0, true), (1, true), (99, true), (100, false), (101, false)] {
+            let mut cfg = config();
+            cfg.min_profit_rate = percent(rate);
+            assert_eq!(cfg.validate().is_ok(), valid, "{rate}%");
+        }
+    }
 }
@@ -0,0 +1,448 @@
+use core::time::Duration;
+use std::{
+    collections::VecDeque,
+    sync::atomic::{self, AtomicU64},
+};
+
+use aceton_core::ton_utils::{
+    contract::TonContract,
+    wallet::{HighloadWalletV3I, WalletI},
+};
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use tlb_ton::MsgAddress;
+use tonlibjson_client::ton::TonClient;
+use tracing::{instrument, warn};
+
+use crate::{
+    HighloadQueryId, HighloadWalletV3ExternalBody, HighloadWalletV3SignBody, OperationResultExt,
+    WalletSigner, WalletTransfer,
+};
+
+/// Lifetime of external messages to seqno wallets
+pub(crate) const MESSAGE_TTL: Duration = Duration::from_secs(60);
+
+/// Delivers signed transfers to the wallet and checks whether they
+/// were processed
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Returns current wallet seqno, used only for seqno wallets
+    async fn seqno(&self) -> anyhow::Result<u32>;
+
+    /// Sends external message packed into BoC
+    async fn send(&self, boc: Vec<u8>) -> anyhow::Result<()>;
+
+    /// Returns whether transfer sent with given seqno (or query ID index
+    /// for highload wallets) was processed by the wallet
+    async fn is_processed(&self, seqno: u32) -> anyhow::Result<bool>;
+
+    /// Returns query ID index of the newest transfer processed by the
+    /// highload wallet, `None` if the wallet still remembers none of them
+    async fn last_query_index(&self) -> anyhow::Result<Option<u32>>;
+}
+
+/// [`Transport`] through liteservers
+pub struct TonTransport {
+    ton: TonClient,
+    wallet: TonContract,
+    highload: bool,
+}
+
+impl TonTransport {
+    pub fn new(ton: TonClient, wallet: MsgAddress, highload: bool) -> Self {
+        Self {
+            wallet: TonContract::new(ton.clone(), wallet),
+            ton,
+            highload,
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for TonTransport {
+    async fn seqno(&self) -> anyhow::Result<u32> {
+        self.wallet.seqno().await
+    }
+
+    async fn send(&self, boc: Vec<u8>) -> anyhow::Result<()> {
+        let tx_hash = self
+            .ton
+            .send_message_returning_hash(STANDARD.encode(boc).as_str())
+            .await?;
+        let decoded_tx_hash = STANDARD.decode(tx_hash)?;
+        warn!(tx.hash = hex::encode(decoded_tx_hash), "sent tx");
+        Ok(())
+    }
+
+    async fn is_processed(&self, seqno: u32) -> anyhow::Result<bool> {
+        if !self.highload {
+            return Ok(self.seqno().await? > seqno);
+        }
+        self.wallet
+            .processed(HighloadQueryId::from_index(seqno).packed(), false)
+            .await
+    }
+
+    async fn last_query_index(&self) -> anyhow::Result<Option<u32>> {
+        // processed query IDs are kept for up to two timeouts
+        let timeout = self.wallet.get_timeout().await?;
+        let since = (Utc::now().timestamp() as u64).saturating_sub(2 * timeout as u64);
+        let Some(tx) = self
+            .wallet
+            .last_transaction_matching(since, |tx| {
+                tx.in_msg.as_ref().is_some_and(|msg| msg.src.is_none())
+            })
+            .await?
+        else {
+            return Ok(None);
+        };
+        let body = tx
+            .in_msg
+            .and_then(|msg| msg.body)
+            .context("external message without body")?;
+        let body: HighloadWalletV3ExternalBody<HighloadWalletV3SignBody> = body.parse_fully()?;
+        Ok(Some(body.body.query_id.index()))
+    }
+}
+
+/// Transfer sent to the wallet which was not processed yet
+#[derive(Debug)]
+pub struct PendingSend<P> {
+    pub seqno: u32,
+    pub expire_at: DateTime<Utc>,
+    /// Arbitrary data returned back along with the outcome
+    pub payload: P,
+}
+
+#[derive(Debug)]
+pub enum SendOutcome<P> {
+    /// Transfer was processed by the wallet
+    Confirmed(PendingSend<P>),
+    /// Transfer expired before being processed, so that it would never be
+    Expired(PendingSend<P>),
+}
+
+/// Sends transfers from the wallet without waiting for previous ones to
+/// be processed, as long as the wallet allows to.
+///
+/// Seqno wallets allow only one transfer in flight, since the next one
+/// needs incremented seqno. Highload wallets identify transfers by
+/// sequentially allocated query IDs, so that many of them can be in
+/// flight at once.
+pub struct Executor<T, P> {
+    transport: T,
+    highload: bool,
+    /// Index of the next query ID of highload wallet transfer
+    query_ids: AtomicU64,
+    max_in_flight: usize,
+    /// In order of sending
+    pending: VecDeque<PendingSend<P>>,
+}
+
+impl<T, P> Executor<T, P>
+where
+    T: Transport,
+{
+    pub async fn new(transport: T, highload: bool, max_in_flight: usize) -> anyhow::Result<Self> {
+        let max_in_flight = if highload { max_in_flight } else { 1 };
+        // highload wallet rejects query IDs it has processed recently,
+        // including ones sent before restart, so that allocation continues
+        // after the newest of them. Transfers which were still in flight
+        // could be processed later, so that their IDs are skipped as well.
+        let first_query_index = if highload {
+            transport
+                .last_query_index()
+                .await
+                .context("last query ID")?
+                .map_or(0, |index| index as u64 + 1 + max_in_flight as u64)
+        } else {
+            0
+        };
+        Ok(Self {
+            transport,
+            highload,
+            query_ids: AtomicU64::new(first_query_index),
+            max_in_flight,
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Payloads of transfers in flight
+    pub fn in_flight(&self) -> impl Iterator<Item = &P> {
+        self.pending.iter().map(|send| &send.payload)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.pending.len() >= self.max_in_flight
+    }
+
+    /// Returns seqno for the next transfer, which is the index of the
+    /// next query ID for highload wallets
+    pub async fn next_seqno(&self) -> anyhow::Result<u32> {
+        if self.highload {
+            return Ok(self.query_ids.fetch_add(1, atomic::Ordering::SeqCst) as u32);
+        }
+        self.transport.seqno().await
+    }
+
+    /// Signs and sends the transfer without waiting for it to be
+    /// processed, returns its seqno
+    #[instrument(skip_all)]
+    pub async fn submit(
+        &mut self,
+        wallet: &dyn WalletSigner,
+        transfer: WalletTransfer,
+        payload: P,
+    ) -> anyhow::Result<u32> {
+        if self.is_full() {
+            return Err(anyhow!(
+                "too many transfers in flight: {}",
+                self.pending.len()
+            ));
+        }
+        let seqno = self.next_seqno().await?;
+        // signing would fail the same way on retry
+        let (boc, expire_at) = wallet
+            .sign_transfer(seqno, MESSAGE_TTL, transfer.into_messages())
+            .fatal()?;
+        self.transport.send(boc).await?;
+        self.pending.push_back(PendingSend {
+            seqno,
+            expire_at,
+            payload,
+        });
+        Ok(seqno)
+    }
+
+    /// Checks transfers in flight and returns those which are either
+    /// confirmed or expired
+    #[instrument(skip_all, fields(in_flight = self.pending.len()))]
+    pub async fn poll(&mut self) -> anyhow::Result<Vec<SendOutcome<P>>> {
+        let mut outcomes = Vec::new();
+        let mut still_pending = VecDeque::with_capacity(self.pending.len());
+        while let Some(send) = self.pending.pop_front() {
+            // status must be fetched after expiration to be sure that
+            // the message would not be accepted later
+            let expired = Utc::now() > send.expire_at;
+            match self.transport.is_processed(send.seqno).await {
+                Ok(true) => outcomes.push(SendOutcome::Confirmed(send)),
+                Ok(false) if expired => outcomes.push(SendOutcome::Expired(send)),
+                Ok(false) => still_pending.push_back(send),
+                Err(err) => {
+                    // keep the rest in flight, they will be checked again
+                    still_pending.push_back(send);
+                    still_pending.extend(self.pending.drain(..));
+                    self.pending = still_pending;
+                    return Err(err);
+                }
+            }
+        }
+        self.pending = still_pending;
+        Ok(outcomes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+    };
+
+    use chrono::TimeDelta;
+    use ton_contracts::wallet::WalletOpSendMessage;
+
+    use crate::{WalletVersion, MAX_WALLET_MESSAGES};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct FakeTransport {
+        seqno: Mutex<u32>,
+        sent: Mutex<Vec<Vec<u8>>>,
+        processed: Mutex<HashSet<u32>>,
+        last_query_index: Option<u32>,
+    }
+
+    impl FakeTransport {
+        fn process(&self, seqno: u32) {
+            self.processed.lock().unwrap().insert(seqno);
+            let mut current = self.seqno.lock().unwrap();
+            *current = (*current).max(seqno + 1);
+        }
+    }
+
+    #[async_trait]
+    impl Transport for Arc<FakeTransport> {
+        async fn seqno(&self) -> anyhow::Result<u32> {
+            Ok(*self.seqno.lock().unwrap())
+        }
+
+        async fn send(&self, boc: Vec<u8>) -> anyhow::Result<()> {
+            self.sent.lock().unwrap().push(boc);
+            Ok(())
+        }
+
+        async fn is_processed(&self, seqno: u32) -> anyhow::Result<bool> {
+            Ok(self.processed.lock().unwrap().contains(&seqno))
+        }
+
+        async fn last_query_index(&self) -> anyhow::Result<Option<u32>> {
+            Ok(self.last_query_index)
+        }
+    }
+
+    /// Signs transfers by packing their seqno
+    struct FakeWallet {
+        version: WalletVersion,
+        ttl: TimeDelta,
+    }
+
+    impl WalletSigner for FakeWallet {
+        fn version(&self) -> WalletVersion {
+            self.version
+        }
+
+        fn address(&self) -> MsgAddress {
+            MsgAddress::NULL
+        }
+
+        fn max_messages(&self) -> usize {
+            MAX_WALLET_MESSAGES
+        }
+
+        fn sign_transfer(
+            &self,
+            seqno: u32,
+            _ttl: Duration,
+            _messages: Vec<WalletOpSendMessage>,
+        ) -> anyhow::Result<(Vec<u8>, DateTime<Utc>)> {
+            Ok((seqno.to_be_bytes().into(), Utc::now() + self.ttl))
+        }
+    }
+
+    /// Highload wallet has processed query ID 7 before
+    async fn executor(
+        highload: bool,
+        max_in_flight: usize,
+    ) -> (
+        Arc<FakeTransport>,
+        Executor<Arc<FakeTransport>, &'static str>,
+    ) {
+        let transport = Arc::new(FakeTransport {
+            last_query_index: Some(7),
+            ..Default::default()
+        });
+        let executor = Executor::new(transport.clone(), highload, max_in_flight)
+            .await
+            .unwrap();
+        (transport, executor)
+    }
+
+    fn wallet(version: WalletVersion, ttl: TimeDelta) -> FakeWallet {
+        FakeWallet { version, ttl }
+    }
+
+    #[tokio::test]
+    async fn highload_sends_concurrently() {
+        let (transport, mut executor) = executor(true, 2).await;
+        let wallet = wallet(WalletVersion::HighloadV3, TimeDelta::hours(1));
+
+        let first = executor
+            .submit(&wallet, WalletTransfer::new(1), "first")
+            .await
+            .unwrap();
+        let second = executor
+            .submit(&wallet, WalletTransfer::new(1), "second")
+            .await
+            .unwrap();
+        assert_eq!((first, second), (10, 11));
+        assert_eq!(transport.sent.lock().unwrap().len(), 2);
+        assert!(executor.is_full());
+        assert!(executor
+            .submit(&wallet, WalletTransfer::new(1), "third")
+            .await
+            .is_err());
+
+        transport.process(second);
+        let outcomes = executor.poll().await.unwrap();
+        assert!(matches!(
+            outcomes.as_slice(),
+            [SendOutcome::Confirmed(PendingSend { seqno: 11, .. })]
+        ));
+        assert_eq!(executor.in_flight().copied().collect::<Vec<_>>(), ["first"]);
+        assert!(!executor.is_full());
+    }
+
+    #[tokio::test]
+    async fn highload_skips_recent_query_ids() {
+        // after the last processed one and those which could be in flight
+        let (_transport, executor) = executor(true, 2).await;
+        assert_eq!(executor.next_seqno().await.unwrap(), 10);
+
+        let executor: Executor<_, ()> = Executor::new(Arc::new(FakeTransport::default()), true, 2)
+            .await
+            .unwrap();
+        assert_eq!(executor.next_seqno().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn seqno_wallet_sends_one_at_a_time() {
+        let (transport, mut executor) = executor(false, 4).await;
+        let wallet = wallet(WalletVersion::V4R2, TimeDelta::minutes(1));
+        *transport.seqno.lock().unwrap() = 5;
+
+        let seqno = executor
+            .submit(&wallet, WalletTransfer::new(1), "swap")
+            .await
+            .unwrap();
+        assert_eq!(seqno, 5);
+        assert!(executor.is_full());
+
+        assert!(executor.poll().await.unwrap().is_empty());
+        transport.process(seqno);
+        assert!(matches!(
+            executor.poll().await.unwrap().as_slice(),
+            [SendOutcome::Confirmed(PendingSend {
+                payload: "swap",
+                ..
+            })]
+        ));
+        assert_eq!(executor.next_seqno().await.unwrap(), 6);
+    }
+
+    #[tokio::test]
+    async fn expired_transfers_are_dropped() {
+        let (transport, mut executor) = executor(true, 2).await;
+        let expired = wallet(WalletVersion::HighloadV3, TimeDelta::seconds(-1));
+        let alive = wallet(WalletVersion::HighloadV3, TimeDelta::hours(1));
+
+        let dropped = executor
+            .submit(&expired, WalletTransfer::new(1), "dropped")
+            .await
+            .unwrap();
+        let late = executor
+            .submit(&expired, WalletTransfer::new(1), "late")
+            .await
+            .unwrap();
+        // processed right before expiration, but seen only after it
+        transport.process(late);
+
+        let outcomes = executor.poll().await.unwrap();
+        assert!(matches!(
+            outcomes.as_slice(),
+            [
+                SendOutcome::Expired(PendingSend { seqno: s1, .. }),
+                SendOutcome::Confirmed(PendingSend { seqno: s2, .. }),
+            ] if *s1 == dropped && *s2 == late
+        ));
+
+        executor
+            .submit(&alive, WalletTransfer::new(1), "alive")
+            .await
+            .unwrap();
+        assert!(executor.poll().await.unwrap().is_empty());
+    }
+}
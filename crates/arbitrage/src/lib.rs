@@ -1,4 +1,11 @@
 mod arbitrager;
 mod config;
+mod executor;
+mod retry;
+mod tracker;
+mod transfer;
+mod wallet;
 
-pub use self::{arbitrager::*, config::*};
+pub use self::{
+    arbitrager::*, config::*, executor::*, retry::*, tracker::*, transfer::*, wallet::*,
+};
@@ -0,0 +1,227 @@
+use core::time::Duration;
+use std::collections::HashMap;
+
+use aceton_core::ton_utils::contract::TonContractError;
+use thiserror::Error as ThisError;
+
+/// How many times in a row an operation is retried after errors which may
+/// be deterministic, e.g. malformed data, before the main loop exits
+const MAX_RETRIES: u32 = 10;
+
+/// Error which cannot be fixed by retrying, such as invalid config or
+/// wallet, so that the main loop should exit
+#[derive(Debug, ThisError)]
+#[error(transparent)]
+pub struct Fatal(#[from] anyhow::Error);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Marked as [`Fatal`]
+    Fatal,
+    /// Get-method exited with non-zero exit code
+    Contract,
+    /// HTTP request failed
+    Network,
+    Timeout,
+    /// Anything else, mostly failed liteserver requests, but possibly a
+    /// deterministic failure, so that it is retried a limited number of
+    /// times
+    Other,
+}
+
+impl ErrorKind {
+    pub fn of(err: &anyhow::Error) -> Self {
+        for cause in err.chain() {
+            if cause.is::<Fatal>() {
+                return Self::Fatal;
+            }
+            if cause.is::<TonContractError>() {
+                return Self::Contract;
+            }
+            if cause.is::<reqwest::Error>() {
+                return Self::Network;
+            }
+            if cause.is::<tokio::time::error::Elapsed>() {
+                return Self::Timeout;
+            }
+        }
+        Self::Other
+    }
+
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, Self::Fatal)
+    }
+
+    /// Max number of consecutive retries of an operation failed with this
+    /// kind of error, `None` if it is retried until it succeeds
+    pub fn max_retries(&self) -> Option<u32> {
+        match self {
+            Self::Fatal => Some(0),
+            Self::Contract | Self::Other => Some(MAX_RETRIES),
+            Self::Network | Self::Timeout => None,
+        }
+    }
+}
+
+/// Error of a named operation of the main loop
+#[derive(Debug, ThisError)]
+#[error("{operation} failed")]
+pub struct OperationError {
+    pub operation: &'static str,
+    pub source: anyhow::Error,
+}
+
+impl OperationError {
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::of(&self.source)
+    }
+}
+
+pub trait OperationResultExt<T> {
+    /// Names the operation which result is this
+    fn op(self, operation: &'static str) -> Result<T, OperationError>;
+
+    /// Marks error as [`Fatal`]
+    fn fatal(self) -> anyhow::Result<T>;
+}
+
+impl<T, E> OperationResultExt<T> for Result<T, E>
+where
+    E: Into<anyhow::Error>,
+{
+    fn op(self, operation: &'static str) -> Result<T, OperationError> {
+        self.map_err(|err| OperationError {
+            operation,
+            source: err.into(),
+        })
+    }
+
+    fn fatal(self) -> anyhow::Result<T> {
+        self.map_err(|err| Fatal(err.into()).into())
+    }
+}
+
+/// Exponential backoff tracked separately for each operation
+pub struct Backoff {
+    min: Duration,
+    max: Duration,
+    /// operation -> number of consecutive failures
+    failures: HashMap<&'static str, u32>,
+}
+
+impl Backoff {
+    pub fn new(min: Duration, max: Duration) -> Self {
+        Self {
+            min,
+            max,
+            failures: HashMap::new(),
+        }
+    }
+
+    /// Records a failure of the operation and returns how long to wait
+    /// before retrying it
+    pub fn failed(&mut self, operation: &'static str) -> Duration {
+        let failures = self.failures.entry(operation).or_default();
+        let delay = self
+            .min
+            .saturating_mul(1u32 << (*failures).min(16))
+            .min(self.max);
+        *failures += 1;
+        delay
+    }
+
+    /// Records a failure of the operation and returns how long to wait
+    /// before retrying it, or `None` if it should not be retried anymore
+    pub fn retry(&mut self, err: &OperationError) -> Option<Duration> {
+        if err
+            .kind()
+            .max_retries()
+            .is_some_and(|max| self.failures(err.operation) >= max)
+        {
+            return None;
+        }
+        Some(self.failed(err.operation))
+    }
+
+    /// Number of consecutive failures of the operation
+    pub fn failures(&self, operation: &str) -> u32 {
+        self.failures.get(operation).copied().unwrap_or_default()
+    }
+
+    /// Forgets failures of all operations after the whole iteration
+    /// succeeded
+    pub fn reset(&mut self) {
+        self.failures.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Context};
+
+    use super::*;
+
+    #[test]
+    fn backoff_grows_per_operation() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(5));
+        assert_eq!(backoff.failed("send"), Duration::from_secs(1));
+        assert_eq!(backoff.failed("send"), Duration::from_secs(2));
+        assert_eq!(backoff.failed("update_pools"), Duration::from_secs(1));
+        assert_eq!(backoff.failed("send"), Duration::from_secs(4));
+        assert_eq!(backoff.failed("send"), Duration::from_secs(5));
+        assert_eq!(backoff.failures("send"), 4);
+
+        backoff.reset();
+        assert_eq!(backoff.failed("send"), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn classifies_errors() {
+        let fatal: anyhow::Result<()> = Err(anyhow!("watch-only wallet")).fatal();
+        let fatal = fatal.context("send").op("send").unwrap_err();
+        assert_eq!(fatal.kind(), ErrorKind::Fatal);
+
+        let other = Err::<(), _>(anyhow!("liteserver timeout"))
+            .op("update_pools")
+            .unwrap_err();
+        assert_eq!(other.kind(), ErrorKind::Other);
+        assert!(!other.kind().is_fatal());
+    }
+
+    #[test]
+    fn fatal_is_not_retried() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(5));
+        let fatal = Err::<(), _>(anyhow!("watch-only wallet"))
+            .fatal()
+            .op("send")
+            .unwrap_err();
+        assert_eq!(backoff.retry(&fatal), None);
+    }
+
+    #[test]
+    fn deterministic_failure_terminates() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(5));
+        let err = Err::<(), _>(anyhow!("invalid pool data"))
+            .op("update_pools")
+            .unwrap_err();
+        let retries = std::iter::from_fn(|| backoff.retry(&err)).take(100).count();
+        assert_eq!(retries, MAX_RETRIES as usize);
+
+        // succeeded iteration restores the budget
+        backoff.reset();
+        assert_eq!(backoff.retry(&err), Some(Duration::from_secs(1)));
+    }
+
+    #[tokio::test]
+    async fn timeout_is_retried_until_success() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(5));
+        let err = tokio::time::timeout(Duration::ZERO, std::future::pending::<()>())
+            .await
+            .op("seqno")
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Timeout);
+        for _ in 0..100 {
+            assert!(backoff.retry(&err).is_some());
+        }
+    }
+}
@@ -0,0 +1,589 @@
+use core::{fmt, time::Duration};
+
+use std::sync::Arc;
+
+use aceton_core::{
+    ton_utils::transactions::{AccountTransactionsI, TransactionMessage},
+    Asset, Dex, DexPayout,
+};
+use num::{BigInt, BigUint, ToPrimitive};
+use tlb::{BitReaderExt, Cell};
+use tlb_ton::MsgAddress;
+use tokio::time::{self, Instant};
+use tracing::{info, instrument, warn};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Prefix of bodies of bounced messages
+const BOUNCED_TAG: u32 = 0xffffffff;
+
+/// Swap sent to the DEX which outcome is not known yet
+#[derive(Debug, Clone)]
+pub struct PendingSwap {
+    pub query_id: u64,
+    /// Logical time of the last wallet transaction before the swap
+    /// was sent
+    pub after_lt: u64,
+    pub asset_in: Asset,
+    pub amount_in: BigUint,
+    pub estimated_amount_out: BigUint,
+    /// Recepient of the message sent from the wallet
+    pub dst: MsgAddress,
+    /// Total nanoTONs attached to the message sent from the wallet
+    pub value: BigUint,
+}
+
+impl PendingSwap {
+    /// Profit in base asset units as it was estimated before sending
+    pub fn estimated_profit(&self) -> BigInt {
+        let mut profit =
+            BigInt::from(self.estimated_amount_out.clone()) - BigInt::from(self.amount_in.clone());
+        if self.asset_in == Asset::Native {
+            // the rest of value is gas
+            profit -= BigInt::from(&self.value - &self.amount_in);
+        }
+        profit
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SwapOutcome {
+    /// All pools' limits were met and the output was paid out
+    Fulfilled { amount_out: BigUint },
+    /// One of pools' limits was not met, so that an intermediate
+    /// asset was paid out
+    Rejected { asset: Asset, amount_out: BigUint },
+    /// Message from the wallet bounced
+    Bounced { refund: BigUint },
+    /// Nothing was received until timeout
+    Unknown,
+}
+
+impl SwapOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Fulfilled { .. } => "fulfilled",
+            Self::Rejected { .. } => "rejected",
+            Self::Bounced { .. } => "bounced",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for SwapOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SwapReport {
+    pub swap: PendingSwap,
+    pub outcome: SwapOutcome,
+    /// Profit in base asset units, `None` if the outcome is unknown.
+    /// For native base asset, all TONs spent on gas are counted.
+    pub realized_profit: Option<BigInt>,
+}
+
+impl SwapReport {
+    fn new(swap: PendingSwap, outcome: SwapOutcome) -> Self {
+        // what was paid out for what was spent on the swap
+        let profit = |amount_out: &BigUint| match swap.asset_in {
+            // all TONs attached to the message are spent
+            Asset::Native => BigInt::from(amount_out.clone()) - BigInt::from(swap.value.clone()),
+            _ => BigInt::from(amount_out.clone()) - BigInt::from(swap.amount_in.clone()),
+        };
+        let realized_profit = match &outcome {
+            SwapOutcome::Fulfilled { amount_out } => Some(profit(amount_out)),
+            SwapOutcome::Rejected { asset, amount_out } if *asset == swap.asset_in => {
+                Some(profit(amount_out))
+            }
+            // intermediate asset can not be compared with the spent one
+            SwapOutcome::Rejected { .. } => None,
+            SwapOutcome::Bounced { refund } if swap.asset_in == Asset::Native => {
+                Some(profit(refund))
+            }
+            // jettons are returned to the wallet as is
+            SwapOutcome::Bounced { .. } => Some(BigInt::ZERO),
+            SwapOutcome::Unknown => None,
+        };
+        Self {
+            swap,
+            outcome,
+            realized_profit,
+        }
+    }
+
+    /// Emits the report as a log event and metrics
+    pub fn emit(&self) {
+        let estimated_profit = self.swap.estimated_profit();
+        let outcome = self.outcome.as_str();
+        info!(
+            query_id = self.swap.query_id,
+            asset_in = %self.swap.asset_in,
+            amount_in = %self.swap.amount_in,
+            %estimated_profit,
+            realized_profit = ?self.realized_profit,
+            %outcome,
+            "swap finished",
+        );
+
+        info!(monotonic_counter.arbitrage_swaps = 1u64, outcome);
+        if let Some(realized_profit) = &self.realized_profit {
+            info!(
+                histogram.arbitrage_realized_profit = realized_profit.to_f64().unwrap(),
+                outcome,
+            );
+            info!(
+                histogram.arbitrage_profit_estimate_error =
+                    (realized_profit - &estimated_profit).to_f64().unwrap(),
+                outcome,
+            );
+        }
+    }
+}
+
+/// Swaps sent in a single wallet transfer, so that they share the same
+/// seqno
+#[derive(Debug, Clone)]
+pub struct SwapGroup {
+    pub seqno: u32,
+    pub swaps: Vec<PendingSwap>,
+}
+
+/// Reports of all swaps in the group
+#[derive(Debug, Clone)]
+pub struct GroupReport {
+    pub seqno: u32,
+    pub reports: Vec<SwapReport>,
+}
+
+impl GroupReport {
+    /// Emits each swap report along with the total of the group
+    pub fn emit(&self) {
+        for report in &self.reports {
+            report.emit();
+        }
+        if self.reports.len() < 2 {
+            return;
+        }
+        let realized_profit = self
+            .reports
+            .iter()
+            .map(|report| report.realized_profit.clone())
+            .sum::<Option<BigInt>>();
+        info!(
+            seqno = self.seqno,
+            swaps = self.reports.len(),
+            realized_profit = ?realized_profit,
+            "swap group finished",
+        );
+    }
+}
+
+/// Follows swaps sent from the wallet by polling its transactions until
+/// the DEX reports the result back
+pub struct SwapTracker<D, W> {
+    dex: Arc<D>,
+    wallet: W,
+    timeout: Duration,
+}
+
+impl<D, W> SwapTracker<D, W>
+where
+    D: Dex,
+    W: AccountTransactionsI,
+{
+    pub fn new(dex: Arc<D>, wallet: W, timeout: Duration) -> Self {
+        Self {
+            dex,
+            wallet,
+            timeout,
+        }
+    }
+
+    pub async fn last_transaction_lt(&self) -> anyhow::Result<u64> {
+        self.wallet.last_transaction_lt().await
+    }
+
+    #[instrument(skip_all, fields(swap.query_id = swap.query_id))]
+    pub async fn track(&self, swap: PendingSwap) -> anyhow::Result<SwapReport> {
+        let mut reports = self.track_swaps(vec![swap]).await?;
+        Ok(reports.remove(0))
+    }
+
+    /// Tracks all swaps of the group at once, reports are in the same
+    /// order as swaps
+    #[instrument(skip_all, fields(group.seqno = group.seqno))]
+    pub async fn track_group(&self, group: SwapGroup) -> anyhow::Result<GroupReport> {
+        Ok(GroupReport {
+            seqno: group.seqno,
+            reports: self.track_swaps(group.swaps).await?,
+        })
+    }
+
+    async fn track_swaps(&self, swaps: Vec<PendingSwap>) -> anyhow::Result<Vec<SwapReport>> {
+        let mut after_lt = swaps.iter().map(|swap| swap.after_lt).min().unwrap_or(0);
+        let deadline = Instant::now() + self.timeout;
+        let mut outcomes: Vec<Option<SwapOutcome>> = vec![None; swaps.len()];
+        loop {
+            for tx in self.wallet.transactions_after(after_lt).await? {
+                after_lt = tx.lt;
+                let Some(msg) = tx.in_msg else {
+                    continue;
+                };
+                let payout = match self.dex.parse_payout(&msg).await {
+                    Ok(payout) => payout,
+                    Err(err) => {
+                        warn!(tx.lt, error = format!("{err:#}"), "skipping invalid payout");
+                        continue;
+                    }
+                };
+                for (swap, outcome) in swaps.iter().zip(&mut outcomes) {
+                    if outcome.is_some() {
+                        continue;
+                    }
+                    if let Some(o) = match_outcome(swap, &msg, payout.as_ref()) {
+                        *outcome = Some(o);
+                        break;
+                    }
+                }
+            }
+            if outcomes.iter().all(Option::is_some) {
+                break;
+            }
+
+            if Instant::now() >= deadline {
+                warn!("no swap outcome until timeout");
+                break;
+            }
+            time::sleep(POLL_INTERVAL).await;
+        }
+
+        Ok(swaps
+            .into_iter()
+            .zip(outcomes)
+            .map(|(swap, outcome)| SwapReport::new(swap, outcome.unwrap_or(SwapOutcome::Unknown)))
+            .collect())
+    }
+}
+
+/// Outcome of `swap` if `msg` received by the wallet reports it,
+/// where `payout` is parsed from `msg` by the DEX
+fn match_outcome(
+    swap: &PendingSwap,
+    msg: &TransactionMessage,
+    payout: Option<&DexPayout>,
+) -> Option<SwapOutcome> {
+    let body = msg.body.as_deref()?;
+
+    if msg.src == Some(swap.dst) && bounced_query_id(body) == Some(swap.query_id) {
+        return Some(SwapOutcome::Bounced {
+            refund: msg.value.clone(),
+        });
+    }
+
+    let payout = payout?;
+    if payout.query_id != swap.query_id {
+        return None;
+    }
+    let amount_out = payout.amount.clone().unwrap_or_else(|| msg.value.clone());
+    Some(if payout.fulfilled {
+        // cycles end with the asset they start from
+        if payout.asset != swap.asset_in {
+            warn!(asset = %payout.asset, "fulfilled swap paid out in another asset");
+            return None;
+        }
+        SwapOutcome::Fulfilled { amount_out }
+    } else {
+        SwapOutcome::Rejected {
+            asset: payout.asset,
+            amount_out,
+        }
+    })
+}
+
+/// Query ID of the bounced message, which body keeps the first 256 bits
+/// of the original one, i.e. `op:uint32 query_id:uint64`
+fn bounced_query_id(body: &Cell) -> Option<u64> {
+    let mut parser = body.parser();
+    if parser.unpack::<u32>().ok()? != BOUNCED_TAG {
+        return None;
+    }
+    let _op: u32 = parser.unpack().ok()?;
+    parser.unpack().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use aceton_core::{
+        testing::{MockBody, MockPool},
+        ton_utils::transactions::Transaction,
+        DexBody, DexPool, SwapPath,
+    };
+    use anyhow::anyhow;
+    use async_trait::async_trait;
+    use tlb::BitWriterExt;
+
+    use super::*;
+
+    const DEX: MsgAddress = MsgAddress {
+        workchain_id: 0,
+        address: [1; 32],
+    };
+    const WALLET: MsgAddress = MsgAddress {
+        workchain_id: 0,
+        address: [2; 32],
+    };
+    const JETTON: Asset = Asset::Jetton(MsgAddress {
+        workchain_id: 0,
+        address: [3; 32],
+    });
+
+    /// Pays out from [`DEX`] with
+    /// `payout#_ query_id:uint64 fulfilled:Bool jetton:(Maybe uint64)`,
+    /// where jetton amount is set if [`JETTON`] is paid out instead of TONs.
+    /// Has no pools, since swaps are only tracked.
+    struct FakeDex;
+
+    #[async_trait]
+    impl Dex for FakeDex {
+        type Pool = MockPool;
+        type Body = MockBody;
+
+        async fn get_pools(&self) -> anyhow::Result<Vec<Self::Pool>> {
+            Ok(Vec::new())
+        }
+
+        async fn update_pool(&self, _pool: &mut Self::Pool) -> anyhow::Result<bool> {
+            Ok(false)
+        }
+
+        fn estimate_gas(&self, _path: &SwapPath<&Self::Pool>) -> BigUint {
+            BigUint::ZERO
+        }
+
+        async fn make_body(
+            &self,
+            _query_id: u64,
+            _sender: MsgAddress,
+            _asset_in: Asset,
+            _amount_in: BigUint,
+            _steps: <Self::Pool as DexPool>::Step,
+        ) -> anyhow::Result<Vec<DexBody<Self::Body>>> {
+            Ok(Vec::new())
+        }
+
+        async fn parse_payout(
+            &self,
+            msg: &TransactionMessage,
+        ) -> anyhow::Result<Option<DexPayout>> {
+            let Some(body) = msg
+                .body
+                .as_deref()
+                .filter(|body| msg.src == Some(DEX) && bounced_query_id(body).is_none())
+            else {
+                return Ok(None);
+            };
+            let mut parser = body.parser();
+            let (Ok(query_id), Ok(fulfilled), Ok(jetton_amount)) = (
+                parser.unpack::<u64>(),
+                parser.unpack::<bool>(),
+                parser.unpack::<Option<u64>>(),
+            ) else {
+                return Err(anyhow!("invalid payout"));
+            };
+            Ok(Some(DexPayout {
+                query_id,
+                asset: if jetton_amount.is_some() {
+                    JETTON
+                } else {
+                    Asset::Native
+                },
+                fulfilled,
+                amount: jetton_amount.map(Into::into),
+            }))
+        }
+    }
+
+    struct FakeWallet(Vec<Transaction>);
+
+    #[async_trait]
+    impl AccountTransactionsI for FakeWallet {
+        async fn last_transaction_lt(&self) -> anyhow::Result<u64> {
+            Ok(self.0.last().map_or(0, |tx| tx.lt))
+        }
+
+        async fn transactions_after(&self, after_lt: u64) -> anyhow::Result<Vec<Transaction>> {
+            Ok(self
+                .0
+                .iter()
+                .filter(|tx| tx.lt > after_lt)
+                .cloned()
+                .collect())
+        }
+    }
+
+    fn received(lt: u64, src: MsgAddress, value: u64, body: Cell) -> Transaction {
+        Transaction {
+            lt,
+            utime: 0,
+            in_msg: Some(TransactionMessage {
+                src: Some(src),
+                dst: Some(WALLET),
+                value: value.into(),
+                body: Some(body.into()),
+            }),
+            out_msgs: Vec::new(),
+        }
+    }
+
+    fn payout(query_id: u64, fulfilled: bool, jetton_amount: Option<u64>) -> Cell {
+        let mut builder = Cell::builder();
+        builder
+            .pack(query_id)
+            .unwrap()
+            .pack(fulfilled)
+            .unwrap()
+            .pack(jetton_amount)
+            .unwrap();
+        builder.into_cell()
+    }
+
+    /// Bounce of a message with `op:uint32 query_id:uint64` body
+    fn bounced(query_id: u64) -> Cell {
+        let mut builder = Cell::builder();
+        builder
+            .pack(BOUNCED_TAG)
+            .unwrap()
+            .pack(0x0f8a7ea5u32)
+            .unwrap()
+            .pack(query_id)
+            .unwrap();
+        builder.into_cell()
+    }
+
+    fn swap(query_id: u64, asset_in: Asset) -> PendingSwap {
+        PendingSwap {
+            query_id,
+            after_lt: 0,
+            asset_in,
+            amount_in: 1_000u32.into(),
+            estimated_amount_out: 1_100u32.into(),
+            dst: DEX,
+            value: match asset_in {
+                Asset::Native => 1_200u32.into(),
+                _ => 200u32.into(),
+            },
+        }
+    }
+
+    async fn track(swaps: Vec<PendingSwap>, txs: Vec<Transaction>) -> Vec<SwapReport> {
+        SwapTracker::new(Arc::new(FakeDex), FakeWallet(txs), Duration::ZERO)
+            .track_group(SwapGroup { seqno: 1, swaps })
+            .await
+            .unwrap()
+            .reports
+    }
+
+    #[tokio::test]
+    async fn matches_payouts_by_query_id() {
+        let reports = track(
+            vec![swap(1, Asset::Native), swap(2, JETTON)],
+            vec![
+                // payout forged by someone else
+                received(1, WALLET, 5_000, payout(1, true, None)),
+                received(2, DEX, 1_300, payout(2, false, None)),
+                received(3, DEX, 1_500, payout(1, true, None)),
+            ],
+        )
+        .await;
+
+        assert_eq!(
+            reports[0].outcome,
+            SwapOutcome::Fulfilled {
+                amount_out: 1_500u32.into()
+            }
+        );
+        assert_eq!(reports[0].realized_profit, Some(300.into()));
+
+        // rejected after the first hop, so that intermediate TONs are
+        // paid out
+        assert_eq!(
+            reports[1].outcome,
+            SwapOutcome::Rejected {
+                asset: Asset::Native,
+                amount_out: 1_300u32.into()
+            }
+        );
+        assert_eq!(reports[1].realized_profit, None);
+    }
+
+    #[tokio::test]
+    async fn bounced_and_unknown() {
+        let reports = track(
+            vec![swap(1, Asset::Native), swap(2, Asset::Native)],
+            vec![
+                // payout of another swap
+                received(1, DEX, 1_500, payout(3, true, None)),
+                received(2, DEX, 1_150, bounced(2)),
+            ],
+        )
+        .await;
+
+        assert_eq!(reports[0].outcome, SwapOutcome::Unknown);
+        assert_eq!(reports[0].realized_profit, None);
+        assert_eq!(
+            reports[1].outcome,
+            SwapOutcome::Bounced {
+                refund: 1_150u32.into()
+            }
+        );
+        assert_eq!(reports[1].realized_profit, Some((-50).into()));
+    }
+
+    #[tokio::test]
+    async fn skips_invalid_payouts() {
+        let reports = track(
+            vec![swap(1, Asset::Native)],
+            vec![
+                received(1, DEX, 1_500, Cell::default()),
+                received(2, DEX, 1_500, payout(1, true, None)),
+            ],
+        )
+        .await;
+
+        assert_eq!(
+            reports[0].outcome,
+            SwapOutcome::Fulfilled {
+                amount_out: 1_500u32.into()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn jetton_payouts() {
+        let reports = track(
+            vec![swap(1, JETTON), swap(2, JETTON), swap(3, Asset::Native)],
+            vec![
+                received(1, DEX, 50, payout(1, true, Some(1_100))),
+                // rejected at the first hop, so that jettons are returned
+                received(2, DEX, 50, payout(2, false, Some(990))),
+                // cycle through TONs can not end with jettons
+                received(3, DEX, 50, payout(3, true, Some(2_000))),
+            ],
+        )
+        .await;
+
+        assert_eq!(reports[0].realized_profit, Some(100.into()));
+        assert_eq!(
+            reports[1].outcome,
+            SwapOutcome::Rejected {
+                asset: JETTON,
+                amount_out: 990u32.into()
+            }
+        );
+        assert_eq!(reports[1].realized_profit, Some((-10).into()));
+        assert_eq!(reports[2].outcome, SwapOutcome::Unknown);
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::anyhow;
+use tlb::CellSerialize;
+use tlb_ton::Message;
+use ton_contracts::wallet::WalletOpSendMessage;
+
+/// Max number of internal messages in a single transfer from v3 and v4
+/// wallets
+pub const MAX_WALLET_MESSAGES: usize = 4;
+
+/// Pay forwarding fees separately from the message value
+pub const SEND_MODE_PAY_FEES_SEPARATELY: u8 = 1;
+/// Ignore errors during the action phase, so that other messages of the
+/// transfer are still sent
+pub const SEND_MODE_IGNORE_ERRORS: u8 = 2;
+
+/// Internal messages to be sent in a single external message to the
+/// wallet, so that they share the same seqno
+pub struct WalletTransfer {
+    capacity: usize,
+    messages: Vec<WalletOpSendMessage>,
+}
+
+impl WalletTransfer {
+    /// Transfer of at most `capacity` messages, which depends on
+    /// the wallet version
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            messages: Vec::new(),
+        }
+    }
+
+    /// Adds a message to be sent with given mode, fails if the transfer
+    /// is already full
+    pub fn push<T>(&mut self, mode: u8, message: Message<T>) -> anyhow::Result<()>
+    where
+        T: CellSerialize,
+    {
+        if self.remaining() == 0 {
+            return Err(anyhow!(
+                "wallet transfer can contain at most {} messages",
+                self.capacity,
+            ));
+        }
+        self.messages.push(WalletOpSendMessage {
+            mode,
+            message: message.normalize()?,
+        });
+        Ok(())
+    }
+
+    /// Number of messages that can still be added
+    pub fn remaining(&self) -> usize {
+        self.capacity - self.messages.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    pub fn messages(&self) -> &[WalletOpSendMessage] {
+        &self.messages
+    }
+
+    pub fn into_messages(self) -> Vec<WalletOpSendMessage> {
+        self.messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
+    use tlb_ton::{
+        CommonMsgInfo, CurrencyCollection, ExtraCurrencyCollection, InternalMsgInfo, MsgAddress,
+    };
+
+    use super::*;
+
+    fn message(grams: u32) -> Message<()> {
+        Message {
+            info: CommonMsgInfo::Internal(InternalMsgInfo {
+                ihr_disabled: true,
+                bounce: true,
+                bounced: false,
+                src: MsgAddress::NULL,
+                dst: MsgAddress::NULL,
+                value: CurrencyCollection {
+                    grams: grams.into(),
+                    other: ExtraCurrencyCollection,
+                },
+                ihr_fee: BigUint::ZERO,
+                fwd_fee: BigUint::ZERO,
+                created_lt: 0,
+                created_at: None,
+            }),
+            init: None,
+            body: (),
+        }
+    }
+
+    #[test]
+    fn keeps_mode_of_each_message() {
+        let mut transfer = WalletTransfer::new(MAX_WALLET_MESSAGES);
+        transfer
+            .push(
+                SEND_MODE_PAY_FEES_SEPARATELY | SEND_MODE_IGNORE_ERRORS,
+                message(1),
+            )
+            .unwrap();
+        transfer
+            .push(SEND_MODE_PAY_FEES_SEPARATELY, message(2))
+            .unwrap();
+
+        assert_eq!(
+            transfer
+                .messages()
+                .iter()
+                .map(|op| op.mode)
+                .collect::<Vec<_>>(),
+            [3, 1]
+        );
+        assert_eq!(transfer.remaining(), MAX_WALLET_MESSAGES - 2);
+    }
+
+    #[test]
+    fn rejects_too_many_messages() {
+        let mut transfer = WalletTransfer::new(MAX_WALLET_MESSAGES);
+        for i in 0..MAX_WALLET_MESSAGES {
+            transfer.push(3, message(i as u32)).unwrap();
+        }
+        assert!(transfer.push(3, message(0)).is_err());
+        assert_eq!(transfer.len(), MAX_WALLET_MESSAGES);
+    }
+}
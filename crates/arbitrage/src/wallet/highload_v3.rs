@@ -0,0 +1,424 @@
+use core::time::Duration;
+
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, TimeDelta, Utc};
+use num::BigUint;
+use tlb::{
+    BitPack, BitReader, BitReaderExt, BitUnpack, BitWriter, BitWriterExt, CellBuilder,
+    CellBuilderError, CellDeserialize, CellParser, CellParserError, CellSerialize,
+    CellSerializeExt, Error, NBits, Ref,
+};
+use tlb_ton::{
+    BagOfCells, CommonMsgInfo, CurrencyCollection, ExternalInMsgInfo, ExtraCurrencyCollection,
+    InternalMsgInfo, Message, MsgAddress,
+};
+use ton_contracts::wallet::{mnemonic::Keypair, WalletOpSendMessage};
+
+use super::{WalletSigner, WalletVersion};
+
+pub const HIGHLOAD_V3_DEFAULT_SUBWALLET_ID: u32 = 0x10ad;
+
+/// Max number of actions which can be set by a single transaction,
+/// one of which is reserved by the wallet
+const MAX_ACTIONS: usize = 254;
+
+/// Liteservers can lag behind, so that messages created right now would
+/// be rejected as created in the future
+const CLOCK_SKEW: TimeDelta = TimeDelta::seconds(30);
+
+/// nanoTONs attached to the message which the wallet sends to itself
+/// to send a batch of messages
+const INTERNAL_TRANSFER_VALUE: u64 = 10_000_000;
+
+/// Highload wallet v3, which identifies messages by query ID instead of
+/// seqno, so that many of them can be sent without waiting for previous
+/// ones.
+pub struct HighloadWalletV3 {
+    address: MsgAddress,
+    key_pair: Keypair,
+    subwallet_id: u32,
+    timeout: Duration,
+}
+
+impl HighloadWalletV3 {
+    pub fn new(
+        address: MsgAddress,
+        key_pair: Keypair,
+        subwallet_id: u32,
+        timeout: Duration,
+    ) -> Self {
+        Self {
+            address,
+            key_pair,
+            subwallet_id,
+            timeout,
+        }
+    }
+
+    /// Message to be sent by the wallet: a single message is sent as is,
+    /// while a batch is sent to the wallet itself to set all actions at once
+    fn message_to_send(
+        &self,
+        query_id: HighloadQueryId,
+        mut messages: Vec<WalletOpSendMessage>,
+    ) -> anyhow::Result<(u8, Message)> {
+        if messages.len() == 1 {
+            let WalletOpSendMessage { mode, message } = messages.pop().unwrap();
+            return Ok((mode, message));
+        }
+        let internal_transfer: Message<_> = Message {
+            info: CommonMsgInfo::Internal(InternalMsgInfo {
+                ihr_disabled: true,
+                bounce: false,
+                bounced: false,
+                src: MsgAddress::NULL,
+                dst: self.address,
+                value: CurrencyCollection {
+                    grams: INTERNAL_TRANSFER_VALUE.into(),
+                    other: ExtraCurrencyCollection,
+                },
+                ihr_fee: BigUint::ZERO,
+                fwd_fee: BigUint::ZERO,
+                created_lt: 0,
+                created_at: None,
+            }),
+            init: None,
+            body: HighloadInternalTransfer {
+                query_id: query_id.packed().into(),
+                actions: messages,
+            },
+        };
+        Ok((3, internal_transfer.normalize()?))
+    }
+}
+
+impl WalletSigner for HighloadWalletV3 {
+    fn version(&self) -> WalletVersion {
+        WalletVersion::HighloadV3
+    }
+
+    fn address(&self) -> MsgAddress {
+        self.address
+    }
+
+    fn max_messages(&self) -> usize {
+        MAX_ACTIONS
+    }
+
+    fn sign_transfer(
+        &self,
+        seqno: u32,
+        _ttl: Duration,
+        messages: Vec<WalletOpSendMessage>,
+    ) -> anyhow::Result<(Vec<u8>, DateTime<Utc>)> {
+        if messages.is_empty() || messages.len() > MAX_ACTIONS {
+            return Err(anyhow!(
+                "highload wallet can send from 1 to {MAX_ACTIONS} messages, got: {}",
+                messages.len(),
+            ));
+        }
+
+        let query_id = HighloadQueryId::from_index(seqno);
+        let created_at = Utc::now() - CLOCK_SKEW;
+        let expire_at = created_at + TimeDelta::from_std(self.timeout)?;
+        let (mode, message) = self.message_to_send(query_id, messages)?;
+
+        let body = HighloadWalletV3SignBody {
+            subwallet_id: self.subwallet_id,
+            message,
+            mode,
+            query_id,
+            created_at,
+            timeout: self.timeout.as_secs().try_into()?,
+        }
+        .to_cell()?;
+        let signature = nacl::sign::signature(body.hash().as_slice(), &self.key_pair.skey)
+            .map_err(|err| anyhow!("sign: {err:?}"))?
+            .try_into()
+            .map_err(|signature: Vec<u8>| {
+                anyhow!("invalid signature length: {}", signature.len())
+            })?;
+
+        let msg: Message<_> = Message {
+            info: CommonMsgInfo::ExternalIn(ExternalInMsgInfo {
+                src: MsgAddress::NULL,
+                dst: self.address,
+                import_fee: BigUint::ZERO,
+            }),
+            init: None,
+            body: HighloadWalletV3ExternalBody { signature, body },
+        };
+        let boc = BagOfCells::from_root(msg.to_cell().context("external message")?);
+        Ok((boc.pack(true)?, expire_at))
+    }
+}
+
+/// Number of valid bit numbers in each shift
+const BIT_NUMBERS: u32 = 1023;
+/// Number of distinct query IDs
+const QUERY_IDS: u32 = (1 << 13) * BIT_NUMBERS;
+
+/// query_id$_ shift:uint13 bit_number:(## 10) { bit_number >= 0 }
+/// { bit_number < 1023 } = QueryId;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HighloadQueryId {
+    pub shift: u16,
+    pub bit_number: u16,
+}
+
+impl HighloadQueryId {
+    /// Maps sequential index onto query IDs, wrapping around after all
+    /// of them are used
+    pub fn from_index(index: u32) -> Self {
+        let index = index % QUERY_IDS;
+        Self {
+            shift: (index / BIT_NUMBERS) as u16,
+            bit_number: (index % BIT_NUMBERS) as u16,
+        }
+    }
+
+    /// Inverse of [`from_index`](Self::from_index)
+    pub fn index(&self) -> u32 {
+        self.shift as u32 * BIT_NUMBERS + self.bit_number as u32
+    }
+
+    /// As it is passed to `processed?` get-method
+    pub fn packed(&self) -> u32 {
+        (self.shift as u32) << 10 | self.bit_number as u32
+    }
+}
+
+impl BitPack for HighloadQueryId {
+    fn pack<W>(&self, mut writer: W) -> Result<(), W::Error>
+    where
+        W: BitWriter,
+    {
+        writer
+            .pack_as::<_, NBits<13>>(self.shift)?
+            .pack_as::<_, NBits<10>>(self.bit_number)?;
+        Ok(())
+    }
+}
+
+impl BitUnpack for HighloadQueryId {
+    fn unpack<R>(mut reader: R) -> Result<Self, R::Error>
+    where
+        R: BitReader,
+    {
+        let shift = reader.unpack_as::<_, NBits<13>>()?;
+        let bit_number = reader.unpack_as::<_, NBits<10>>()?;
+        if bit_number as u32 >= BIT_NUMBERS {
+            return Err(Error::custom(format!("invalid bit_number: {bit_number}")));
+        }
+        Ok(Self { shift, bit_number })
+    }
+}
+
+/// msg_inner$_ subwallet_id:uint32 message_to_send:^Cell send_mode:uint8
+/// query_id:QueryId created_at:uint64 timeout:uint22 = MsgInner;
+pub struct HighloadWalletV3SignBody {
+    pub subwallet_id: u32,
+    pub message: Message,
+    pub mode: u8,
+    pub query_id: HighloadQueryId,
+    pub created_at: DateTime<Utc>,
+    pub timeout: u32,
+}
+
+impl CellSerialize for HighloadWalletV3SignBody {
+    fn store(&self, builder: &mut CellBuilder) -> Result<(), CellBuilderError> {
+        builder
+            .pack(self.subwallet_id)?
+            .store_as::<_, Ref>(&self.message)?
+            .pack(self.mode)?
+            .pack(self.query_id)?
+            .pack(self.created_at.timestamp() as u64)?
+            .pack_as::<_, NBits<22>>(self.timeout)?;
+        Ok(())
+    }
+}
+
+impl<'de> CellDeserialize<'de> for HighloadWalletV3SignBody {
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        Ok(Self {
+            subwallet_id: parser.unpack()?,
+            message: parser.parse_as::<_, Ref>()?,
+            mode: parser.unpack()?,
+            query_id: parser.unpack()?,
+            created_at: DateTime::from_timestamp(parser.unpack::<u64>()? as i64, 0)
+                .ok_or_else(|| Error::custom("created_at out of range"))?,
+            timeout: parser.unpack_as::<_, NBits<22>>()?,
+        })
+    }
+}
+
+/// msg_body$_ signature:bits512 signed_msg:^MsgInner = ExternalInMsgBody;
+pub struct HighloadWalletV3ExternalBody<T> {
+    pub signature: [u8; 64],
+    pub body: T,
+}
+
+impl<T> CellSerialize for HighloadWalletV3ExternalBody<T>
+where
+    T: CellSerialize,
+{
+    fn store(&self, builder: &mut CellBuilder) -> Result<(), CellBuilderError> {
+        builder
+            .pack(self.signature)?
+            .store_as::<_, Ref>(&self.body)?;
+        Ok(())
+    }
+}
+
+impl<'de, T> CellDeserialize<'de> for HighloadWalletV3ExternalBody<T>
+where
+    T: CellDeserialize<'de>,
+{
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        Ok(Self {
+            signature: parser.unpack()?,
+            body: parser.parse_as::<_, Ref>()?,
+        })
+    }
+}
+
+const INTERNAL_TRANSFER_TAG: u32 = 0xae42e5a4;
+
+/// internal_transfer#ae42e5a4 {n:#} query_id:uint64 actions:^(OutList n)
+/// = InternalMsgBody n;
+pub struct HighloadInternalTransfer {
+    pub query_id: u64,
+    pub actions: Vec<WalletOpSendMessage>,
+}
+
+impl CellSerialize for HighloadInternalTransfer {
+    fn store(&self, builder: &mut CellBuilder) -> Result<(), CellBuilderError> {
+        builder
+            .pack(INTERNAL_TRANSFER_TAG)?
+            .pack(self.query_id)?
+            .store_as::<_, Ref>(&OutList(&self.actions))?;
+        Ok(())
+    }
+}
+
+const SEND_MSG_TAG: u32 = 0x0ec3c86d;
+
+/// out_list_empty$_ = OutList 0;
+/// out_list$_ {n:#} prev:^(OutList n) action:OutAction = OutList (n + 1);
+/// action_send_msg#0ec3c86d mode:(## 8) out_msg:^(MessageRelaxed Any) = OutAction;
+struct OutList<'a>(&'a [WalletOpSendMessage]);
+
+impl<'a> CellSerialize for OutList<'a> {
+    fn store(&self, builder: &mut CellBuilder) -> Result<(), CellBuilderError> {
+        let Some((last, prev)) = self.0.split_last() else {
+            return Ok(());
+        };
+        builder
+            .store_as::<_, Ref>(&OutList(prev))?
+            .pack(SEND_MSG_TAG)?
+            .pack(last.mode)?
+            .store_as::<_, Ref>(&last.message)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tlb::BitReaderExt;
+
+    use super::*;
+
+    fn message(grams: u32) -> WalletOpSendMessage {
+        WalletOpSendMessage {
+            mode: 3,
+            message: Message {
+                info: CommonMsgInfo::Internal(InternalMsgInfo {
+                    ihr_disabled: true,
+                    bounce: true,
+                    bounced: false,
+                    src: MsgAddress::NULL,
+                    dst: MsgAddress::NULL,
+                    value: CurrencyCollection {
+                        grams: grams.into(),
+                        other: ExtraCurrencyCollection,
+                    },
+                    ihr_fee: BigUint::ZERO,
+                    fwd_fee: BigUint::ZERO,
+                    created_lt: 0,
+                    created_at: None,
+                }),
+                init: None,
+                body: ().to_cell().unwrap(),
+            },
+        }
+    }
+
+    #[test]
+    fn query_id_from_index() {
+        assert_eq!(
+            HighloadQueryId::from_index(0),
+            HighloadQueryId {
+                shift: 0,
+                bit_number: 0
+            }
+        );
+        let query_id = HighloadQueryId::from_index(BIT_NUMBERS + 5);
+        assert_eq!(
+            query_id,
+            HighloadQueryId {
+                shift: 1,
+                bit_number: 5
+            }
+        );
+        assert_eq!(query_id.packed(), 1 << 10 | 5);
+        assert_eq!(query_id.index(), BIT_NUMBERS + 5);
+        // wraps around
+        assert_eq!(
+            HighloadQueryId::from_index(QUERY_IDS + 7),
+            HighloadQueryId::from_index(7)
+        );
+    }
+
+    #[test]
+    fn parse_external_body() {
+        let query_id = HighloadQueryId::from_index(3 * BIT_NUMBERS + 7);
+        let created_at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let body = HighloadWalletV3ExternalBody {
+            signature: [1; 64],
+            body: HighloadWalletV3SignBody {
+                subwallet_id: HIGHLOAD_V3_DEFAULT_SUBWALLET_ID,
+                message: message(1).message,
+                mode: 3,
+                query_id,
+                created_at,
+                timeout: 3600,
+            },
+        }
+        .to_cell()
+        .unwrap();
+
+        let parsed: HighloadWalletV3ExternalBody<HighloadWalletV3SignBody> =
+            body.parse_fully().unwrap();
+        assert_eq!(parsed.signature, [1; 64]);
+        assert_eq!(parsed.body.subwallet_id, HIGHLOAD_V3_DEFAULT_SUBWALLET_ID);
+        assert_eq!(parsed.body.mode, 3);
+        assert_eq!(parsed.body.query_id, query_id);
+        assert_eq!(parsed.body.query_id.index(), 3 * BIT_NUMBERS + 7);
+        assert_eq!(parsed.body.created_at, created_at);
+        assert_eq!(parsed.body.timeout, 3600);
+    }
+
+    #[test]
+    fn out_list_is_reversed() {
+        let actions = [1, 2, 3].map(message);
+        let mut list = OutList(&actions).to_cell().unwrap();
+        for action in actions.iter().rev() {
+            let mut parser = list.parser();
+            assert_eq!(parser.unpack::<u32>().unwrap(), SEND_MSG_TAG);
+            assert_eq!(parser.unpack::<u8>().unwrap(), action.mode);
+            assert_eq!(*list.references[1], action.message.to_cell().unwrap());
+            list = (*list.references[0]).clone();
+        }
+        assert!(list.data.is_empty() && list.references.is_empty());
+    }
+}
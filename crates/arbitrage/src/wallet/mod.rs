@@ -0,0 +1,186 @@
+mod highload_v3;
+
+pub use self::highload_v3::*;
+
+use core::time::Duration;
+
+use anyhow::Context;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr, DurationSeconds};
+use tlb::CellSerializeExt;
+use tlb_ton::{BagOfCells, MsgAddress};
+use ton_contracts::wallet::{mnemonic::Keypair, v4r2::V4R2, Wallet, WalletOpSendMessage};
+
+use crate::MAX_WALLET_MESSAGES;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum WalletVersion {
+    #[default]
+    #[serde(rename = "v4r2")]
+    V4R2,
+    #[serde(rename = "highload_v3")]
+    HighloadV3,
+}
+
+impl WalletVersion {
+    /// Highload wallets identify messages by query ID instead of seqno,
+    /// so that many of them can be in flight at once
+    pub fn is_highload(&self) -> bool {
+        matches!(self, Self::HighloadV3)
+    }
+}
+
+#[serde_as]
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct WalletConfig {
+    pub version: WalletVersion,
+    /// Defaults to the one of the wallet version
+    pub subwallet_id: Option<u32>,
+    /// Address of deployed highload wallet.
+    /// Required for highload v3, since its code is not bundled, so that
+    /// the address cannot be derived.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub address: Option<MsgAddress>,
+    /// Lifetime of external messages of highload wallet in seconds,
+    /// must match the one of deployed wallet
+    #[serde_as(as = "DurationSeconds<u64>")]
+    pub timeout: Duration,
+}
+
+impl Default for WalletConfig {
+    fn default() -> Self {
+        Self {
+            version: Default::default(),
+            subwallet_id: None,
+            address: None,
+            timeout: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl WalletConfig {
+    pub fn signer(&self, key_pair: Keypair) -> anyhow::Result<Box<dyn WalletSigner>> {
+        Ok(match self.version {
+            WalletVersion::V4R2 => Box::new(self.derive::<V4R2>(key_pair)?),
+            WalletVersion::HighloadV3 => Box::new(HighloadWalletV3::new(
+                self.address
+                    .context("address is required for highload wallet")?,
+                key_pair,
+                self.subwallet_id
+                    .unwrap_or(HIGHLOAD_V3_DEFAULT_SUBWALLET_ID),
+                self.timeout,
+            )),
+        })
+    }
+
+    fn derive<V>(&self, key_pair: Keypair) -> anyhow::Result<Wallet<V>>
+    where
+        V: ton_contracts::wallet::WalletVersion,
+    {
+        match self.subwallet_id {
+            Some(subwallet_id) => Wallet::derive(0, key_pair, subwallet_id),
+            None => Wallet::derive_default(key_pair),
+        }
+    }
+}
+
+/// Wallet which can sign transfers of internal messages
+pub trait WalletSigner: Send + Sync {
+    fn version(&self) -> WalletVersion;
+
+    fn address(&self) -> MsgAddress;
+
+    /// Max number of internal messages in a single transfer
+    fn max_messages(&self) -> usize;
+
+    /// Signs external message which sends given internal messages and
+    /// returns it packed into BoC along with its expiration time.
+    /// For highload wallets, `seqno` is the index of query ID and
+    /// expiration is defined by the wallet timeout instead of `ttl`.
+    fn sign_transfer(
+        &self,
+        seqno: u32,
+        ttl: Duration,
+        messages: Vec<WalletOpSendMessage>,
+    ) -> anyhow::Result<(Vec<u8>, DateTime<Utc>)>;
+}
+
+macro_rules! impl_wallet_signer {
+    ($($version:ident => $max_messages:expr),+ $(,)?) => {$(
+        impl WalletSigner for Wallet<$version> {
+            fn version(&self) -> WalletVersion {
+                WalletVersion::$version
+            }
+
+            fn address(&self) -> MsgAddress {
+                Wallet::address(self)
+            }
+
+            fn max_messages(&self) -> usize {
+                $max_messages
+            }
+
+            fn sign_transfer(
+                &self,
+                seqno: u32,
+                ttl: Duration,
+                messages: Vec<WalletOpSendMessage>,
+            ) -> anyhow::Result<(Vec<u8>, DateTime<Utc>)> {
+                let expire_at = Utc::now() + TimeDelta::from_std(ttl)?;
+                let msg = self.create_external_message(expire_at, seqno, messages, false)?;
+                let boc = BagOfCells::from_root(msg.to_cell()?);
+                Ok((boc.pack(true)?, expire_at))
+            }
+        }
+    )+};
+}
+
+impl_wallet_signer!(V4R2 => MAX_WALLET_MESSAGES);
+
+/// Wallet to trade from
+pub enum ArbitragerWallet {
+    /// Wallet with known keys, so that messages can be signed and sent
+    Signer(Box<dyn WalletSigner>),
+    /// Wallet known only by its address, can be used only in
+    /// [dry run](crate::ArbitragerConfig::dry_run)
+    WatchOnly(MsgAddress),
+}
+
+impl ArbitragerWallet {
+    pub fn address(&self) -> MsgAddress {
+        match self {
+            Self::Signer(wallet) => wallet.address(),
+            Self::WatchOnly(address) => *address,
+        }
+    }
+
+    pub fn signer(&self) -> Option<&dyn WalletSigner> {
+        match self {
+            Self::Signer(wallet) => Some(wallet.as_ref()),
+            Self::WatchOnly(_) => None,
+        }
+    }
+
+    /// Watch-only wallet is treated as a seqno one
+    pub fn is_highload(&self) -> bool {
+        self.signer()
+            .is_some_and(|wallet| wallet.version().is_highload())
+    }
+
+    /// Max number of internal messages in a single transfer
+    pub fn max_messages(&self) -> usize {
+        self.signer()
+            .map_or(MAX_WALLET_MESSAGES, WalletSigner::max_messages)
+    }
+}
+
+impl<W> From<W> for ArbitragerWallet
+where
+    W: WalletSigner + 'static,
+{
+    fn from(wallet: W) -> Self {
+        Self::Signer(Box::new(wallet))
+    }
+}
@@ -9,6 +9,7 @@ use lazy_static::lazy_static;
 use opentelemetry::KeyValue;
 use opentelemetry_otlp::{TonicExporterBuilder, WithExportConfig};
 use opentelemetry_sdk::Resource;
+use tlb_ton::MsgAddress;
 use tokio::fs;
 use ton_contracts::wallet::mnemonic::{Keypair, Mnemonic};
 use tracing::{info, level_filters::LevelFilter, Level, Subscriber};
@@ -20,7 +21,7 @@ use tracing_subscriber::{
     Layer, Registry,
 };
 
-use aceton::config::AcetonConfig;
+use aceton::{config::AcetonConfig, ArbitragerWallet, WalletConfig};
 
 use crate::metrics::MetricsFilter;
 
@@ -43,6 +44,15 @@ pub struct CliArgs {
         default_value_os_t = PathBuf::from("./mnemonic.txt"),
     )]
     secret: PathBuf,
+
+    #[arg(long, value_name = "ADDRESS")]
+    /// Use watch-only wallet with given address instead of reading secret,
+    /// only allowed in dry run
+    watch_only: Option<MsgAddress>,
+
+    #[arg(long)]
+    /// Do not send any messages, only log them and track hypothetical profit
+    dry_run: bool,
     // #[arg(
     //     short, long,
     //     value_parser,
@@ -59,10 +69,22 @@ impl CliArgs {
     pub async fn config(&self) -> anyhow::Result<AcetonConfig> {
         info!(config = %self.config.display(), "reading config");
         let contents = fs::read_to_string(&self.config).await.context("read")?;
-        toml::from_str(&contents).context("TOML")
+        let mut cfg: AcetonConfig = toml::from_str(&contents).context("TOML")?;
+        cfg.arbitrage.dry_run |= self.dry_run;
+        Ok(cfg)
+    }
+
+    pub async fn wallet(&self, cfg: &WalletConfig) -> anyhow::Result<ArbitragerWallet> {
+        if let Some(address) = self.watch_only {
+            return Ok(ArbitragerWallet::WatchOnly(address));
+        }
+        let key_pair = self.key_pair().await.context("secret")?;
+        Ok(ArbitragerWallet::Signer(
+            cfg.signer(key_pair).context("wallet")?,
+        ))
     }
 
-    pub async fn key_pair(&self) -> anyhow::Result<Keypair> {
+    async fn key_pair(&self) -> anyhow::Result<Keypair> {
         let contents = fs::read_to_string(&self.secret).await.context("read")?;
         contents.parse::<Mnemonic>()?.generate_keypair(None)
     }
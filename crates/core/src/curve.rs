@@ -0,0 +1,90 @@
+use num::{rational::Ratio, BigUint, Integer, One};
+
+/// Swap curve of the form `amount_out = a * amount_in / (b + c * amount_in)`.
+///
+/// It describes a single constant-product pool as well as a chain of them,
+/// since a composition of such curves has the same form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstantProductCurve {
+    a: BigUint,
+    b: BigUint,
+    c: BigUint,
+}
+
+impl ConstantProductCurve {
+    /// Curve of a constant-product pool with given reserves and
+    /// [trade fees](crate::DexPool::trade_fees) for incoming and
+    /// outcoming assets respectively
+    pub fn new(
+        [reserve_in, reserve_out]: [&BigUint; 2],
+        [fee_in, fee_out]: [Ratio<BigUint>; 2],
+    ) -> Self {
+        // amount_out = fee_out * (fee_in * amount_in) * reserve_out / (reserve_in + fee_in * amount_in)
+        let (fee_in_numer, fee_in_denom) = fee_in.into_raw();
+        let (fee_out_numer, fee_out_denom) = fee_out.into_raw();
+        Self {
+            a: &fee_out_numer * &fee_in_numer * reserve_out,
+            b: &fee_out_denom * fee_in_denom * reserve_in,
+            c: fee_out_denom * fee_in_numer,
+        }
+        .reduced()
+    }
+
+    /// Curve that returns `amount_in` as is
+    pub fn identity() -> Self {
+        Self {
+            a: BigUint::one(),
+            b: BigUint::one(),
+            c: BigUint::ZERO,
+        }
+    }
+
+    /// Curve of swapping through `self` and then through `next`
+    pub fn then(self, next: Self) -> Self {
+        Self {
+            c: &next.b * &self.c + &self.a * &next.c,
+            a: self.a * next.a,
+            b: self.b * next.b,
+        }
+        .reduced()
+    }
+
+    fn reduced(mut self) -> Self {
+        let gcd = self.a.gcd(&self.b).gcd(&self.c);
+        if gcd > BigUint::one() {
+            self.a /= &gcd;
+            self.b /= &gcd;
+            self.c /= &gcd;
+        }
+        self
+    }
+
+    pub fn estimate_swap_out(&self, amount_in: &BigUint) -> BigUint {
+        let denom = &self.b + &self.c * amount_in;
+        if denom == BigUint::ZERO {
+            return BigUint::ZERO;
+        }
+        &self.a * amount_in / denom
+    }
+
+    /// Whether infinitesimally small swap gives more than it takes
+    pub fn is_profitable(&self) -> bool {
+        self.a > self.b
+    }
+
+    /// Returns `amount_in` that maximizes `estimate_swap_out(amount_in) - amount_in`,
+    /// capped by `max_amount_in`.
+    ///
+    /// The maximum is reached where the derivative `a * b / (b + c * x)^2` equals to 1,
+    /// i.e. at `x = (sqrt(a * b) - b) / c`.
+    pub fn optimal_amount_in(&self, max_amount_in: &BigUint) -> BigUint {
+        if !self.is_profitable() {
+            return BigUint::ZERO;
+        }
+        if self.c == BigUint::ZERO {
+            // linear curve: the more we put, the more we get
+            return max_amount_in.clone();
+        }
+        (((&self.a * &self.b).sqrt() - &self.b) / &self.c).min(max_amount_in.clone())
+    }
+}
@@ -6,7 +6,7 @@ use num::BigUint;
 use tlb::CellSerialize;
 use tlb_ton::MsgAddress;
 
-use crate::{Asset, DexPool};
+use crate::{ton_utils::transactions::TransactionMessage, Asset, DexPool, SwapPath};
 
 #[async_trait]
 #[autoimpl(for<T: trait + ?Sized> &T, &mut T, Box<T>, Arc<T>)]
@@ -18,17 +18,62 @@ pub trait Dex {
 
     async fn update_pool(&self, pool: &mut Self::Pool) -> anyhow::Result<bool>;
 
+    /// Returns amount of TONs spent on gas by messages built by
+    /// [`make_body`](Dex::make_body) for a swap along the path
+    fn estimate_gas(&self, path: &SwapPath<&Self::Pool>) -> BigUint;
+
+    /// Builds bodies of messages that `sender` should send to
+    /// [`DexBody::dst`] in order to swap `amount_in` of `asset_in`
+    /// through given steps.
+    /// Returns a body per message: a single one if the DEX chains all hops
+    /// in one message, and one per leg otherwise, e.g.
+    /// [`MultiDex`](crate::MultiDex) returns one per each DEX on the path.
+    /// Messages after the first one spend intermediate assets from the
+    /// sender's balance, since they are sent before previous legs pay out.
     async fn make_body(
         &self,
         query_id: u64,
+        sender: MsgAddress,
         asset_in: Asset,
         amount_in: BigUint,
         steps: <Self::Pool as DexPool>::Step,
-    ) -> anyhow::Result<DexBody<Self::Body>>;
+    ) -> anyhow::Result<Vec<DexBody<Self::Body>>>;
+
+    /// Parses the message received by the sender of a swap started with
+    /// [`make_body`](Dex::make_body), returns `None` if it is not a payout
+    /// of this DEX. Implementations check that the message was sent by
+    /// the DEX, so that payouts can not be forged.
+    async fn parse_payout(&self, msg: &TransactionMessage) -> anyhow::Result<Option<DexPayout>>;
 }
 
 pub struct DexBody<B> {
     pub dst: MsgAddress,
+    /// TONs spent on gas
     pub gas: BigUint,
+    /// Total TONs to attach: gas and swapped TONs, if any
+    pub value: BigUint,
     pub body: B,
 }
+
+impl<B> DexBody<B> {
+    pub fn map<T>(self, f: impl FnOnce(B) -> T) -> DexBody<T> {
+        DexBody {
+            dst: self.dst,
+            gas: self.gas,
+            value: self.value,
+            body: f(self.body),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DexPayout {
+    pub query_id: u64,
+    /// Asset paid out, which is an intermediate one if the swap was
+    /// rejected in the middle of the path
+    pub asset: Asset,
+    /// Whether the swap was fulfilled or rejected
+    pub fulfilled: bool,
+    /// Amount of received asset, if it is not TONs attached to the message
+    pub amount: Option<BigUint>,
+}
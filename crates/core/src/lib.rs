@@ -1,8 +1,13 @@
 mod asset;
+mod curve;
 mod dex;
+mod multi;
 mod pool;
+mod risk;
 mod swap_path;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
-pub use self::{asset::*, dex::*, pool::*, swap_path::*};
+pub use self::{asset::*, curve::*, dex::*, multi::*, pool::*, risk::*, swap_path::*};
 
 pub use aceton_ton_utils as ton_utils;
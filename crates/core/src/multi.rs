@@ -0,0 +1,512 @@
+use std::collections::VecDeque;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use futures::try_join;
+use num::{rational::Ratio, BigUint};
+use tlb::{CellBuilder, CellBuilderError, CellSerialize};
+use tlb_ton::MsgAddress;
+
+use crate::{
+    ton_utils::transactions::TransactionMessage, Asset, ConstantProductCurve, Dex, DexBody,
+    DexPayout, DexPool, SwapPath,
+};
+
+/// Composite [`Dex`] which merges pools of two DEXes, so that cycles
+/// can span both of them. Nest it to combine more DEXes:
+/// `MultiDex<A, MultiDex<B, C>>`.
+///
+/// Each part of a path that goes through pools of a single DEX is
+/// executed by a separate message. All of them are sent at once, so that
+/// parts after the first one spend intermediate assets from the sender's
+/// balance, which has to hold them in advance.
+pub struct MultiDex<L, R> {
+    pub left: L,
+    pub right: R,
+}
+
+impl<L, R> MultiDex<L, R> {
+    pub fn new(left: L, right: R) -> Self {
+        Self { left, right }
+    }
+}
+
+/// Pool of either DEX
+#[derive(Debug, Clone)]
+pub enum MultiPool<L, R> {
+    Left(L),
+    Right(R),
+}
+
+/// Pool ID keyed by the DEX it belongs to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MultiPoolId<L, R> {
+    Left(L),
+    Right(R),
+}
+
+macro_rules! dispatch {
+    ($pool:expr, $p:ident => $e:expr) => {
+        match $pool {
+            MultiPool::Left($p) => $e,
+            MultiPool::Right($p) => $e,
+        }
+    };
+}
+
+impl<L, R> DexPool for MultiPool<L, R>
+where
+    L: DexPool,
+    R: DexPool,
+{
+    type ID = MultiPoolId<L::ID, R::ID>;
+    type Step = MultiStep<L::Step, R::Step>;
+
+    fn id(&self) -> Self::ID {
+        match self {
+            Self::Left(pool) => MultiPoolId::Left(pool.id()),
+            Self::Right(pool) => MultiPoolId::Right(pool.id()),
+        }
+    }
+
+    fn address(&self) -> MsgAddress {
+        dispatch!(self, pool => pool.address())
+    }
+
+    fn assets(&self) -> [Asset; 2] {
+        dispatch!(self, pool => pool.assets())
+    }
+
+    fn reserves(&self) -> [&BigUint; 2] {
+        dispatch!(self, pool => pool.reserves())
+    }
+
+    fn trade_fees(&self) -> [Ratio<BigUint>; 2] {
+        dispatch!(self, pool => pool.trade_fees())
+    }
+
+    fn make_step(&self, amount_out_min: Option<BigUint>, next: Option<Self::Step>) -> Self::Step {
+        let mut next = next.unwrap_or_default();
+
+        // continue the first leg if it belongs to the same DEX
+        let (step, mut hops, leg_amount_out_min) = match (self, next.legs.pop_front()) {
+            (
+                Self::Left(pool),
+                Some(MultiLeg {
+                    step: MultiLegStep::Left(step),
+                    hops,
+                    amount_out_min: leg_amount_out_min,
+                }),
+            ) => (
+                MultiLegStep::Left(pool.make_step(amount_out_min, Some(step))),
+                hops,
+                leg_amount_out_min,
+            ),
+            (
+                Self::Right(pool),
+                Some(MultiLeg {
+                    step: MultiLegStep::Right(step),
+                    hops,
+                    amount_out_min: leg_amount_out_min,
+                }),
+            ) => (
+                MultiLegStep::Right(pool.make_step(amount_out_min, Some(step))),
+                hops,
+                leg_amount_out_min,
+            ),
+            (pool, leg) => {
+                // otherwise, start a new one
+                if let Some(leg) = leg {
+                    next.legs.push_front(leg);
+                }
+                let limit = amount_out_min.clone().unwrap_or(BigUint::ZERO);
+                let step = match pool {
+                    Self::Left(pool) => MultiLegStep::Left(pool.make_step(amount_out_min, None)),
+                    Self::Right(pool) => MultiLegStep::Right(pool.make_step(amount_out_min, None)),
+                };
+                (step, Vec::new(), limit)
+            }
+        };
+        hops.insert(0, self.assets());
+        next.legs.push_front(MultiLeg {
+            step,
+            hops,
+            amount_out_min: leg_amount_out_min,
+        });
+        next
+    }
+
+    fn is_active(&self) -> bool {
+        dispatch!(self, pool => pool.is_active())
+    }
+
+    fn ratio(&self, asset_in: Asset) -> Ratio<BigUint> {
+        dispatch!(self, pool => pool.ratio(asset_in))
+    }
+
+    fn rate(&self, asset_in: Asset) -> f64 {
+        dispatch!(self, pool => pool.rate(asset_in))
+    }
+
+    fn rate_with_fees(&self, asset_in: Asset) -> f64 {
+        dispatch!(self, pool => pool.rate_with_fees(asset_in))
+    }
+
+    fn constant_product_curve(&self, asset_in: Asset) -> Option<ConstantProductCurve> {
+        dispatch!(self, pool => pool.constant_product_curve(asset_in))
+    }
+
+    fn estimate_swap_out(&self, asset_in: Asset, amount_in: &BigUint) -> BigUint {
+        dispatch!(self, pool => pool.estimate_swap_out(asset_in, amount_in))
+    }
+}
+
+/// Swap steps split into legs, each of which goes through a single DEX
+pub struct MultiStep<L, R> {
+    pub legs: VecDeque<MultiLeg<L, R>>,
+}
+
+impl<L, R> Default for MultiStep<L, R> {
+    fn default() -> Self {
+        Self {
+            legs: Default::default(),
+        }
+    }
+}
+
+pub struct MultiLeg<L, R> {
+    pub step: MultiLegStep<L, R>,
+    /// Assets of pools the leg goes through
+    pub hops: Vec<[Asset; 2]>,
+    /// Min output of the last hop, which is also the input of the next leg
+    pub amount_out_min: BigUint,
+}
+
+pub enum MultiLegStep<L, R> {
+    Left(L),
+    Right(R),
+}
+
+/// Part of the path going through pools of a single DEX
+type PathLeg<'a, L, R> = MultiPool<SwapPath<&'a L>, SwapPath<&'a R>>;
+
+/// Body of the message to either DEX
+pub enum MultiBody<L, R> {
+    Left(L),
+    Right(R),
+}
+
+impl<L, R> CellSerialize for MultiBody<L, R>
+where
+    L: CellSerialize,
+    R: CellSerialize,
+{
+    fn store(&self, builder: &mut CellBuilder) -> Result<(), CellBuilderError> {
+        match self {
+            Self::Left(body) => body.store(builder),
+            Self::Right(body) => body.store(builder),
+        }
+    }
+}
+
+#[async_trait]
+impl<L, R> Dex for MultiDex<L, R>
+where
+    L: Dex + Send + Sync,
+    L::Pool: Send + Sync,
+    <L::Pool as DexPool>::Step: Send,
+    L::Body: Send,
+    R: Dex + Send + Sync,
+    R::Pool: Send + Sync,
+    <R::Pool as DexPool>::Step: Send,
+    R::Body: Send,
+{
+    type Pool = MultiPool<L::Pool, R::Pool>;
+    type Body = MultiBody<L::Body, R::Body>;
+
+    async fn get_pools(&self) -> anyhow::Result<Vec<Self::Pool>> {
+        let (left, right) = try_join!(self.left.get_pools(), self.right.get_pools())?;
+        Ok(left
+            .into_iter()
+            .map(MultiPool::Left)
+            .chain(right.into_iter().map(MultiPool::Right))
+            .collect())
+    }
+
+    async fn update_pool(&self, pool: &mut Self::Pool) -> anyhow::Result<bool> {
+        match pool {
+            MultiPool::Left(pool) => self.left.update_pool(pool).await,
+            MultiPool::Right(pool) => self.right.update_pool(pool).await,
+        }
+    }
+
+    /// Sum over parts of the path going through a single DEX
+    fn estimate_gas(&self, path: &SwapPath<&Self::Pool>) -> BigUint {
+        let mut legs: Vec<PathLeg<L::Pool, R::Pool>> = Vec::new();
+        for step in path.iter_steps() {
+            let pool: &Self::Pool = step.pool();
+            match (legs.last_mut(), pool) {
+                (Some(MultiPool::Left(leg)), MultiPool::Left(pool)) => {
+                    leg.push(pool);
+                }
+                (Some(MultiPool::Right(leg)), MultiPool::Right(pool)) => {
+                    leg.push(pool);
+                }
+                (_, MultiPool::Left(pool)) => {
+                    let mut leg = SwapPath::new(step.asset_in());
+                    leg.push(pool);
+                    legs.push(MultiPool::Left(leg));
+                }
+                (_, MultiPool::Right(pool)) => {
+                    let mut leg = SwapPath::new(step.asset_in());
+                    leg.push(pool);
+                    legs.push(MultiPool::Right(leg));
+                }
+            }
+        }
+        legs.iter()
+            .map(|leg| match leg {
+                MultiPool::Left(leg) => self.left.estimate_gas(leg),
+                MultiPool::Right(leg) => self.right.estimate_gas(leg),
+            })
+            .sum()
+    }
+
+    /// Bodies of each part of the path going through a single DEX, where
+    /// `amount_out_min` of a part is `amount_in` of the next one
+    async fn make_body(
+        &self,
+        query_id: u64,
+        sender: MsgAddress,
+        asset_in: Asset,
+        amount_in: BigUint,
+        steps: <Self::Pool as DexPool>::Step,
+    ) -> anyhow::Result<Vec<DexBody<Self::Body>>> {
+        let (mut asset_in, mut amount_in) = (asset_in, amount_in);
+        let mut bodies = Vec::with_capacity(steps.legs.len());
+        for leg in steps.legs {
+            let asset_out = leg
+                .hops
+                .iter()
+                .try_fold(asset_in, |asset, &[a0, a1]| {
+                    if asset == a0 {
+                        return Some(a1);
+                    }
+                    (asset == a1).then_some(a0)
+                })
+                .with_context(|| format!("{asset_in} is not in the path"))?;
+
+            match leg.step {
+                MultiLegStep::Left(step) => bodies.extend(
+                    self.left
+                        .make_body(query_id, sender, asset_in, amount_in, step)
+                        .await?
+                        .into_iter()
+                        .map(|body| body.map(MultiBody::Left)),
+                ),
+                MultiLegStep::Right(step) => bodies.extend(
+                    self.right
+                        .make_body(query_id, sender, asset_in, amount_in, step)
+                        .await?
+                        .into_iter()
+                        .map(|body| body.map(MultiBody::Right)),
+                ),
+            }
+            (asset_in, amount_in) = (asset_out, leg.amount_out_min);
+        }
+        Ok(bodies)
+    }
+
+    async fn parse_payout(&self, msg: &TransactionMessage) -> anyhow::Result<Option<DexPayout>> {
+        if let Some(payout) = self.left.parse_payout(msg).await? {
+            return Ok(Some(payout));
+        }
+        self.right.parse_payout(msg).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{MockBody, MockDex, MockPool, MockStep};
+
+    use super::*;
+
+    const JETTON: Asset = Asset::Jetton(MsgAddress::NULL);
+    const EXTRA: Asset = Asset::ExtraCurrency { currency_id: 1 };
+
+    fn pool(id: u8, assets: [Asset; 2]) -> MockPool {
+        MockPool::new(id, assets, [1_000, 1_000])
+    }
+
+    fn multi_dex() -> MultiDex<MockDex, MockDex> {
+        MultiDex::new(MockDex::new(10), MockDex::new(1000))
+    }
+
+    fn make_steps(
+        path: &[MultiPool<MockPool, MockPool>],
+        limits: &[u32],
+    ) -> MultiStep<MockStep, MockStep> {
+        path.iter()
+            .zip(limits)
+            .rev()
+            .fold(None, |next, (pool, &limit)| {
+                Some(pool.make_step(Some(limit.into()), next))
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn make_step_splits_legs() {
+        let path: [MultiPool<MockPool, MockPool>; 3] = [
+            MultiPool::Left(pool(1, [Asset::Native, JETTON])),
+            MultiPool::Left(pool(2, [JETTON, EXTRA])),
+            MultiPool::Right(pool(3, [EXTRA, Asset::Native])),
+        ];
+
+        let legs: Vec<_> = make_steps(&path, &[10, 20, 30]).legs.into_iter().collect();
+        assert_eq!(legs.len(), 2);
+
+        let MultiLegStep::Left(ref left) = legs[0].step else {
+            panic!("first leg must go through the left DEX");
+        };
+        assert_eq!(left, &[(1, Some(10u32.into())), (2, Some(20u32.into()))]);
+        assert_eq!(legs[0].hops, [path[0].assets(), path[1].assets()]);
+        assert_eq!(legs[0].amount_out_min, 20u32.into());
+
+        let MultiLegStep::Right(ref right) = legs[1].step else {
+            panic!("second leg must go through the right DEX");
+        };
+        assert_eq!(right, &[(3, Some(30u32.into()))]);
+        assert_eq!(legs[1].hops, [path[2].assets()]);
+        assert_eq!(legs[1].amount_out_min, 30u32.into());
+    }
+
+    #[test]
+    fn estimate_gas_sums_legs() {
+        let pools: [MultiPool<MockPool, MockPool>; 4] = [
+            MultiPool::Left(pool(1, [Asset::Native, JETTON])),
+            MultiPool::Left(pool(2, [JETTON, EXTRA])),
+            MultiPool::Right(pool(3, [EXTRA, JETTON])),
+            MultiPool::Left(pool(4, [JETTON, Asset::Native])),
+        ];
+
+        let mut path = SwapPath::new(Asset::Native);
+        for pool in &pools {
+            path.push(pool);
+        }
+        assert_eq!(multi_dex().estimate_gas(&path), 1030u32.into());
+    }
+
+    #[tokio::test]
+    async fn make_body_single_dex() {
+        let path: [MultiPool<MockPool, MockPool>; 2] = [
+            MultiPool::Right(pool(1, [Asset::Native, JETTON])),
+            MultiPool::Right(pool(2, [JETTON, Asset::Native])),
+        ];
+
+        let bodies = multi_dex()
+            .make_body(
+                1,
+                MsgAddress::NULL,
+                Asset::Native,
+                100u32.into(),
+                make_steps(&path, &[50, 110]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(bodies.len(), 1);
+        let MultiBody::Right(ref body) = bodies[0].body else {
+            panic!("path goes through the right DEX");
+        };
+        assert_eq!(
+            body.step,
+            [(1, Some(50u32.into())), (2, Some(110u32.into()))]
+        );
+        assert_eq!(bodies[0].value, 2100u32.into());
+    }
+
+    #[tokio::test]
+    async fn make_body_per_leg() {
+        let path: [MultiPool<MockPool, MockPool>; 4] = [
+            MultiPool::Left(pool(1, [Asset::Native, JETTON])),
+            MultiPool::Left(pool(2, [JETTON, EXTRA])),
+            MultiPool::Right(pool(3, [JETTON, EXTRA])),
+            MultiPool::Left(pool(4, [JETTON, Asset::Native])),
+        ];
+
+        let bodies = multi_dex()
+            .make_body(
+                7,
+                MsgAddress::NULL,
+                Asset::Native,
+                100u32.into(),
+                make_steps(&path, &[50, 40, 30, 120]),
+            )
+            .await
+            .unwrap();
+
+        let bodies: Vec<_> = bodies
+            .into_iter()
+            .map(|body| match body.body {
+                MultiBody::Left(swap) => (false, body.value, swap),
+                MultiBody::Right(swap) => (true, body.value, swap),
+            })
+            .collect();
+        let expected = [
+            // TONs of the first leg are attached along with gas
+            (
+                false,
+                120u32,
+                Asset::Native,
+                100u32,
+                vec![(1, 50u32), (2, 40)],
+            ),
+            // the next legs swap output of the previous ones
+            (true, 1000, EXTRA, 40, vec![(3, 30)]),
+            (false, 10, JETTON, 30, vec![(4, 120)]),
+        ]
+        .map(|(right, value, asset_in, amount_in, step)| {
+            (
+                right,
+                BigUint::from(value),
+                MockBody {
+                    query_id: 7,
+                    asset_in,
+                    amount_in: amount_in.into(),
+                    step: step
+                        .into_iter()
+                        .map(|(id, limit)| (id, Some(limit.into())))
+                        .collect(),
+                },
+            )
+        });
+        assert_eq!(bodies, expected);
+    }
+
+    #[tokio::test]
+    async fn make_body_rejects_disconnected_path() {
+        let path: [MultiPool<MockPool, MockPool>; 2] = [
+            MultiPool::Left(pool(1, [Asset::Native, JETTON])),
+            MultiPool::Right(pool(2, [EXTRA, Asset::Native])),
+        ];
+
+        assert!(multi_dex()
+            .make_body(
+                1,
+                MsgAddress::NULL,
+                Asset::Native,
+                100u32.into(),
+                make_steps(&path, &[50, 100]),
+            )
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn ids_keyed_by_dex() {
+        let left: MultiPool<MockPool, MockPool> = MultiPool::Left(pool(1, [Asset::Native, JETTON]));
+        let right: MultiPool<MockPool, MockPool> =
+            MultiPool::Right(pool(1, [Asset::Native, JETTON]));
+        assert_ne!(left.id(), right.id());
+    }
+}
@@ -4,8 +4,9 @@ use std::sync::Arc;
 
 use impl_tools::autoimpl;
 use num::{rational::Ratio, BigUint, One, ToPrimitive};
+use tlb_ton::MsgAddress;
 
-use crate::Asset;
+use crate::{Asset, ConstantProductCurve};
 
 #[autoimpl(for<T: trait + ?Sized> &T, &mut T, Box<T>, Arc<T>)]
 pub trait DexPool {
@@ -14,6 +15,10 @@ pub trait DexPool {
 
     fn id(&self) -> Self::ID;
 
+    /// Address of the pool contract, which reserves change only by its
+    /// transactions
+    fn address(&self) -> MsgAddress;
+
     fn assets(&self) -> [Asset; 2];
     /// In the same order as in [`.assets()`](DEXPool::assets)
     fn reserves(&self) -> [&BigUint; 2];
@@ -70,6 +75,16 @@ pub trait DexPool {
             .unwrap()
     }
 
+    /// Returns [`ConstantProductCurve`] for swapping `asset_in` if the pool
+    /// follows constant-product formula, `None` otherwise
+    #[inline]
+    fn constant_product_curve(&self, asset_in: Asset) -> Option<ConstantProductCurve> {
+        Some(ConstantProductCurve::new(
+            self.reserves_in_out(asset_in),
+            self.trade_fees(),
+        ))
+    }
+
     fn estimate_swap_out(&self, asset_in: Asset, amount_in: &BigUint) -> BigUint {
         let [reserve_in, reserve_out] = self.reserves_in_out(asset_in);
         if [amount_in, reserve_in, reserve_out]
@@ -92,8 +107,6 @@ pub trait DexPool {
 
 #[cfg(test)]
 mod tests {
-    use tlb_ton::MsgAddress;
-
     use super::*;
 
     struct MockPool {
@@ -109,6 +122,10 @@ mod tests {
             ()
         }
 
+        fn address(&self) -> MsgAddress {
+            MsgAddress::NULL
+        }
+
         fn assets(&self) -> [Asset; 2] {
             self.assets
         }
@@ -0,0 +1,78 @@
+use core::time::Duration;
+
+use std::sync::Arc;
+
+use impl_tools::autoimpl;
+use serde::Deserialize;
+use serde_with::{serde_as, DurationSecondsWithFrac};
+
+/// Observable inputs to score risk of a [`SwapPath`](crate::SwapPath) on
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskFactors {
+    /// Number of swaps in the path
+    pub hops: usize,
+    /// The smallest ratio of incoming reserve to the amount swapped
+    /// through the pool among all hops
+    pub min_reserve_depth: f64,
+    /// Time passed since reserves of the most recently updated pool
+    /// have changed, if known
+    pub min_reserves_age: Option<Duration>,
+    /// Relative loss of the output compared to swapping at marginal rates,
+    /// i.e. `1 - estimate_swap_out / (amount_in * rate_with_fees)`
+    pub price_impact: f64,
+}
+
+#[autoimpl(for<T: trait + ?Sized> &T, &mut T, Box<T>, Arc<T>)]
+pub trait RiskModel {
+    /// Returns non-negative risk score: the higher, the riskier
+    fn risk(&self, factors: &RiskFactors) -> f64;
+}
+
+/// Weighted sum of normalized [`RiskFactors`]
+#[serde_as]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WeightedRiskModel {
+    /// Weight per each hop
+    pub hops_weight: f64,
+    /// Weight of `1 / min_reserve_depth`
+    pub reserve_depth_weight: f64,
+    /// Weight of the recency of reserves update: a pool which has just
+    /// changed is likely to be contested by other traders
+    pub recent_update_weight: f64,
+    /// Reserves updated this long ago contribute a half of
+    /// `recent_update_weight`
+    #[serde_as(as = "DurationSecondsWithFrac<f64>")]
+    pub recent_update_half_life: Duration,
+    /// Weight of the price impact
+    pub price_impact_weight: f64,
+}
+
+impl Default for WeightedRiskModel {
+    fn default() -> Self {
+        Self {
+            hops_weight: 0.05,
+            reserve_depth_weight: 1.0,
+            recent_update_weight: 0.2,
+            recent_update_half_life: Duration::from_secs(30),
+            price_impact_weight: 1.0,
+        }
+    }
+}
+
+impl RiskModel for WeightedRiskModel {
+    fn risk(&self, factors: &RiskFactors) -> f64 {
+        let recency = factors.min_reserves_age.map_or(0.0, |age| {
+            if self.recent_update_half_life.is_zero() {
+                return 0.0;
+            }
+            let half_life = self.recent_update_half_life.as_secs_f64();
+            half_life / (half_life + age.as_secs_f64())
+        });
+
+        self.hops_weight * factors.hops as f64
+            + self.reserve_depth_weight / factors.min_reserve_depth
+            + self.recent_update_weight * recency
+            + self.price_impact_weight * factors.price_impact.max(0.0)
+    }
+}
@@ -1,12 +1,13 @@
 use core::{
     fmt::{self, Display},
     iter,
+    time::Duration,
 };
 
 use itertools::{unfold, FoldWhile, Itertools};
-use num::BigUint;
+use num::{iter::range_inclusive, BigInt, BigUint, ToPrimitive};
 
-use crate::{Asset, DexPool};
+use crate::{Asset, ConstantProductCurve, DexPool, RiskFactors, RiskModel};
 
 #[derive(Debug, Clone)]
 pub struct SwapStep<DP> {
@@ -33,6 +34,10 @@ where
     pub fn estimate_swap_out(&self, amount_in: &BigUint) -> BigUint {
         self.pool.estimate_swap_out(self.asset_in, &amount_in)
     }
+
+    pub fn constant_product_curve(&self) -> Option<ConstantProductCurve> {
+        self.pool.constant_product_curve(self.asset_in)
+    }
 }
 
 impl<DP> Display for SwapStep<DP>
@@ -126,12 +131,93 @@ where
             .into_inner()
     }
 
-    pub fn optimal_amount_in(&self) -> BigUint {
-        todo!()
+    /// Returns [`ConstantProductCurve`] of the whole path if all of its pools
+    /// follow constant-product formula
+    pub fn constant_product_curve(&self) -> Option<ConstantProductCurve> {
+        self.iter_steps()
+            .try_fold(ConstantProductCurve::identity(), |curve, step| {
+                Some(curve.then(step.constant_product_curve()?))
+            })
+    }
+
+    /// Returns `estimate_swap_out(amount_in) - amount_in`
+    pub fn estimate_profit(&self, amount_in: &BigUint) -> BigInt {
+        BigInt::from(self.estimate_swap_out(amount_in.clone())) - BigInt::from(amount_in.clone())
     }
 
-    pub fn risk(&self) -> f64 {
-        todo!()
+    /// Returns `amount_in` in range `[0, max_amount_in]` that maximizes
+    /// [profit](SwapPath::estimate_profit), or zero if no amount is profitable.
+    pub fn optimal_amount_in(&self, max_amount_in: &BigUint) -> BigUint {
+        if let Some(curve) = self.constant_product_curve() {
+            return curve.optimal_amount_in(max_amount_in);
+        }
+
+        // profit is concave for any chain of pools with non-increasing
+        // marginal rates, so ternary search converges to the maximum
+        let three = BigUint::from(3u32);
+        let (mut lo, mut hi) = (BigUint::ZERO, max_amount_in.clone());
+        while &hi - &lo > three {
+            let third = (&hi - &lo) / &three;
+            let (m1, m2) = (&lo + &third, &hi - &third);
+            if self.estimate_profit(&m1) < self.estimate_profit(&m2) {
+                lo = m1;
+            } else {
+                hi = m2;
+            }
+        }
+
+        range_inclusive(lo, hi)
+            .map(|amount_in| (self.estimate_profit(&amount_in), amount_in))
+            .max_by(|(p1, _), (p2, _)| p1.cmp(p2))
+            .filter(|(profit, _)| profit > &BigInt::ZERO)
+            .map_or(BigUint::ZERO, |(_, amount_in)| amount_in)
+    }
+
+    /// Collects [`RiskFactors`] of swapping `amount_in` through the path.
+    /// `reserves_age` returns time passed since reserves of given pool
+    /// have changed, if known.
+    pub fn risk_factors(
+        &self,
+        amount_in: &BigUint,
+        mut reserves_age: impl FnMut(&DP) -> Option<Duration>,
+    ) -> RiskFactors {
+        let mut min_reserve_depth = f64::INFINITY;
+        let mut min_reserves_age: Option<Duration> = None;
+        let mut marginal_rate = 1.0;
+        let mut amount = amount_in.clone();
+        for step in self.iter_steps() {
+            let [reserve_in, _] = step.pool().reserves_in_out(step.asset_in());
+            min_reserve_depth =
+                min_reserve_depth.min(reserve_in.to_f64().unwrap() / amount.to_f64().unwrap());
+            if let Some(age) = reserves_age(step.pool()) {
+                min_reserves_age = Some(min_reserves_age.map_or(age, |min| min.min(age)));
+            }
+            marginal_rate *= step.pool().rate_with_fees(step.asset_in());
+            amount = step.estimate_swap_out(&amount);
+        }
+
+        let ideal_amount_out = amount_in.to_f64().unwrap() * marginal_rate;
+        RiskFactors {
+            hops: self.len(),
+            min_reserve_depth,
+            min_reserves_age,
+            price_impact: if ideal_amount_out > 0.0 {
+                1.0 - amount.to_f64().unwrap() / ideal_amount_out
+            } else {
+                0.0
+            },
+        }
+    }
+
+    /// Scores risk of swapping `amount_in` through the path with given
+    /// [`RiskModel`]
+    pub fn risk(
+        &self,
+        model: impl RiskModel,
+        amount_in: &BigUint,
+        reserves_age: impl FnMut(&DP) -> Option<Duration>,
+    ) -> f64 {
+        model.risk(&self.risk_factors(amount_in, reserves_age))
     }
 }
 
@@ -154,3 +240,76 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tlb_ton::MsgAddress;
+
+    use crate::testing::MockPool;
+
+    use super::*;
+
+    fn cycle(reserves: [[u32; 2]; 3], constant_product: bool) -> SwapPath<MockPool> {
+        let [a, b, c] = [
+            Asset::Native,
+            Asset::Jetton(MsgAddress::NULL),
+            Asset::ExtraCurrency { currency_id: 1 },
+        ];
+        let mut p = SwapPath::new(a);
+        let pool = |id, assets, reserves| MockPool {
+            constant_product,
+            ..MockPool::new(id, assets, reserves)
+        };
+        p.extend([
+            pool(1, [a, b], reserves[0]),
+            pool(2, [b, c], reserves[1]),
+            pool(3, [c, a], reserves[2]),
+        ]);
+        p
+    }
+
+    #[test]
+    fn optimal_amount_in() {
+        const RESERVES: [[u32; 2]; 3] = [[10_000, 20_000], [30_000, 25_000], [5_000, 4_000]];
+        let max_amount_in = BigUint::from(1_000_000u32);
+
+        let best_profit = (0u32..10_000)
+            .map(|amount_in| cycle(RESERVES, true).estimate_profit(&amount_in.into()))
+            .max()
+            .unwrap();
+
+        for constant_product in [true, false] {
+            let p = cycle(RESERVES, constant_product);
+            let amount_in = p.optimal_amount_in(&max_amount_in);
+            assert_eq!(p.estimate_profit(&amount_in), best_profit);
+        }
+    }
+
+    #[test]
+    fn optimal_amount_in_capped() {
+        let p = cycle([[10_000, 20_000], [30_000, 25_000], [5_000, 4_000]], true);
+        assert_eq!(p.optimal_amount_in(&100u32.into()), 100u32.into());
+    }
+
+    #[test]
+    fn optimal_amount_in_unprofitable() {
+        for constant_product in [true, false] {
+            let p = cycle([[10_000, 20_000], [30_000, 16_000], [5_000, 3_100]], constant_product);
+            assert_eq!(p.optimal_amount_in(&1_000_000u32.into()), BigUint::ZERO);
+        }
+    }
+
+    #[test]
+    fn risk_factors() {
+        let p = cycle([[10_000, 20_000], [30_000, 25_000], [5_000, 4_000]], true);
+        let factors = p.risk_factors(&100u32.into(), |pool| {
+            (pool.reserves[0] == 30_000u32.into()).then(|| Duration::from_secs(5))
+        });
+
+        assert_eq!(factors.hops, 3);
+        // 10_000 / 100, 30_000 / 197, 5_000 / 162
+        assert!((factors.min_reserve_depth - 5_000.0 / 162.0).abs() < 1e-9);
+        assert_eq!(factors.min_reserves_age, Some(Duration::from_secs(5)));
+        assert!(factors.price_impact > 0.0 && factors.price_impact < 0.1);
+    }
+}
@@ -0,0 +1,168 @@
+//! Mock pools and DEXes for tests, shared with dependent crates through
+//! `testing` feature
+
+use async_trait::async_trait;
+use num::{rational::Ratio, BigUint};
+use tlb::{BitWriterExt, CellBuilder, CellBuilderError, CellSerialize};
+use tlb_ton::MsgAddress;
+
+use crate::{
+    ton_utils::transactions::TransactionMessage, Asset, ConstantProductCurve, Dex, DexBody,
+    DexPayout, DexPool, SwapPath,
+};
+
+/// IDs of pools the swap goes through along with their `amount_out_min`
+pub type MockStep = Vec<(u8, Option<BigUint>)>;
+
+/// Pool taking 0.3% fee from the incoming asset
+#[derive(Debug, Clone)]
+pub struct MockPool {
+    pub id: u8,
+    pub assets: [Asset; 2],
+    pub reserves: [BigUint; 2],
+    /// Whether [`ConstantProductCurve`] is exposed, so that optimal
+    /// amount in can be found without numeric search
+    pub constant_product: bool,
+}
+
+impl MockPool {
+    pub fn new(id: u8, assets: [Asset; 2], reserves: [u32; 2]) -> Self {
+        Self {
+            id,
+            assets,
+            reserves: reserves.map(Into::into),
+            constant_product: true,
+        }
+    }
+}
+
+impl DexPool for MockPool {
+    type ID = u8;
+    type Step = MockStep;
+
+    fn id(&self) -> Self::ID {
+        self.id
+    }
+
+    fn address(&self) -> MsgAddress {
+        MsgAddress::NULL
+    }
+
+    fn assets(&self) -> [Asset; 2] {
+        self.assets
+    }
+
+    fn reserves(&self) -> [&BigUint; 2] {
+        let [ref r0, ref r1] = &self.reserves;
+        [r0, r1]
+    }
+
+    fn trade_fees(&self) -> [Ratio<BigUint>; 2] {
+        [
+            Ratio::new(997u32.into(), 1000u32.into()),
+            Ratio::from_integer(1u32.into()),
+        ]
+    }
+
+    fn make_step(&self, amount_out_min: Option<BigUint>, next: Option<Self::Step>) -> Self::Step {
+        let mut step = vec![(self.id, amount_out_min)];
+        step.extend(next.into_iter().flatten());
+        step
+    }
+
+    fn constant_product_curve(&self, asset_in: Asset) -> Option<ConstantProductCurve> {
+        self.constant_product
+            .then(|| ConstantProductCurve::new(self.reserves_in_out(asset_in), self.trade_fees()))
+    }
+}
+
+/// Swap built by [`MockDex`], which keeps the arguments it was built with.
+/// Only query ID and IDs of pools are serialized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockBody {
+    pub query_id: u64,
+    pub asset_in: Asset,
+    pub amount_in: BigUint,
+    pub step: MockStep,
+}
+
+impl CellSerialize for MockBody {
+    fn store(&self, builder: &mut CellBuilder) -> Result<(), CellBuilderError> {
+        builder.pack(self.query_id)?;
+        for (id, _) in &self.step {
+            builder.pack(*id)?;
+        }
+        Ok(())
+    }
+}
+
+/// DEX of fixed pools, which charges `hop_gas` per hop and swaps through
+/// all of them in a single message
+#[derive(Debug, Clone, Default)]
+pub struct MockDex {
+    pub pools: Vec<MockPool>,
+    pub hop_gas: u32,
+}
+
+impl MockDex {
+    pub fn new(hop_gas: u32) -> Self {
+        Self {
+            pools: Vec::new(),
+            hop_gas,
+        }
+    }
+
+    fn gas(&self, hops: usize) -> BigUint {
+        (self.hop_gas * hops as u32).into()
+    }
+}
+
+#[async_trait]
+impl Dex for MockDex {
+    type Pool = MockPool;
+    type Body = MockBody;
+
+    async fn get_pools(&self) -> anyhow::Result<Vec<Self::Pool>> {
+        Ok(self.pools.clone())
+    }
+
+    /// Reserves of mock pools never change
+    async fn update_pool(&self, _pool: &mut Self::Pool) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+
+    fn estimate_gas(&self, path: &SwapPath<&Self::Pool>) -> BigUint {
+        self.gas(path.len())
+    }
+
+    async fn make_body(
+        &self,
+        query_id: u64,
+        _sender: MsgAddress,
+        asset_in: Asset,
+        amount_in: BigUint,
+        steps: <Self::Pool as DexPool>::Step,
+    ) -> anyhow::Result<Vec<DexBody<Self::Body>>> {
+        let gas = self.gas(steps.len());
+        let value = match asset_in {
+            Asset::Native => &gas + &amount_in,
+            _ => gas.clone(),
+        };
+        Ok(vec![DexBody {
+            dst: MsgAddress::NULL,
+            gas,
+            value,
+            body: MockBody {
+                query_id,
+                asset_in,
+                amount_in,
+                step: steps,
+            },
+        }])
+    }
+
+    /// Swaps never pay out
+    async fn parse_payout(&self, _msg: &TransactionMessage) -> anyhow::Result<Option<DexPayout>> {
+        Ok(None)
+    }
+}
@@ -1,7 +1,11 @@
+use std::sync::Arc;
+
 use aceton_core::Asset;
+use aceton_utils::RateLimiter;
 use chrono::{DateTime, Utc};
 use num::BigUint;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde_with::{serde_as, DisplayFromStr};
 use tlb_ton::MsgAddress;
@@ -14,41 +18,59 @@ const BASE_URL: &str = "https://api.dedust.io/v2";
 #[derive(Default)]
 pub struct DedustHTTPClient {
     client: Client,
+    limiter: Option<Arc<RateLimiter>>,
 }
 
 impl DedustHTTPClient {
     pub fn new(client: Client) -> Self {
-        Self { client }
+        Self {
+            client,
+            limiter: None,
+        }
     }
 
-    pub async fn get_available_pools(&self) -> anyhow::Result<Vec<DedustPool>> {
-        self.client
-            .get(format!("{BASE_URL}/pools"))
+    pub fn with_limiter(mut self, limiter: impl Into<Option<Arc<RateLimiter>>>) -> Self {
+        self.limiter = limiter.into();
+        self
+    }
+
+    async fn send<T>(&self, request: RequestBuilder) -> anyhow::Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let _permit = match &self.limiter {
+            Some(limiter) => Some(limiter.acquire().await),
+            None => None,
+        };
+        request
             .send()
             .await?
+            .error_for_status()?
             .json()
             .await
             .map_err(Into::into)
     }
 
+    pub async fn get_available_pools(&self) -> anyhow::Result<Vec<DedustPool>> {
+        self.send(self.client.get(format!("{BASE_URL}/pools")))
+            .await
+    }
+
     pub async fn get_latest_trades(
         &self,
         pool: MsgAddress,
         limit: impl Into<Option<usize>>,
     ) -> anyhow::Result<Vec<Trade>> {
-        self.client
-            .get(
+        self.send(
+            self.client.get(
                 Url::parse_with_params(
                     format!("{BASE_URL}/pools/{pool}/trades").as_str(),
                     limit.into().map(|limit| ("page_size", limit.to_string())),
                 )
                 .unwrap(),
-            )
-            .send()
-            .await?
-            .json()
-            .await
-            .map_err(Into::into)
+            ),
+        )
+        .await
     }
 }
 
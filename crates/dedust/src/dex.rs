@@ -1,6 +1,18 @@
-use std::collections::{hash_map::Entry, HashMap};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::Arc,
+};
 
-use aceton_core::{ton_utils::contract::TonContract, Asset, Dex, DexBody, DexPool};
+use aceton_core::{
+    ton_utils::{
+        contract::TonContract,
+        jetton::{JettonMasterI, JettonTransfer, JettonTransferNotification, JettonWalletI},
+        transactions::TransactionMessage,
+    },
+    Asset, Dex, DexBody, DexPayout, DexPool, SwapPath,
+};
+use aceton_utils::RateLimiter;
+use anyhow::anyhow;
 use async_trait::async_trait;
 use chrono::{Local, TimeDelta};
 use futures::{
@@ -11,45 +23,190 @@ use futures::{
 };
 use lazy_static::lazy_static;
 use num::{BigUint, One};
+use tlb::{
+    BitReaderExt, BitWriterExt, Cell, CellBuilder, CellBuilderError, CellDeserialize, CellParser,
+    CellParserError, CellSerialize,
+};
 use tlb_ton::MsgAddress;
 use tonlibjson_client::ton::TonClient;
 use tracing::{debug, instrument};
 
 use crate::{
-    api::DedustHTTPClient, DedustFactoryI, DedustNativeVaultSwap, DedustPool, DedustPoolI,
-    DedustPoolType, SwapParams,
+    api::DedustHTTPClient, DedustFactoryI, DedustJettonVaultSwap, DedustNativeVaultPayout,
+    DedustNativeVaultSwap, DedustPool, DedustPoolI, DedustPoolType, SwapParams,
 };
 
 pub struct DeDust {
     ton_client: TonClient,
+    ton_limiter: Option<Arc<RateLimiter>>,
     api: DedustHTTPClient,
     factory: MsgAddress,
     vaults: Mutex<HashMap<Asset, MsgAddress>>,
+    /// (jetton_master, owner) -> jetton_wallet
+    jetton_wallets: Mutex<HashMap<(MsgAddress, MsgAddress), MsgAddress>>,
 }
 
 impl DeDust {
     pub fn new(ton_client: TonClient, factory: MsgAddress, http_client: reqwest::Client) -> Self {
         Self {
             ton_client,
+            ton_limiter: None,
             factory,
             api: DedustHTTPClient::new(http_client),
             vaults: Default::default(),
+            jetton_wallets: Default::default(),
         }
     }
 
+    /// Limits get-method calls to TON client
+    pub fn with_ton_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.ton_limiter = Some(limiter);
+        self
+    }
+
+    /// Limits requests to HTTP API
+    pub fn with_api_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.api = self.api.with_limiter(limiter);
+        self
+    }
+
+    fn contract(&self, address: MsgAddress) -> TonContract {
+        TonContract::new(self.ton_client.clone(), address).with_limiter(self.ton_limiter.clone())
+    }
+
     #[instrument(skip(self))]
     async fn vault_address(&self, asset: Asset) -> anyhow::Result<MsgAddress> {
         let mut vaults = self.vaults.lock().await;
         Ok(match vaults.entry(asset) {
             Entry::Occupied(entry) => *entry.get(),
             Entry::Vacant(entry) => {
-                let factory = TonContract::new(self.ton_client.clone(), self.factory);
+                let factory = self.contract(self.factory);
                 let address = factory.get_vault_address(asset).await?;
                 debug!(%asset, %address, "resolved vault address");
                 *entry.insert(address)
             }
         })
     }
+
+    /// Gas for swap through `hops` pools and payout
+    fn swap_gas(hops: usize) -> BigUint {
+        &*SWAP_EXTERNAL_PAYOUT + &*SWAP_STEP_GAS * hops
+    }
+
+    /// Gas for swap of `asset_in` including the transfer to its vault
+    fn gas(asset_in: Asset, hops: usize) -> BigUint {
+        let swap_gas = Self::swap_gas(hops);
+        match asset_in {
+            Asset::Jetton(_) => swap_gas + &*JETTON_TRANSFER_GAS,
+            _ => swap_gas,
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn jetton_wallet_address(
+        &self,
+        master: MsgAddress,
+        owner: MsgAddress,
+    ) -> anyhow::Result<MsgAddress> {
+        let mut jetton_wallets = self.jetton_wallets.lock().await;
+        Ok(match jetton_wallets.entry((master, owner)) {
+            Entry::Occupied(entry) => *entry.get(),
+            Entry::Vacant(entry) => {
+                let master = self.contract(master);
+                let address = master.get_wallet_address(owner).await?;
+                debug!(%address, "resolved jetton wallet address");
+                *entry.insert(address)
+            }
+        })
+    }
+
+    /// Jetton master of `jetton_wallet`, `None` unless it is the wallet
+    /// of `owner` according to the master itself
+    async fn jetton_master_of(
+        &self,
+        jetton_wallet: MsgAddress,
+        owner: MsgAddress,
+    ) -> anyhow::Result<Option<MsgAddress>> {
+        let cached = self
+            .jetton_wallets
+            .lock()
+            .await
+            .iter()
+            .find(|(&(_, o), &w)| o == owner && w == jetton_wallet)
+            .map(|(&(master, _), _)| master);
+        if cached.is_some() {
+            return Ok(cached);
+        }
+        // anyone can claim to be a jetton wallet
+        let master = match self.contract(jetton_wallet).get_wallet_data().await {
+            Ok(data) => data.master,
+            Err(err) => {
+                debug!(%jetton_wallet, ?err, "not a jetton wallet");
+                return Ok(None);
+            }
+        };
+        Ok((self.jetton_wallet_address(master, owner).await? == jetton_wallet).then_some(master))
+    }
+}
+
+/// Body of the message to start swap with
+pub enum DedustSwapBody {
+    /// Sent to native vault along with TONs to swap
+    Native(DedustNativeVaultSwap<SwapResultPayload, SwapResultPayload>),
+    /// Sent to sender's jetton wallet to transfer jettons to the jetton vault
+    Jetton(JettonTransfer<(), DedustJettonVaultSwap<SwapResultPayload, SwapResultPayload>>),
+}
+
+impl CellSerialize for DedustSwapBody {
+    fn store(&self, builder: &mut CellBuilder) -> Result<(), CellBuilderError> {
+        match self {
+            Self::Native(swap) => swap.store(builder),
+            Self::Jetton(transfer) => transfer.store(builder),
+        }
+    }
+}
+
+/// Payload attached to the payout, so that the sender can tell
+/// the result of the swap:
+/// swap_result#_ query_id:uint64 fulfilled:Bool = SwapResultPayload;
+pub struct SwapResultPayload {
+    pub query_id: u64,
+    pub fulfilled: bool,
+}
+
+impl CellSerialize for SwapResultPayload {
+    fn store(&self, builder: &mut CellBuilder) -> Result<(), CellBuilderError> {
+        builder.pack(self.query_id)?.pack(self.fulfilled)?;
+        Ok(())
+    }
+}
+
+impl<'de> CellDeserialize<'de> for SwapResultPayload {
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        Ok(Self {
+            query_id: parser.unpack()?,
+            fulfilled: parser.unpack()?,
+        })
+    }
+}
+
+/// Message which reports the result of a swap to its sender, which
+/// is not checked yet to come from DeDust
+enum DedustPayout {
+    /// Native vault pays out directly
+    Native(SwapResultPayload),
+    /// Jetton vault transfers jettons to the sender's jetton wallet,
+    /// which notifies the sender
+    Jetton(JettonTransferNotification<SwapResultPayload>),
+}
+
+impl DedustPayout {
+    fn parse(body: &Cell) -> Option<Self> {
+        if let Ok(payout) = body.parse_fully::<DedustNativeVaultPayout<SwapResultPayload>>() {
+            return payout.payload.map(Self::Native);
+        }
+        body.parse_fully().map(Self::Jetton).ok()
+    }
 }
 
 lazy_static! {
@@ -59,12 +216,14 @@ lazy_static! {
         100_000_000u32 + // swap_external: 0.08 TON ~= 0.1 TON
         100_000_000u32 // payout: 0.09 TON ~= 0.1 TON
     ).into();
+    // jetton transfer to the vault with excesses returned
+    static ref JETTON_TRANSFER_GAS: BigUint = 50_000_000u32.into();
 }
 
 #[async_trait]
 impl Dex for DeDust {
     type Pool = DedustPool;
-    type Body = DedustNativeVaultSwap<(), ()>;
+    type Body = DedustSwapBody;
 
     #[instrument(skip(self))]
     async fn get_pools(&self) -> anyhow::Result<Vec<Self::Pool>> {
@@ -103,7 +262,7 @@ impl Dex for DeDust {
 
     #[instrument(skip_all, fields(%pool.address))]
     async fn update_pool(&self, pool: &mut Self::Pool) -> anyhow::Result<bool> {
-        let pool_contract = TonContract::new(self.ton_client.clone(), pool.address);
+        let pool_contract = self.contract(pool.address);
         let new_reserves = pool_contract.get_reserves().await?;
         let is_updated = pool.reserves != new_reserves;
         pool.reserves = new_reserves;
@@ -113,28 +272,338 @@ impl Dex for DeDust {
         Ok(is_updated)
     }
 
+    fn estimate_gas(&self, path: &SwapPath<&Self::Pool>) -> BigUint {
+        Self::gas(path.asset_in(), path.len())
+    }
+
     async fn make_body(
         &self,
         query_id: u64,
+        sender: MsgAddress,
         asset_in: Asset,
         amount_in: BigUint,
         steps: <Self::Pool as DexPool>::Step,
-    ) -> anyhow::Result<DexBody<Self::Body>> {
-        Ok(DexBody {
-            dst: self.vault_address(asset_in).await?,
-            gas: &*SWAP_EXTERNAL_PAYOUT + &*SWAP_STEP_GAS * steps.len(),
-            body: DedustNativeVaultSwap {
+    ) -> anyhow::Result<Vec<DexBody<Self::Body>>> {
+        let hops = steps.len();
+        let gas = Self::gas(asset_in, hops);
+        let params = SwapParams {
+            deadline: None,
+            recepient: MsgAddress::NULL,
+            referral: MsgAddress::NULL,
+            fulfill_payload: Some(SwapResultPayload {
+                query_id,
+                fulfilled: true,
+            }),
+            reject_payload: Some(SwapResultPayload {
                 query_id,
-                amount: amount_in,
-                step: steps,
+                fulfilled: false,
+            }),
+        };
+
+        let body = match asset_in {
+            Asset::Native => DexBody {
+                dst: self.vault_address(asset_in).await?,
+                value: &gas + &amount_in,
+                gas,
+                body: DedustSwapBody::Native(DedustNativeVaultSwap {
+                    query_id,
+                    amount: amount_in,
+                    step: steps,
+                    params,
+                }),
+            },
+            Asset::Jetton(master) => DexBody {
+                dst: self.jetton_wallet_address(master, sender).await?,
+                value: gas.clone(),
+                gas,
+                body: DedustSwapBody::Jetton(JettonTransfer {
+                    query_id,
+                    amount: amount_in,
+                    dst: self.vault_address(asset_in).await?,
+                    response_dst: sender,
+                    custom_payload: None,
+                    forward_ton_amount: Self::swap_gas(hops),
+                    forward_payload: DedustJettonVaultSwap {
+                        step: steps,
+                        params,
+                    },
+                }),
+            },
+            Asset::ExtraCurrency { .. } => {
+                return Err(anyhow!("extra currencies are not supported"))
+            }
+        };
+        Ok(vec![body])
+    }
+
+    async fn parse_payout(&self, msg: &TransactionMessage) -> anyhow::Result<Option<DexPayout>> {
+        let (Some(src), Some(recipient), Some(body)) = (msg.src, msg.dst, msg.body.as_deref())
+        else {
+            return Ok(None);
+        };
+        let Some(payout) = DedustPayout::parse(body) else {
+            return Ok(None);
+        };
+        let (asset, result, amount) = match payout {
+            DedustPayout::Native(result) => {
+                if src != self.vault_address(Asset::Native).await? {
+                    debug!(%src, "native payout is not from the vault, ignoring");
+                    return Ok(None);
+                }
+                (Asset::Native, result, None)
+            }
+            DedustPayout::Jetton(notification) => {
+                let Some(master) = self.jetton_master_of(src, recipient).await? else {
+                    return Ok(None);
+                };
+                // sender of the notification is set by the recipient's
+                // jetton wallet, so that it can be trusted once the wallet is
+                let asset = Asset::Jetton(master);
+                if notification.sender != self.vault_address(asset).await? {
+                    debug!(
+                        %src,
+                        sender = %notification.sender,
+                        "jetton payout is not from the vault, ignoring",
+                    );
+                    return Ok(None);
+                }
+                (
+                    asset,
+                    notification.forward_payload,
+                    Some(notification.amount),
+                )
+            }
+        };
+        Ok(Some(DexPayout {
+            query_id: result.query_id,
+            asset,
+            fulfilled: result.fulfilled,
+            amount,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use tlb::{CellSerializeExt, Ref};
+    use tlb_ton::{Coins, UnixTimestamp};
+
+    use crate::{SwapKind, SwapStep, SwapStepParams};
+
+    use super::*;
+
+    const OWNER: MsgAddress = MsgAddress {
+        workchain_id: 0,
+        address: [1; 32],
+    };
+    const VAULT: MsgAddress = MsgAddress {
+        workchain_id: 0,
+        address: [2; 32],
+    };
+    const POOL: MsgAddress = MsgAddress {
+        workchain_id: 0,
+        address: [3; 32],
+    };
+
+    fn result_payload(fulfilled: bool) -> Cell {
+        let mut builder = Cell::builder();
+        builder.pack(5_u64).unwrap().pack(fulfilled).unwrap();
+        builder.into_cell()
+    }
+
+    #[test]
+    fn jetton_swap_body_layout() {
+        let deadline = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let body = DedustSwapBody::Jetton(JettonTransfer {
+            query_id: 5,
+            amount: 1_000_000_u32.into(),
+            dst: VAULT,
+            response_dst: OWNER,
+            custom_payload: None,
+            forward_ton_amount: 200_000_000_u32.into(),
+            forward_payload: DedustJettonVaultSwap {
+                step: SwapStep {
+                    pool: POOL,
+                    params: SwapStepParams {
+                        kind: SwapKind::GivenIn,
+                        limit: 990_u32.into(),
+                        next: None,
+                    },
+                },
                 params: SwapParams {
-                    deadline: None,
-                    recepient: MsgAddress::NULL,
+                    deadline: Some(deadline),
+                    recepient: OWNER,
                     referral: MsgAddress::NULL,
-                    fulfill_payload: Option::<()>::None,
-                    reject_payload: Option::<()>::None,
+                    fulfill_payload: Some(SwapResultPayload {
+                        query_id: 5,
+                        fulfilled: true,
+                    }),
+                    reject_payload: Some(SwapResultPayload {
+                        query_id: 5,
+                        fulfilled: false,
+                    }),
                 },
             },
-        })
+        });
+
+        // swap_params#_ deadline recipient_addr referral_addr
+        // fulfill_payload:(Maybe ^Cell) reject_payload:(Maybe ^Cell)
+        let mut params = Cell::builder();
+        params
+            .pack_as::<_, UnixTimestamp>(deadline)
+            .unwrap()
+            .pack(OWNER)
+            .unwrap()
+            .pack(MsgAddress::NULL)
+            .unwrap()
+            .pack(true)
+            .unwrap()
+            .store_as::<_, Ref>(result_payload(true))
+            .unwrap()
+            .pack(true)
+            .unwrap()
+            .store_as::<_, Ref>(result_payload(false))
+            .unwrap();
+        // swap#e3a0d482 _:SwapStep swap_params:^SwapParams
+        let mut swap = Cell::builder();
+        swap.pack(0xe3a0d482_u32)
+            .unwrap()
+            .pack(POOL)
+            .unwrap()
+            // given_in$0
+            .pack(false)
+            .unwrap()
+            .pack_as::<_, Coins>(BigUint::from(990_u32))
+            .unwrap()
+            // next: nothing$0
+            .pack(false)
+            .unwrap()
+            .store_as::<_, Ref>(params.into_cell())
+            .unwrap();
+        let mut expected = Cell::builder();
+        expected
+            .pack(0x0f8a7ea5_u32)
+            .unwrap()
+            .pack(5_u64)
+            .unwrap()
+            .pack_as::<_, Coins>(BigUint::from(1_000_000_u32))
+            .unwrap()
+            .pack(VAULT)
+            .unwrap()
+            .pack(OWNER)
+            .unwrap()
+            // custom_payload: nothing$0
+            .pack(false)
+            .unwrap()
+            .pack_as::<_, Coins>(BigUint::from(200_000_000_u32))
+            .unwrap()
+            // forward_payload: right$1 ^Cell
+            .pack(true)
+            .unwrap()
+            .store_as::<_, Ref>(swap.into_cell())
+            .unwrap();
+        let expected = expected.into_cell();
+        assert_eq!(body.to_cell().unwrap(), expected);
+
+        let parsed: JettonTransfer<
+            (),
+            DedustJettonVaultSwap<SwapResultPayload, SwapResultPayload>,
+        > = expected.parse_fully().unwrap();
+        assert_eq!(parsed.dst, VAULT);
+        assert_eq!(parsed.amount, 1_000_000_u32.into());
+        let swap = parsed.forward_payload;
+        assert_eq!(swap.step.pool, POOL);
+        assert_eq!(swap.step.params.limit, 990_u32.into());
+        assert!(swap.step.params.next.is_none());
+        assert_eq!(swap.params.deadline, Some(deadline));
+        assert_eq!(swap.params.recepient, OWNER);
+        let fulfill = swap.params.fulfill_payload.unwrap();
+        assert_eq!((fulfill.query_id, fulfill.fulfilled), (5, true));
+        let reject = swap.params.reject_payload.unwrap();
+        assert_eq!((reject.query_id, reject.fulfilled), (5, false));
+    }
+
+    #[test]
+    fn parse_native_payout() {
+        // payout#474f86cf query_id:uint64 payload:(Maybe ^Cell)
+        let mut body = Cell::builder();
+        body.pack(0x474f86cf_u32)
+            .unwrap()
+            .pack(0_u64)
+            .unwrap()
+            .pack(true)
+            .unwrap()
+            .store_as::<_, Ref>(result_payload(false))
+            .unwrap();
+
+        let Some(DedustPayout::Native(result)) = DedustPayout::parse(&body.into_cell()) else {
+            panic!("native payout expected");
+        };
+        assert_eq!((result.query_id, result.fulfilled), (5, false));
+    }
+
+    #[test]
+    fn parse_jetton_payout() {
+        // transfer_notification#7362d09c query_id:uint64 amount:Coins
+        // sender:MsgAddress forward_payload:(Either Cell ^Cell)
+        let mut body = Cell::builder();
+        body.pack(0x7362d09c_u32)
+            .unwrap()
+            .pack(0_u64)
+            .unwrap()
+            .pack_as::<_, Coins>(BigUint::from(1_234_u32))
+            .unwrap()
+            .pack(VAULT)
+            .unwrap()
+            .pack(true)
+            .unwrap()
+            .store_as::<_, Ref>(result_payload(true))
+            .unwrap();
+
+        let Some(DedustPayout::Jetton(notification)) = DedustPayout::parse(&body.into_cell())
+        else {
+            panic!("jetton payout expected");
+        };
+        assert_eq!(notification.amount, 1_234_u32.into());
+        assert_eq!(notification.sender, VAULT);
+        let result = notification.forward_payload;
+        assert_eq!((result.query_id, result.fulfilled), (5, true));
+    }
+
+    #[test]
+    fn parse_not_payout() {
+        // native payout without payload is not a result of our swap
+        let mut body = Cell::builder();
+        body.pack(0x474f86cf_u32)
+            .unwrap()
+            .pack(0_u64)
+            .unwrap()
+            .pack(false)
+            .unwrap();
+        assert!(DedustPayout::parse(&body.into_cell()).is_none());
+
+        // jetton transfer with a text comment
+        let mut comment = Cell::builder();
+        comment.pack(0_u32).unwrap().pack(0x6869_u16).unwrap();
+        let mut body = Cell::builder();
+        body.pack(0x7362d09c_u32)
+            .unwrap()
+            .pack(0_u64)
+            .unwrap()
+            .pack_as::<_, Coins>(BigUint::from(1_234_u32))
+            .unwrap()
+            .pack(VAULT)
+            .unwrap()
+            .pack(true)
+            .unwrap()
+            .store_as::<_, Ref>(comment.into_cell())
+            .unwrap();
+        assert!(DedustPayout::parse(&body.into_cell()).is_none());
+
+        // excesses#d53276db query_id:uint64
+        let mut body = Cell::builder();
+        body.pack(0xd53276db_u32).unwrap().pack(0_u64).unwrap();
+        assert!(DedustPayout::parse(&body.into_cell()).is_none());
     }
 }
@@ -0,0 +1,112 @@
+use aceton_core::ton_utils::contract::{ExitCode, TonContractError};
+use thiserror::Error as ThisError;
+
+/// Thrown by pools which have no liquidity yet
+const POOL_NOT_READY: i32 = 251;
+/// Thrown on zero amount or one exceeding reserves of the pool
+const INVALID_AMOUNT: i32 = 252;
+
+/// Failure of a get-method of DeDust contracts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ThisError)]
+pub enum DedustError {
+    /// Pool or vault was never created for given assets
+    #[error("contract is not deployed")]
+    NotDeployed,
+    /// Contract is of another kind, e.g. a vault instead of a pool
+    #[error("get-method is missing")]
+    MissingGetMethod,
+    /// Pool was created, but liquidity was never deposited to it
+    #[error("pool is not ready")]
+    PoolNotReady,
+    /// Amount to swap can not be quoted by the pool
+    #[error("invalid amount")]
+    InvalidAmount,
+    /// Any other TVM failure, including codes thrown by the contract
+    /// which meaning is unknown
+    #[error(transparent)]
+    Tvm(ExitCode),
+}
+
+impl From<ExitCode> for DedustError {
+    fn from(exit_code: ExitCode) -> Self {
+        match exit_code {
+            ExitCode::Uninitialized => Self::NotDeployed,
+            ExitCode::Unknown => Self::MissingGetMethod,
+            ExitCode::Contract(POOL_NOT_READY) => Self::PoolNotReady,
+            ExitCode::Contract(INVALID_AMOUNT) => Self::InvalidAmount,
+            exit_code => Self::Tvm(exit_code),
+        }
+    }
+}
+
+pub(crate) trait DedustResultExt<T> {
+    /// Describes failed get-method with [`DedustError`], keeping the
+    /// original error as its source
+    fn dedust(self) -> anyhow::Result<T>;
+}
+
+impl<T> DedustResultExt<T> for Result<T, TonContractError> {
+    fn dedust(self) -> anyhow::Result<T> {
+        self.map_err(|err| {
+            let dedust = DedustError::from(err.exit_code);
+            anyhow::Error::new(err).context(dedust)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failed(code: i32) -> Result<(), TonContractError> {
+        Err(TonContractError {
+            exit_code: ExitCode::from_code(code),
+            gas_used: 100,
+            stack: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn from_exit_code() {
+        for (code, expected) in [
+            (-13, DedustError::NotDeployed),
+            (11, DedustError::MissingGetMethod),
+            (POOL_NOT_READY, DedustError::PoolNotReady),
+            (INVALID_AMOUNT, DedustError::InvalidAmount),
+            (42, DedustError::Tvm(ExitCode::Contract(42))),
+            (9, DedustError::Tvm(ExitCode::CellUnderflow)),
+            (13, DedustError::Tvm(ExitCode::OutOfGas)),
+        ] {
+            assert_eq!(DedustError::from(ExitCode::from_code(code)), expected);
+        }
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            DedustError::NotDeployed.to_string(),
+            "contract is not deployed"
+        );
+        assert_eq!(DedustError::PoolNotReady.to_string(), "pool is not ready");
+        assert_eq!(
+            DedustError::Tvm(ExitCode::Contract(42)).to_string(),
+            "contract error: 42"
+        );
+        assert_eq!(
+            DedustError::Tvm(ExitCode::CellUnderflow).to_string(),
+            "cell underflow"
+        );
+    }
+
+    #[test]
+    fn keeps_contract_error() {
+        let err = failed(-13).dedust().unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<DedustError>(),
+            Some(&DedustError::NotDeployed)
+        );
+        let source = err.downcast_ref::<TonContractError>().unwrap();
+        assert_eq!(source.exit_code, ExitCode::Uninitialized);
+        assert_eq!(source.gas_used, 100);
+    }
+}
@@ -11,7 +11,7 @@ use tlb::{
 };
 use tlb_ton::MsgAddress;
 
-use crate::{DedustAsset, DedustPoolType};
+use crate::{error::DedustResultExt, DedustAsset, DedustPoolType};
 
 pub const DEDUST_FACTORY_MAINNET_ADDRESS: MsgAddress = MsgAddress {
     workchain_id: 0,
@@ -26,7 +26,8 @@ pub trait DedustFactoryI: TonContractI {
                 "get_vault_address",
                 [TvmBoxedStackEntryExt::store_cell_as::<_, Data<DedustAsset>>(asset)?].into(),
             )
-            .await??
+            .await?
+            .dedust()?
             .try_into()
             .map_err(|stack| anyhow!("invalid stack: {stack:?}"))?;
         asset.parse_cell_fully_as::<_, Data>()
@@ -47,7 +48,8 @@ pub trait DedustFactoryI: TonContractI {
                 ]
                 .into(),
             )
-            .await??
+            .await?
+            .dedust()?
             .try_into()
             .map_err(|stack| anyhow!("invalid stack: {stack:?}"))?;
         pool.parse_cell_fully_as::<_, Data>()
@@ -70,7 +72,8 @@ pub trait DedustFactoryI: TonContractI {
                 ]
                 .into(),
             )
-            .await??
+            .await?
+            .dedust()?
             .try_into()
             .map_err(|stack| anyhow!("invalid stack: {stack:?}"))?;
         liquidity_deposit_addr.parse_cell_fully_as::<_, Data>()
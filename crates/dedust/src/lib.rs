@@ -1,8 +1,10 @@
 pub mod api;
 mod asset;
+mod error;
 mod factory;
 mod pool;
+mod stable;
 mod vault;
 mod dex;
 
-pub use self::{asset::*, factory::*, pool::*, vault::*, dex::*};
+pub use self::{asset::*, error::*, factory::*, pool::*, vault::*, dex::*};
@@ -1,6 +1,6 @@
 use aceton_core::{
     ton_utils::{adapters::TvmBoxedStackEntryExt, contract::TonContractI},
-    Asset, AssetWithMetadata, DexPool,
+    Asset, AssetWithMetadata, ConstantProductCurve, DexPool,
 };
 use anyhow::anyhow;
 use async_trait::async_trait;
@@ -18,14 +18,15 @@ use tlb_ton::{Coins, MsgAddress, UnixTimestamp};
 
 use aceton_utils::DecimalFloatStrAsRatio;
 
-use crate::DedustAsset;
+use crate::{error::DedustResultExt, stable, DedustAsset};
 
 #[async_trait]
 pub trait DedustPoolI: TonContractI {
     async fn get_assets(&self) -> anyhow::Result<[Asset; 2]> {
         let [asset0, asset1] = self
             .get("get_assets", [].into())
-            .await??
+            .await?
+            .dedust()?
             .try_into()
             .map_err(|stack| anyhow!("invalid output stack size: {stack:?}"))?;
 
@@ -38,7 +39,8 @@ pub trait DedustPoolI: TonContractI {
     async fn get_reserves(&self) -> anyhow::Result<[BigUint; 2]> {
         let [reserve0, reserve1] = self
             .get("get_reserves", [].into())
-            .await??
+            .await?
+            .dedust()?
             .try_into()
             .map_err(|stack| anyhow!("invalid output stack: {stack:?}"))?;
 
@@ -51,7 +53,8 @@ pub trait DedustPoolI: TonContractI {
     async fn is_stable(&self) -> anyhow::Result<bool> {
         let [is_stable] = self
             .get("is_stable", [].into())
-            .await??
+            .await?
+            .dedust()?
             .try_into()
             .map_err(|stack| anyhow!("invalid output stack: {stack:?}"))?;
 
@@ -72,7 +75,8 @@ pub trait DedustPoolI: TonContractI {
                 ]
                 .into(),
             )
-            .await??
+            .await?
+            .dedust()?
             .try_into()
             .map_err(|stack| anyhow!("invalid output stack: {stack:?}"))?;
         Ok(EstimateSwapOutResult {
@@ -170,7 +174,7 @@ where
     fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
         Ok(Self {
             deadline: Some(parser.unpack_as::<_, UnixTimestamp>()?)
-                .filter(|timestamp| *timestamp == DateTime::UNIX_EPOCH),
+                .filter(|timestamp| *timestamp != DateTime::UNIX_EPOCH),
             recepient: parser.unpack()?,
             referral: parser.unpack()?,
             fulfill_payload: parser.parse_as::<_, Option<Ref>>()?,
@@ -326,6 +330,11 @@ impl DexPool for DedustPool {
         self.address
     }
 
+    #[inline]
+    fn address(&self) -> MsgAddress {
+        self.address
+    }
+
     #[inline]
     fn assets(&self) -> [Asset; 2] {
         let (a0, a1) = (self.assets[0].asset, self.assets[1].asset);
@@ -354,7 +363,10 @@ impl DexPool for DedustPool {
                 let [r_in, r_out] = self.reserves_in_out(asset_in);
                 Ratio::new(r_out.clone(), r_in.clone())
             }
-            DedustPoolType::Stable => Ratio::from_integer(1u32.into()),
+            DedustPoolType::Stable => {
+                let [r_in, r_out] = self.reserves_in_out(asset_in);
+                stable::marginal_rate(r_in, r_out)
+            }
         }
     }
 
@@ -370,6 +382,17 @@ impl DexPool for DedustPool {
             .unwrap()
     }
 
+    #[inline]
+    fn constant_product_curve(&self, asset_in: Asset) -> Option<ConstantProductCurve> {
+        match self.r#type {
+            DedustPoolType::Volatile => Some(ConstantProductCurve::new(
+                self.reserves_in_out(asset_in),
+                self.trade_fees(),
+            )),
+            DedustPoolType::Stable => None,
+        }
+    }
+
     fn estimate_swap_out(&self, asset_in: Asset, amount_in: &BigUint) -> BigUint {
         let [reserve_in, reserve_out] = self.reserves_in_out(asset_in);
         if [amount_in, reserve_in, reserve_out]
@@ -391,9 +414,13 @@ impl DexPool for DedustPool {
                 }
                 (fee_out * amount_out).to_integer()
             }
-            // TODO: real stable swap formula
             DedustPoolType::Stable => {
-                (amount_in_with_fee * Ratio::new(reserve_out.clone(), reserve_in.clone())).to_integer()
+                let amount_out = stable::estimate_swap_out(
+                    reserve_in,
+                    reserve_out,
+                    &amount_in_with_fee.to_integer(),
+                );
+                (fee_out * amount_out).to_integer()
             }
         }
     }
@@ -412,8 +439,334 @@ impl DexPool for DedustPool {
 
 #[cfg(test)]
 mod tests {
+    use std::io;
+
+    use aceton_core::ton_utils::emulator::AccountState;
+    use serde::{de::DeserializeOwned, Serialize};
+
     use super::*;
 
+    /// Reads the fixture captured from mainnet by an ignored test, `None`
+    /// if it has not been captured yet
+    fn captured<T: DeserializeOwned>(path: &str) -> Option<T> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => Some(serde_json::from_str(&json).unwrap()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                eprintln!("skipping: {path} is not captured yet");
+                None
+            }
+            Err(err) => panic!("{path}: {err}"),
+        }
+    }
+
+    const STABLE_SWAP_QUOTES: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/fixtures/stable_swap_quotes.json"
+    );
+
+    /// Quote of `estimate_swap_out` get-method of a mainnet stable pool
+    #[serde_as]
+    #[derive(Deserialize)]
+    struct StableSwapQuote {
+        pool: MsgAddress,
+        #[serde_as(as = "[DisplayFromStr; 2]")]
+        reserves: [BigUint; 2],
+        #[serde_as(as = "DecimalFloatStrAsRatio")]
+        trade_fee: Ratio<BigUint>,
+        reversed: bool,
+        #[serde_as(as = "DisplayFromStr")]
+        amount_in: BigUint,
+        #[serde_as(as = "DisplayFromStr")]
+        amount_out: BigUint,
+    }
+
+    fn stable_pool(reserves: [BigUint; 2], trade_fee: Ratio<BigUint>) -> DedustPool {
+        DedustPool {
+            address: MsgAddress::NULL,
+            r#type: DedustPoolType::Stable,
+            assets: [Asset::Native, Asset::Jetton(MsgAddress::NULL)].map(|asset| {
+                AssetWithMetadata {
+                    asset,
+                    metadata: None,
+                }
+            }),
+            trade_fee,
+            reserves,
+        }
+    }
+
+    /// Quotes are captured by [`capture_stable_swap_quotes`]
+    #[test]
+    fn stable_estimate_swap_out_matches_get_method() {
+        let Some(quotes) = captured::<Vec<StableSwapQuote>>(STABLE_SWAP_QUOTES) else {
+            return;
+        };
+        assert!(!quotes.is_empty());
+        for quote in quotes {
+            let pool = stable_pool(quote.reserves, quote.trade_fee);
+            let asset_in = pool.assets()[quote.reversed as usize];
+            assert_eq!(
+                pool.estimate_swap_out(asset_in, &quote.amount_in),
+                quote.amount_out,
+                "{}",
+                quote.pool,
+            );
+        }
+    }
+
+    /// Writes [`STABLE_SWAP_QUOTES`] of `estimate_swap_out` get-method of
+    /// stable pools listed by DeDust API, run by mainnet liteservers.
+    /// Quotes are kept only if reserves have not changed meanwhile.
+    #[tokio::test]
+    #[ignore = "queries DeDust API and mainnet liteservers, overwrites the fixture"]
+    async fn capture_stable_swap_quotes() {
+        use aceton_core::ton_utils::contract::TonContract;
+        use tonlibjson_client::ton::TonClientBuilder;
+        use url::Url;
+
+        use crate::api::DedustHTTPClient;
+
+        let mut ton = TonClientBuilder::from_config_url(
+            Url::parse("https://ton.org/global-config.json").unwrap(),
+            std::time::Duration::from_secs(60),
+        )
+        .build()
+        .await
+        .unwrap();
+        ton.ready().await.unwrap();
+        let pools = DedustHTTPClient::default()
+            .get_available_pools()
+            .await
+            .unwrap();
+
+        let mut quotes = Vec::new();
+        for mut pool in pools
+            .into_iter()
+            .filter(|pool| matches!(pool.r#type, DedustPoolType::Stable))
+            .take(5)
+        {
+            let contract = TonContract::new(ton.clone(), pool.address);
+            pool.reserves = contract.get_reserves().await.unwrap();
+            for reversed in [false, true] {
+                let asset_in = pool.assets()[reversed as usize];
+                for share in [1000u32, 10] {
+                    let amount_in = &pool.reserves[reversed as usize] / share;
+                    let onchain = contract
+                        .estimate_swap_out(asset_in, amount_in.clone())
+                        .await
+                        .unwrap();
+                    // reserves could have changed in between
+                    if contract.get_reserves().await.unwrap() != pool.reserves {
+                        continue;
+                    }
+                    quotes.push(serde_json::json!({
+                        "pool": pool.address,
+                        "reserves": [pool.reserves[0].to_string(), pool.reserves[1].to_string()],
+                        "trade_fee": pool.trade_fee.to_f64().unwrap().to_string(),
+                        "reversed": reversed,
+                        "amount_in": amount_in.to_string(),
+                        "amount_out": onchain.amount_out.to_string(),
+                    }));
+                }
+            }
+        }
+        assert!(!quotes.is_empty(), "no stable pools to capture");
+        std::fs::write(
+            STABLE_SWAP_QUOTES,
+            serde_json::to_string_pretty(&quotes).unwrap() + "\n",
+        )
+        .unwrap();
+    }
+
+    /// Checks that codes thrown by `estimate_swap_out` get-method of
+    /// mainnet pools are decoded as named [`DedustError`](crate::DedustError)s
+    #[tokio::test]
+    #[ignore = "queries DeDust API and mainnet liteservers"]
+    async fn decodes_mainnet_errors() {
+        use aceton_core::ton_utils::contract::TonContract;
+        use tonlibjson_client::ton::TonClientBuilder;
+        use url::Url;
+
+        use crate::{api::DedustHTTPClient, DedustError};
+
+        let mut ton = TonClientBuilder::from_config_url(
+            Url::parse("https://ton.org/global-config.json").unwrap(),
+            std::time::Duration::from_secs(60),
+        )
+        .build()
+        .await
+        .unwrap();
+        ton.ready().await.unwrap();
+        let pools = DedustHTTPClient::default()
+            .get_available_pools()
+            .await
+            .unwrap();
+
+        for (reserves_empty, amount_in, expected) in [
+            (false, BigUint::ZERO, DedustError::InvalidAmount),
+            (true, BigUint::from(1u32), DedustError::PoolNotReady),
+        ] {
+            let Some(pool) = pools
+                .iter()
+                .find(|pool| pool.reserves.iter().all(|r| r == &BigUint::ZERO) == reserves_empty)
+            else {
+                continue;
+            };
+            let err = TonContract::new(ton.clone(), pool.address)
+                .estimate_swap_out(pool.assets()[0], amount_in)
+                .await
+                .err()
+                .expect("quoted");
+            assert_eq!(
+                err.downcast_ref::<DedustError>(),
+                Some(&expected),
+                "{err:#}"
+            );
+        }
+    }
+
+    const POOL_SNAPSHOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/pool_snapshot.json");
+
+    /// State of a pool along with results of its get-methods run by
+    /// liteservers against the same state
+    #[serde_as]
+    #[derive(Serialize, Deserialize)]
+    struct PoolSnapshot {
+        state: AccountState,
+        #[serde_as(as = "[DisplayFromStr; 2]")]
+        reserves: [BigUint; 2],
+        quotes: Vec<SnapshotQuote>,
+    }
+
+    #[serde_as]
+    #[derive(Serialize, Deserialize)]
+    struct SnapshotQuote {
+        reversed: bool,
+        #[serde_as(as = "DisplayFromStr")]
+        amount_in: BigUint,
+        #[serde_as(as = "DisplayFromStr")]
+        amount_out: BigUint,
+        #[serde_as(as = "DisplayFromStr")]
+        trade_fee: BigUint,
+    }
+
+    /// Writes [`POOL_SNAPSHOT`] of the first volatile TON pool listed by
+    /// DeDust API. The snapshot is rejected if the pool has transacted
+    /// while its get-methods were run.
+    #[tokio::test]
+    #[ignore = "queries DeDust API and mainnet liteservers, overwrites the fixture"]
+    async fn capture_pool_snapshot() {
+        use aceton_core::ton_utils::{contract::TonContract, emulator::AccountStateI};
+        use tonlibjson_client::ton::TonClientBuilder;
+        use url::Url;
+
+        use crate::api::DedustHTTPClient;
+
+        let mut ton = TonClientBuilder::from_config_url(
+            Url::parse("https://ton.org/global-config.json").unwrap(),
+            std::time::Duration::from_secs(60),
+        )
+        .build()
+        .await
+        .unwrap();
+        ton.ready().await.unwrap();
+        let pool = DedustHTTPClient::default()
+            .get_available_pools()
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|pool| {
+                matches!(pool.r#type, DedustPoolType::Volatile)
+                    && pool.assets().contains(&Asset::Native)
+            })
+            .expect("no volatile TON pools");
+
+        let contract = TonContract::new(ton.clone(), pool.address);
+        let state = ton.account_state(pool.address).await.unwrap();
+        let reserves = contract.get_reserves().await.unwrap();
+        let mut quotes = Vec::new();
+        for reversed in [false, true] {
+            let asset_in = pool.assets()[reversed as usize];
+            for share in [1000u32, 10] {
+                let amount_in = &reserves[reversed as usize] / share;
+                let onchain = contract
+                    .estimate_swap_out(asset_in, amount_in.clone())
+                    .await
+                    .unwrap();
+                quotes.push(SnapshotQuote {
+                    reversed,
+                    amount_in,
+                    amount_out: onchain.amount_out,
+                    trade_fee: onchain.trade_fee,
+                });
+            }
+        }
+        assert_eq!(
+            ton.account_state(pool.address)
+                .await
+                .unwrap()
+                .last_transaction_lt,
+            state.last_transaction_lt,
+            "pool has transacted while capturing, try again",
+        );
+
+        let snapshot = PoolSnapshot {
+            state,
+            reserves,
+            quotes,
+        };
+        std::fs::write(
+            POOL_SNAPSHOT,
+            serde_json::to_string_pretty(&snapshot).unwrap() + "\n",
+        )
+        .unwrap();
+    }
+
+    /// Runs get-methods against [`POOL_SNAPSHOT`] in local TVM emulator,
+    /// which should agree with liteservers
+    #[cfg(feature = "emulator")]
+    #[tokio::test]
+    async fn emulated_matches_liteserver() {
+        use aceton_core::ton_utils::emulator::{EmulatedContract, NativeTvm};
+
+        let Some(snapshot) = captured::<PoolSnapshot>(POOL_SNAPSHOT) else {
+            return;
+        };
+        let address = snapshot.state.address;
+        let contract = EmulatedContract::new(snapshot.state, NativeTvm::default(), address);
+
+        assert_eq!(contract.get_reserves().await.unwrap(), snapshot.reserves);
+        let assets = contract.get_assets().await.unwrap();
+        for quote in snapshot.quotes {
+            let emulated = contract
+                .estimate_swap_out(assets[quote.reversed as usize], quote.amount_in)
+                .await
+                .unwrap();
+            assert_eq!(emulated.amount_out, quote.amount_out);
+            assert_eq!(emulated.trade_fee, quote.trade_fee);
+        }
+    }
+
+    #[test]
+    fn stable_ratio() {
+        let pool = stable_pool(
+            [1_000_000_000u64, 1_000_000_000u64].map(Into::into),
+            Ratio::from_integer(BigUint::ZERO),
+        );
+        assert_eq!(pool.ratio(Asset::Native), Ratio::from_integer(1u32.into()));
+
+        let pool = stable_pool(
+            [1_250_000_000u64, 1_190_000_000u64].map(Into::into),
+            Ratio::from_integer(BigUint::ZERO),
+        );
+        let rate = pool.rate(Asset::Native);
+        // marginal rate of a stable pool stays much closer to 1
+        // than the one of a constant-product pool
+        assert!(rate < 1.0 && rate > 0.99);
+        let amount_out = pool.estimate_swap_out(Asset::Native, &1_000_000u32.into());
+        assert!((amount_out.to_f64().unwrap() / 1_000_000.0 - rate).abs() < 1e-5);
+    }
+
     #[test]
     fn swap_step_len() {
         assert_eq!(
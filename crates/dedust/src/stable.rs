@@ -0,0 +1,89 @@
+//! StableSwap curve used by [stable](crate::DedustPoolType::Stable) pools:
+//! `x^3 * y + y^3 * x = k`
+use num::{pow::Pow, rational::Ratio, BigUint};
+
+const MAX_ITERATIONS: usize = 255;
+
+/// Returns invariant `k` for given reserves
+pub(crate) fn invariant(x: &BigUint, y: &BigUint) -> BigUint {
+    x * y * (x * x + y * y)
+}
+
+/// Returns amount of `y` to give for `amount_in` of `x`, so that
+/// the invariant holds
+pub(crate) fn estimate_swap_out(
+    reserve_in: &BigUint,
+    reserve_out: &BigUint,
+    amount_in: &BigUint,
+) -> BigUint {
+    let k = invariant(reserve_in, reserve_out);
+    let new_reserve_out = solve_y(&(reserve_in + amount_in), &k, reserve_out.clone());
+    if &new_reserve_out >= reserve_out {
+        return BigUint::ZERO;
+    }
+    reserve_out - new_reserve_out
+}
+
+/// Returns marginal rate `-dy/dx` at given reserves:
+/// `(3 * x^2 * y + y^3) / (x^3 + 3 * x * y^2)`
+pub(crate) fn marginal_rate(reserve_in: &BigUint, reserve_out: &BigUint) -> Ratio<BigUint> {
+    let (x, y) = (reserve_in, reserve_out);
+    let three = BigUint::from(3u32);
+    Ratio::new(
+        &three * x * x * y + Pow::pow(y, 3u32),
+        Pow::pow(x, 3u32) + three * x * y * y,
+    )
+}
+
+/// Finds the smallest `y` such that `x^3 * y + y^3 * x >= k` with Newton's
+/// method starting from `y` which is not less than the solution.
+///
+/// Since the left side is convex and increasing on `y`, rounding steps
+/// down keeps iterations above the solution, so that the resulting
+/// amount out never exceeds the exact one.
+fn solve_y(x: &BigUint, k: &BigUint, mut y: BigUint) -> BigUint {
+    let x3 = Pow::pow(x, 3u32);
+    for _ in 0..MAX_ITERATIONS {
+        let f = &x3 * &y + x * Pow::pow(&y, 3u32);
+        if &f <= k {
+            break;
+        }
+        let df = &x3 + BigUint::from(3u32) * x * &y * &y;
+        let dy = (f - k) / df;
+        if dy == BigUint::ZERO {
+            break;
+        }
+        y -= dy;
+    }
+    y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Amount out is the largest one keeping the invariant, i.e. reserve
+    /// out left in the pool is the smallest one
+    #[test]
+    fn estimate_swap_out_keeps_invariant() {
+        for (reserve_in, reserve_out) in [
+            (1_250_000_000_000_000u64, 1_190_000_000_000_000u64),
+            (1_000_000_000, 1_000_000_000),
+            (7_000_000, 3_000_000_000),
+        ] {
+            let (reserve_in, reserve_out) = (BigUint::from(reserve_in), BigUint::from(reserve_out));
+            let k = invariant(&reserve_in, &reserve_out);
+            for share in [1_000_000u32, 1000, 10, 1] {
+                let amount_in = &reserve_in / share;
+                let amount_out = estimate_swap_out(&reserve_in, &reserve_out, &amount_in);
+                let x = &reserve_in + &amount_in;
+                let y = &reserve_out - &amount_out;
+                assert!(invariant(&x, &y) >= k, "{reserve_in}/{reserve_out}/{share}");
+                assert!(
+                    invariant(&x, &(y - 1u32)) < k,
+                    "{reserve_in}/{reserve_out}/{share}",
+                );
+            }
+        }
+    }
+}
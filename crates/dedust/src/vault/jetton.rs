@@ -24,7 +24,7 @@ where
         builder
             .pack(JETTON_VAULT_SWAP_TAG)?
             .store(&self.step)?
-            .store(&self.params)?;
+            .store_as::<_, Ref>(&self.params)?;
         Ok(())
     }
 }
@@ -38,7 +38,7 @@ where
         parser.unpack::<ConstU32<JETTON_VAULT_SWAP_TAG>>()?;
         Ok(Self {
             step: parser.parse()?,
-            params: parser.parse()?,
+            params: parser.parse_as::<_, Ref>()?,
         })
     }
 }
@@ -1,8 +1,29 @@
+use core::ops::AddAssign;
+
 use petgraph::{
     algo::FloatMeasure,
     visit::{EdgeRef, IntoEdges},
 };
 
+/// Counters of the search, which are accumulated across restarts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// Number of paths which were extended by one more edge
+    pub visited: u64,
+    /// Number of edges skipped because of infinite or NaN cost
+    pub skipped: u64,
+    /// Number of branches cut off by [`NegativeCycles::with_pruning`]
+    pub pruned: u64,
+}
+
+impl AddAssign for SearchStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.visited += rhs.visited;
+        self.skipped += rhs.skipped;
+        self.pruned += rhs.pruned;
+    }
+}
+
 pub struct NegativeCycles<G, F, K>
 where
     G: IntoEdges,
@@ -13,6 +34,11 @@ where
     path: Vec<G::EdgeRef>,
     edge_cost: F,
     max_length: Option<usize>,
+    /// Only cycles starting with this edge are searched
+    first_edge: Option<G::EdgeId>,
+    /// remaining number of edges -> lower bound of their total cost
+    remaining_bounds: Option<Vec<K>>,
+    stats: SearchStats,
 }
 
 impl<G, F, K> NegativeCycles<G, F, K>
@@ -29,11 +55,54 @@ where
             path: Vec::with_capacity(max_length.unwrap_or_default()),
             edge_cost,
             max_length,
+            first_edge: None,
+            remaining_bounds: None,
+            stats: SearchStats::default(),
         };
         s.restart(start);
         s
     }
 
+    /// Cuts off paths which can not become negative cycles, since even
+    /// the cheapest edges within the remaining length would not make
+    /// their cost negative. The last edge is bounded by the cheapest one
+    /// into the start node.
+    ///
+    /// Bounds are computed from current edge costs and the start node, so
+    /// that they should be recomputed after costs change or on restart
+    /// from another node. Has no effect without `max_length`.
+    pub fn with_pruning(mut self) -> Self {
+        let Some(max_length) = self.max_length else {
+            return self;
+        };
+        let start = self.stack[0].0;
+        self.remaining_bounds = Some(pruning_bounds(
+            self.g,
+            start,
+            &mut self.edge_cost,
+            max_length,
+        ));
+        self
+    }
+
+    /// Pruning with bounds precomputed by [`pruning_bounds`]
+    pub(crate) fn with_pruning_bounds(mut self, bounds: Option<Vec<K>>) -> Self {
+        self.remaining_bounds = bounds;
+        self
+    }
+
+    /// Searches only cycles starting with given edge of the start node,
+    /// so that the search can be split by first edges between workers.
+    /// Restarting from another node makes it find nothing.
+    pub fn with_first_edge(mut self, edge: G::EdgeId) -> Self {
+        self.first_edge = Some(edge);
+        self
+    }
+
+    pub fn stats(&self) -> SearchStats {
+        self.stats
+    }
+
     pub fn restart(&mut self, start: G::NodeId) {
         self.stack.clear();
         self.stack.push((start, K::zero(), self.g.edges(start)));
@@ -41,6 +110,44 @@ where
     }
 }
 
+/// Lower bounds of the total cost of the remaining number of edges of a
+/// cycle through `start`, indexed by that number minus one
+pub(crate) fn pruning_bounds<G, K>(
+    g: G,
+    start: G::NodeId,
+    mut edge_cost: impl FnMut(G::EdgeRef) -> K,
+    max_length: usize,
+) -> Vec<K>
+where
+    G: IntoEdges,
+    K: FloatMeasure,
+{
+    let mut min_cost = K::infinite();
+    let mut min_closing_cost = K::infinite();
+    for edge in g.edge_references() {
+        let cost = edge_cost(edge);
+        if cost < min_cost {
+            min_cost = cost;
+        }
+        if edge.target() == start && cost < min_closing_cost {
+            min_closing_cost = cost;
+        }
+    }
+    // extra edges can only decrease the cost if they are negative
+    let min_cost = if min_cost < K::zero() {
+        min_cost
+    } else {
+        K::zero()
+    };
+    (0..max_length)
+        .scan(min_closing_cost, |bound, _| {
+            let current = *bound;
+            *bound = *bound + min_cost;
+            Some(current)
+        })
+        .collect()
+}
+
 impl<G, F, K> Iterator for NegativeCycles<G, F, K>
 where
     G: IntoEdges,
@@ -58,11 +165,21 @@ where
                 continue;
             };
 
+            if self.path.is_empty()
+                && self
+                    .first_edge
+                    .is_some_and(|first_edge| first_edge != next_edge.id())
+            {
+                continue;
+            }
+
             let next_cost = *cost + (self.edge_cost)(next_edge);
-            // TODO
-            // if next_cost == K::infinite() {
-            //     continue;
-            // }
+            // NaN is never less than infinity
+            let finite = next_cost < K::infinite();
+            if !finite {
+                self.stats.skipped += 1;
+                continue;
+            }
 
             for (i, (node_id, last_cost, _)) in self.stack.iter().enumerate() {
                 if next_edge.target() == *node_id && next_cost >= *last_cost {
@@ -85,13 +202,22 @@ where
                 return Some(cycle);
             }
 
-            if self
-                .max_length
-                .is_some_and(|max_length| self.path.len() == max_length - 1)
-            {
-                continue;
+            if let Some(max_length) = self.max_length {
+                if self.path.len() == max_length - 1 {
+                    continue;
+                }
+                if let Some(bounds) = &self.remaining_bounds {
+                    // at least one more edge to return to the start node
+                    let remaining = max_length - self.path.len() - 1;
+                    let can_be_negative = next_cost + bounds[remaining - 1] < K::zero();
+                    if !can_be_negative {
+                        self.stats.pruned += 1;
+                        continue;
+                    }
+                }
             }
 
+            self.stats.visited += 1;
             self.path.push(next_edge);
             self.stack.push((
                 next_edge.target(),
@@ -161,4 +287,45 @@ mod tests {
             .collect()
         );
     }
+
+    #[test]
+    fn pruning_keeps_negative_cycles() {
+        let mut g = Graph::new();
+
+        let a = g.add_node("a");
+        let b = g.add_node("b");
+        let c = g.add_node("c");
+        let d = g.add_node("d");
+
+        let _ab = g.add_edge(a, b, -1.0);
+        let _bc = g.add_edge(b, c, 3.0);
+        let ca = g.add_edge(c, a, -1.0);
+        let _bd = g.add_edge(b, d, 5.0);
+        let _dc = g.add_edge(d, c, -1.0);
+        let _ba = g.add_edge(b, a, f64::INFINITY);
+        let ac = g.add_edge(a, c, -0.5);
+        let _cb = g.add_edge(c, b, f64::NAN);
+
+        let search = |pruning: bool| {
+            let mut cycles = NegativeCycles::new(&g, a, |e| *e.weight(), 4);
+            if pruning {
+                cycles = cycles.with_pruning();
+            }
+            let found = cycles
+                .by_ref()
+                .map(|path| path.into_iter().map(|e| e.id()).collect::<Vec<_>>())
+                .collect::<HashSet<_>>();
+            (found, cycles.stats())
+        };
+
+        let (all, stats) = search(false);
+        assert_eq!(all, [vec![ac, ca]].into());
+        assert_eq!(stats.pruned, 0);
+        assert!(stats.skipped > 0);
+
+        let (pruned, pruned_stats) = search(true);
+        assert_eq!(pruned, all);
+        assert!(pruned_stats.pruned > 0);
+        assert!(pruned_stats.visited < stats.visited);
+    }
 }
@@ -0,0 +1,302 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    hash::Hash,
+};
+
+use petgraph::{
+    algo::FloatMeasure,
+    visit::{EdgeRef, GraphBase, IntoEdgeReferences, IntoEdges, NodeIndexable},
+};
+
+use crate::NegativeCycles;
+
+/// Algorithm to find negative cycles through the start node
+pub trait NegativeCycleFinder {
+    /// Returns negative cycles, each as a sequence of edges starting from
+    /// and returning back to `start`
+    fn negative_cycles<G, F, K>(
+        &self,
+        g: G,
+        start: G::NodeId,
+        edge_cost: F,
+    ) -> Vec<Vec<G::EdgeRef>>
+    where
+        G: IntoEdges + NodeIndexable,
+        G::EdgeId: Eq + Hash,
+        F: FnMut(G::EdgeRef) -> K,
+        K: FloatMeasure;
+}
+
+/// Enumerates all cycles with [`NegativeCycles`], which takes
+/// exponential time of `max_length`
+#[derive(Debug, Clone, Copy)]
+pub struct Dfs {
+    pub max_length: Option<usize>,
+    /// See [`NegativeCycles::with_pruning`]
+    pub pruning: bool,
+}
+
+impl NegativeCycleFinder for Dfs {
+    fn negative_cycles<G, F, K>(&self, g: G, start: G::NodeId, edge_cost: F) -> Vec<Vec<G::EdgeRef>>
+    where
+        G: IntoEdges + NodeIndexable,
+        G::EdgeId: Eq + Hash,
+        F: FnMut(G::EdgeRef) -> K,
+        K: FloatMeasure,
+    {
+        let cycles = NegativeCycles::new(g, start, edge_cost, self.max_length);
+        if self.pruning {
+            cycles.with_pruning().collect()
+        } else {
+            cycles.collect()
+        }
+    }
+}
+
+/// Detects a single negative cycle reachable from the start node, edges
+/// with `None` cost are considered absent
+pub(crate) type Detect<G, K> = fn(
+    G,
+    <G as GraphBase>::NodeId,
+    &mut dyn FnMut(<G as IntoEdgeReferences>::EdgeRef) -> Option<K>,
+) -> Option<Vec<<G as IntoEdgeReferences>::EdgeRef>>;
+
+/// Runs the detector repeatedly, excluding edges of each found cycle
+/// from following runs, so that at most `max_cycles` edge-disjoint
+/// cycles are found.
+///
+/// Detected cycles which do not go through the start node are joined
+/// with it by paths with the fewest edges and kept only if still
+/// negative.
+pub(crate) fn find_edge_disjoint<G, F, K>(
+    g: G,
+    start: G::NodeId,
+    mut edge_cost: F,
+    max_length: Option<usize>,
+    max_cycles: usize,
+    detect: Detect<G, K>,
+) -> Vec<Vec<G::EdgeRef>>
+where
+    G: IntoEdges + NodeIndexable,
+    G::EdgeId: Eq + Hash,
+    F: FnMut(G::EdgeRef) -> K,
+    K: FloatMeasure,
+{
+    let mut excluded = HashSet::new();
+    let mut cycles = Vec::new();
+    for _ in 0..max_cycles {
+        let mut usable_cost = |edge: G::EdgeRef| {
+            if excluded.contains(&edge.id()) {
+                return None;
+            }
+            let cost = edge_cost(edge);
+            // skip infinite and NaN costs
+            (cost < K::infinite()).then_some(cost)
+        };
+        let Some(cycle) = detect(g, start, &mut usable_cost) else {
+            break;
+        };
+        let closed = through_start(g, start, &cycle, &mut usable_cost);
+        excluded.extend(cycle.iter().map(|edge| edge.id()));
+        if let Some(closed) =
+            closed.filter(|closed| max_length.map_or(true, |max_length| closed.len() <= max_length))
+        {
+            cycles.push(closed);
+        }
+    }
+    cycles
+}
+
+/// Rotates the cycle to start from `start` or joins it with `start` if
+/// it does not go through it
+fn through_start<G, K>(
+    g: G,
+    start: G::NodeId,
+    cycle: &[G::EdgeRef],
+    edge_cost: &mut dyn FnMut(G::EdgeRef) -> Option<K>,
+) -> Option<Vec<G::EdgeRef>>
+where
+    G: IntoEdges + NodeIndexable,
+    G::EdgeId: Eq + Hash,
+    K: FloatMeasure,
+{
+    if let Some(i) = cycle.iter().position(|edge| edge.source() == start) {
+        let mut cycle = cycle.to_vec();
+        cycle.rotate_left(i);
+        return Some(cycle);
+    }
+
+    let mut used: HashSet<_> = cycle.iter().map(|edge| edge.id()).collect();
+    let mut on_cycle = vec![false; g.node_bound()];
+    for edge in cycle {
+        on_cycle[g.to_index(edge.source())] = true;
+    }
+    let to_cycle = fewest_edges_path(
+        g,
+        start,
+        |node| on_cycle[g.to_index(node)],
+        &used,
+        edge_cost,
+    )?;
+    used.extend(to_cycle.iter().map(|edge| edge.id()));
+    let entry = to_cycle.last()?.target();
+    let from_cycle = fewest_edges_path(g, entry, |node| node == start, &used, edge_cost)?;
+    let i = cycle.iter().position(|edge| edge.source() == entry)?;
+
+    let walk: Vec<_> = to_cycle
+        .into_iter()
+        .chain(cycle[i..].iter().copied())
+        .chain(cycle[..i].iter().copied())
+        .chain(from_cycle)
+        .collect();
+    let mut cost = K::zero();
+    for edge in &walk {
+        cost = cost + edge_cost(*edge)?;
+    }
+    (cost < K::zero()).then_some(walk)
+}
+
+/// BFS from `from` to the first node matching `to` through edges with
+/// finite cost which are not `used`
+fn fewest_edges_path<G, K>(
+    g: G,
+    from: G::NodeId,
+    to: impl Fn(G::NodeId) -> bool,
+    used: &HashSet<G::EdgeId>,
+    edge_cost: &mut dyn FnMut(G::EdgeRef) -> Option<K>,
+) -> Option<Vec<G::EdgeRef>>
+where
+    G: IntoEdges + NodeIndexable,
+    G::EdgeId: Eq + Hash,
+{
+    let mut pred: Vec<Option<G::EdgeRef>> = vec![None; g.node_bound()];
+    let mut visited = vec![false; g.node_bound()];
+    visited[g.to_index(from)] = true;
+    let mut queue = VecDeque::from([from]);
+    while let Some(node) = queue.pop_front() {
+        for edge in g.edges(node) {
+            let next = edge.target();
+            if visited[g.to_index(next)] || used.contains(&edge.id()) || edge_cost(edge).is_none() {
+                continue;
+            }
+            visited[g.to_index(next)] = true;
+            pred[g.to_index(next)] = Some(edge);
+            if !to(next) {
+                queue.push_back(next);
+                continue;
+            }
+
+            let mut path = Vec::new();
+            let mut node = next;
+            while node != from {
+                let edge = pred[g.to_index(node)]?;
+                path.push(edge);
+                node = edge.source();
+            }
+            path.reverse();
+            return Some(path);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use petgraph::{
+        graph::{EdgeIndex, EdgeReference, NodeIndex},
+        Graph,
+    };
+
+    use crate::{Spfa, Tarjan};
+
+    use super::*;
+
+    /// Checks that each cycle is negative, goes from `start` back to it
+    /// and cycles do not share edges
+    fn assert_valid_cycles<N>(
+        g: &Graph<N, f64>,
+        start: NodeIndex,
+        cycles: &[Vec<EdgeReference<f64>>],
+    ) {
+        let mut seen = HashSet::<EdgeIndex>::new();
+        for cycle in cycles {
+            assert_eq!(cycle.first().unwrap().source(), start);
+            assert_eq!(cycle.last().unwrap().target(), start);
+            for pair in cycle.windows(2) {
+                assert_eq!(pair[0].target(), pair[1].source());
+            }
+            assert!(cycle.iter().map(|edge| g[edge.id()]).sum::<f64>() < 0.0);
+            assert!(cycle.iter().all(|edge| seen.insert(edge.id())));
+        }
+    }
+
+    fn graph() -> (Graph<&'static str, f64>, NodeIndex) {
+        let mut g = Graph::new();
+
+        let a = g.add_node("a");
+        let b = g.add_node("b");
+        let c = g.add_node("c");
+        let d = g.add_node("d");
+
+        g.add_edge(a, b, 7.0);
+        g.add_edge(a, d, 12.0);
+        g.add_edge(b, a, -6.0);
+        g.add_edge(b, d, 3.0);
+        g.add_edge(b, c, 5.0);
+        g.add_edge(c, b, -4.0);
+        g.add_edge(c, d, -3.0);
+        g.add_edge(d, a, -11.0);
+        g.add_edge(d, b, -2.0);
+        g.add_edge(d, c, 4.0);
+        g.add_edge(d, a, -12.0);
+        (g, a)
+    }
+
+    fn finds_edge_disjoint_cycles(finder: impl NegativeCycleFinder) {
+        let (g, a) = graph();
+        let cycles = finder.negative_cycles(&g, a, |edge| *edge.weight());
+        assert!(!cycles.is_empty());
+        assert_valid_cycles(&g, a, &cycles);
+    }
+
+    fn joins_cycle_with_start(finder: impl NegativeCycleFinder) {
+        let mut g = Graph::new();
+        let a = g.add_node("a");
+        let b = g.add_node("b");
+        let c = g.add_node("c");
+        let ab = g.add_edge(a, b, 0.5);
+        let bc = g.add_edge(b, c, -1.0);
+        let cb = g.add_edge(c, b, -1.0);
+        let ba = g.add_edge(b, a, 0.5);
+        // unreachable from a
+        let d = g.add_node("d");
+        g.add_edge(d, d, -1.0);
+
+        let cycles: Vec<Vec<_>> = finder
+            .negative_cycles(&g, a, |edge| *edge.weight())
+            .into_iter()
+            .map(|cycle| cycle.into_iter().map(|edge| edge.id()).collect())
+            .collect();
+        assert_eq!(cycles, [[ab, bc, cb, ba]]);
+    }
+
+    #[test]
+    fn spfa() {
+        let finder = Spfa {
+            max_length: None,
+            max_cycles: 8,
+        };
+        finds_edge_disjoint_cycles(finder);
+        joins_cycle_with_start(finder);
+    }
+
+    #[test]
+    fn tarjan() {
+        let finder = Tarjan {
+            max_length: None,
+            max_cycles: 8,
+        };
+        finds_edge_disjoint_cycles(finder);
+        joins_cycle_with_start(finder);
+    }
+}
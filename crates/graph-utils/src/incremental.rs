@@ -0,0 +1,190 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+use petgraph::{
+    algo::FloatMeasure,
+    visit::{EdgeRef, IntoEdges},
+};
+
+/// Index of all simple cycles through the start node up to given length,
+/// so that after some edges change their costs only cycles through them
+/// have to be re-evaluated.
+///
+/// Cycles are enumerated once regardless of edge costs, which makes the
+/// index suitable for graphs with fixed topology, but changing costs.
+/// Edges which should be excluded can be given infinite cost.
+pub struct CycleIndex<E> {
+    cycles: Vec<Vec<E>>,
+    /// edge -> indexes of cycles through it
+    edge2cycles: HashMap<E, Vec<usize>>,
+    /// Indexes of cycles which were negative on the last evaluation
+    negative: HashSet<usize>,
+}
+
+impl<E> CycleIndex<E>
+where
+    E: Copy + Eq + Hash,
+{
+    pub fn new<G>(g: G, start: G::NodeId, max_length: usize) -> Self
+    where
+        G: IntoEdges<EdgeId = E>,
+    {
+        let mut s = Self {
+            cycles: Vec::new(),
+            edge2cycles: HashMap::new(),
+            negative: HashSet::new(),
+        };
+
+        let mut nodes = vec![start];
+        let mut path: Vec<E> = Vec::with_capacity(max_length);
+        let mut stack = vec![g.edges(start)];
+        while let Some(edges) = stack.last_mut() {
+            let Some(next_edge) = edges.next() else {
+                stack.pop();
+                nodes.pop();
+                path.pop();
+                continue;
+            };
+            if next_edge.target() == start {
+                let mut cycle = path.clone();
+                cycle.push(next_edge.id());
+                s.insert(cycle);
+                continue;
+            }
+            if path.len() + 1 == max_length || nodes.contains(&next_edge.target()) {
+                continue;
+            }
+            nodes.push(next_edge.target());
+            path.push(next_edge.id());
+            stack.push(g.edges(next_edge.target()));
+        }
+        s
+    }
+
+    fn insert(&mut self, cycle: Vec<E>) {
+        let index = self.cycles.len();
+        for edge in &cycle {
+            self.edge2cycles.entry(*edge).or_default().push(index);
+        }
+        self.cycles.push(cycle);
+    }
+
+    /// Number of indexed cycles
+    pub fn len(&self) -> usize {
+        self.cycles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cycles.is_empty()
+    }
+
+    /// Re-evaluates all cycles with given edge costs
+    pub fn update_all<K, F>(&mut self, edge_cost: F)
+    where
+        F: FnMut(E) -> K,
+        K: FloatMeasure,
+    {
+        self.negative.clear();
+        self.evaluate(0..self.cycles.len(), edge_cost);
+    }
+
+    /// Re-evaluates only cycles through edges which costs have changed,
+    /// returns the number of re-evaluated cycles
+    pub fn update<K, F>(&mut self, changed: impl IntoIterator<Item = E>, edge_cost: F) -> usize
+    where
+        F: FnMut(E) -> K,
+        K: FloatMeasure,
+    {
+        let affected: HashSet<usize> = changed
+            .into_iter()
+            .filter_map(|edge| self.edge2cycles.get(&edge))
+            .flatten()
+            .copied()
+            .collect();
+        let count = affected.len();
+        self.evaluate(affected, edge_cost);
+        count
+    }
+
+    fn evaluate<K, F>(&mut self, cycles: impl IntoIterator<Item = usize>, mut edge_cost: F)
+    where
+        F: FnMut(E) -> K,
+        K: FloatMeasure,
+    {
+        for index in cycles {
+            let cost = self.cycles[index]
+                .iter()
+                .fold(K::zero(), |cost, edge| cost + edge_cost(*edge));
+            // NaN, e.g. sum of opposite infinities, is not negative
+            if cost < K::zero() {
+                self.negative.insert(index);
+            } else {
+                self.negative.remove(&index);
+            }
+        }
+    }
+
+    /// Cycles which were negative on the last update, each as a sequence
+    /// of edges starting from the start node
+    pub fn negative_cycles(&self) -> impl Iterator<Item = &[E]> {
+        self.negative
+            .iter()
+            .map(|index| self.cycles[*index].as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use petgraph::Graph;
+
+    use super::*;
+
+    #[test]
+    fn reevaluates_only_changed_cycles() {
+        let mut g = Graph::new();
+
+        let a = g.add_node("a");
+        let b = g.add_node("b");
+        let c = g.add_node("c");
+
+        let ab = g.add_edge(a, b, 1.0);
+        let _ba = g.add_edge(b, a, -0.5);
+        let bc = g.add_edge(b, c, 1.0);
+        let ca = g.add_edge(c, a, -1.0);
+        let ac = g.add_edge(a, c, 1.0);
+        let _cb = g.add_edge(c, b, 1.0);
+
+        let mut index = CycleIndex::new(&g, a, 3);
+        // a-b-a, a-b-c-a, a-c-a, a-c-b-a
+        assert_eq!(index.len(), 4);
+        index.update_all(|e| g[e]);
+        assert_eq!(index.negative_cycles().count(), 0);
+
+        g[ca] = -2.5;
+        // a-b-a and a-c-b-a are not affected
+        assert_eq!(index.update([ca], |e| g[e]), 2);
+        let negative: HashSet<_> = index.negative_cycles().map(<[_]>::to_vec).collect();
+        assert_eq!(negative, [vec![ab, bc, ca], vec![ac, ca]].into());
+
+        g[ab] = f64::INFINITY;
+        index.update([ab], |e| g[e]);
+        let negative: Vec<_> = index.negative_cycles().collect();
+        assert_eq!(negative, [[ac, ca].as_slice()]);
+    }
+
+    #[test]
+    fn respects_max_length() {
+        let mut g = Graph::new();
+        let a = g.add_node(());
+        let b = g.add_node(());
+        let c = g.add_node(());
+        g.add_edge(a, b, 0.0);
+        g.add_edge(b, c, 0.0);
+        g.add_edge(c, a, 0.0);
+
+        assert_eq!(CycleIndex::new(&g, a, 2).len(), 0);
+        assert_eq!(CycleIndex::new(&g, a, 3).len(), 1);
+    }
+}
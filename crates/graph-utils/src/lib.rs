@@ -1,3 +1,8 @@
 mod cycles;
+mod finder;
+mod incremental;
+mod parallel;
+mod spfa;
+mod tarjan;
 
-pub use self::cycles::*;
+pub use self::{cycles::*, finder::*, incremental::*, parallel::*, spfa::*, tarjan::*};
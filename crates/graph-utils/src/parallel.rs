@@ -0,0 +1,108 @@
+use petgraph::{
+    algo::FloatMeasure,
+    visit::{EdgeRef, IntoEdges},
+};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{cycles::pruning_bounds, NegativeCycles, SearchStats};
+
+/// Runs [`NegativeCycles`] on rayon thread pool, where the search is
+/// split by outgoing edges of the start node, so that each of them is
+/// explored by its own worker.
+///
+/// Returns cycles found by all workers along with their accumulated
+/// stats.
+pub fn par_negative_cycles<G, F, K>(
+    g: G,
+    start: G::NodeId,
+    edge_cost: F,
+    max_length: Option<usize>,
+    pruning: bool,
+) -> (Vec<Vec<G::EdgeRef>>, SearchStats)
+where
+    G: IntoEdges + Sync,
+    G::NodeId: Sync,
+    G::EdgeId: Send,
+    G::EdgeRef: Send,
+    F: Fn(G::EdgeRef) -> K + Sync,
+    K: FloatMeasure + Sync,
+{
+    // bounds depend only on the start node and costs, so that they are
+    // shared by all workers
+    let bounds = max_length
+        .filter(|_| pruning)
+        .map(|max_length| pruning_bounds(g, start, &edge_cost, max_length));
+    let first_edges: Vec<_> = g.edges(start).map(|edge| edge.id()).collect();
+    first_edges
+        .into_par_iter()
+        .map(|first_edge| {
+            let mut cycles = NegativeCycles::new(g, start, &edge_cost, max_length)
+                .with_first_edge(first_edge)
+                .with_pruning_bounds(bounds.clone());
+            let found: Vec<_> = cycles.by_ref().collect();
+            (found, cycles.stats())
+        })
+        .reduce(
+            Default::default,
+            |(mut cycles, mut stats), (found, found_stats)| {
+                cycles.extend(found);
+                stats += found_stats;
+                (cycles, stats)
+            },
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use petgraph::{
+        graph::{EdgeIndex, EdgeReference},
+        Graph,
+    };
+
+    use super::*;
+
+    #[test]
+    fn same_as_sequential() {
+        let mut g = Graph::new();
+
+        let a = g.add_node("a");
+        let b = g.add_node("b");
+        let c = g.add_node("c");
+        let d = g.add_node("d");
+
+        g.add_edge(a, b, 7.0);
+        g.add_edge(a, d, 12.0);
+        g.add_edge(b, a, -6.0);
+        g.add_edge(b, d, 3.0);
+        g.add_edge(b, c, 5.0);
+        g.add_edge(c, b, -4.0);
+        g.add_edge(c, d, -3.0);
+        g.add_edge(d, a, -11.0);
+        g.add_edge(d, b, -2.0);
+        g.add_edge(d, c, 4.0);
+        g.add_edge(d, a, -12.0);
+        g.add_edge(a, a, f64::INFINITY);
+
+        let ids = |cycles: Vec<Vec<EdgeReference<f64>>>| -> HashSet<Vec<EdgeIndex>> {
+            cycles
+                .into_iter()
+                .map(|cycle| cycle.into_iter().map(|e| e.id()).collect())
+                .collect()
+        };
+        for (max_length, pruning) in [(None, false), (Some(3), false), (Some(3), true)] {
+            let mut sequential = NegativeCycles::new(&g, a, |e| *e.weight(), max_length);
+            if pruning {
+                sequential = sequential.with_pruning();
+            }
+            let expected: Vec<_> = sequential.by_ref().collect();
+            assert!(!expected.is_empty());
+
+            let (found, stats) = par_negative_cycles(&g, a, |e| *e.weight(), max_length, pruning);
+            assert_eq!(found.len(), expected.len());
+            assert_eq!(ids(found), ids(expected));
+            assert_eq!(stats, sequential.stats());
+        }
+    }
+}
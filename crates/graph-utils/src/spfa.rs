@@ -0,0 +1,123 @@
+use std::{collections::VecDeque, hash::Hash};
+
+use petgraph::{
+    algo::FloatMeasure,
+    visit::{EdgeRef, IntoEdges, NodeIndexable},
+};
+
+use crate::{finder::find_edge_disjoint, NegativeCycleFinder};
+
+/// Shortest Path Faster Algorithm, i.e. queue-based Bellman-Ford, which
+/// finds a negative cycle in the predecessor graph in `O(V * E)`
+#[derive(Debug, Clone, Copy)]
+pub struct Spfa {
+    /// Longer cycles are dropped after being found
+    pub max_length: Option<usize>,
+    pub max_cycles: usize,
+}
+
+impl NegativeCycleFinder for Spfa {
+    fn negative_cycles<G, F, K>(&self, g: G, start: G::NodeId, edge_cost: F) -> Vec<Vec<G::EdgeRef>>
+    where
+        G: IntoEdges + NodeIndexable,
+        G::EdgeId: Eq + Hash,
+        F: FnMut(G::EdgeRef) -> K,
+        K: FloatMeasure,
+    {
+        find_edge_disjoint(
+            g,
+            start,
+            edge_cost,
+            self.max_length,
+            self.max_cycles,
+            detect,
+        )
+    }
+}
+
+fn detect<G, K>(
+    g: G,
+    start: G::NodeId,
+    edge_cost: &mut dyn FnMut(G::EdgeRef) -> Option<K>,
+) -> Option<Vec<G::EdgeRef>>
+where
+    G: IntoEdges + NodeIndexable,
+    K: FloatMeasure,
+{
+    let n = g.node_bound();
+    let mut dist = vec![K::infinite(); n];
+    let mut pred: Vec<Option<G::EdgeRef>> = vec![None; n];
+    let mut in_queue = vec![false; n];
+
+    dist[g.to_index(start)] = K::zero();
+    in_queue[g.to_index(start)] = true;
+    let mut queue = VecDeque::from([start]);
+    let mut relaxations = 0usize;
+    while let Some(node) = queue.pop_front() {
+        let i = g.to_index(node);
+        in_queue[i] = false;
+        for edge in g.edges(node) {
+            let Some(cost) = edge_cost(edge) else {
+                continue;
+            };
+            let next = g.to_index(edge.target());
+            let next_dist = dist[i] + cost;
+            if next_dist < dist[next] {
+                dist[next] = next_dist;
+                pred[next] = Some(edge);
+            } else {
+                continue;
+            }
+
+            // predecessor graph has a cycle only if it is negative,
+            // checking it every n relaxations keeps amortized cost linear
+            relaxations += 1;
+            if relaxations % n == 0 {
+                if let Some(cycle) = predecessor_cycle(g, &pred) {
+                    return Some(cycle);
+                }
+            }
+            if !in_queue[next] {
+                in_queue[next] = true;
+                queue.push_back(edge.target());
+            }
+        }
+    }
+    None
+}
+
+fn predecessor_cycle<G>(g: G, pred: &[Option<G::EdgeRef>]) -> Option<Vec<G::EdgeRef>>
+where
+    G: IntoEdges + NodeIndexable,
+{
+    // index of the walk which visited the node first
+    let mut visited_by = vec![usize::MAX; pred.len()];
+    for walk in 0..pred.len() {
+        let mut i = walk;
+        while visited_by[i] == usize::MAX {
+            visited_by[i] = walk;
+            let Some(edge) = pred[i] else {
+                break;
+            };
+            i = g.to_index(edge.source());
+        }
+        if visited_by[i] != walk || pred[i].is_none() {
+            // reached either the root or a node seen by previous walks
+            continue;
+        }
+
+        let mut cycle = Vec::new();
+        let mut j = i;
+        loop {
+            let edge = pred[j]?;
+            cycle.push(edge);
+            j = g.to_index(edge.source());
+            if j == i {
+                break;
+            }
+        }
+        cycle.reverse();
+        return Some(cycle);
+    }
+    None
+}
@@ -0,0 +1,127 @@
+use std::{collections::VecDeque, hash::Hash};
+
+use petgraph::{
+    algo::FloatMeasure,
+    visit::{EdgeRef, IntoEdges, NodeIndexable},
+};
+
+use crate::{finder::find_edge_disjoint, NegativeCycleFinder};
+
+/// Bellman-Ford with Tarjan's subtree disassembly, as described by
+/// Goldberg and Cherkassky.
+///
+/// When distance to a node improves, its whole subtree in the shortest
+/// path tree is removed, since distances to its nodes are going to
+/// improve as well. If the subtree contains the node which the distance
+/// improved through, there is a negative cycle, so that it is detected
+/// right away instead of after `V` passes.
+#[derive(Debug, Clone, Copy)]
+pub struct Tarjan {
+    /// Longer cycles are dropped after being found
+    pub max_length: Option<usize>,
+    pub max_cycles: usize,
+}
+
+impl NegativeCycleFinder for Tarjan {
+    fn negative_cycles<G, F, K>(&self, g: G, start: G::NodeId, edge_cost: F) -> Vec<Vec<G::EdgeRef>>
+    where
+        G: IntoEdges + NodeIndexable,
+        G::EdgeId: Eq + Hash,
+        F: FnMut(G::EdgeRef) -> K,
+        K: FloatMeasure,
+    {
+        find_edge_disjoint(
+            g,
+            start,
+            edge_cost,
+            self.max_length,
+            self.max_cycles,
+            detect,
+        )
+    }
+}
+
+fn detect<G, K>(
+    g: G,
+    start: G::NodeId,
+    edge_cost: &mut dyn FnMut(G::EdgeRef) -> Option<K>,
+) -> Option<Vec<G::EdgeRef>>
+where
+    G: IntoEdges + NodeIndexable,
+    K: FloatMeasure,
+{
+    let n = g.node_bound();
+    let mut dist = vec![K::infinite(); n];
+    let mut pred: Vec<Option<G::EdgeRef>> = vec![None; n];
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut in_tree = vec![false; n];
+    let mut in_queue = vec![false; n];
+
+    dist[g.to_index(start)] = K::zero();
+    in_tree[g.to_index(start)] = true;
+    in_queue[g.to_index(start)] = true;
+    let mut queue = VecDeque::from([start]);
+    let mut subtree = Vec::new();
+    while let Some(node) = queue.pop_front() {
+        let i = g.to_index(node);
+        in_queue[i] = false;
+        if !in_tree[i] {
+            // removed along with the subtree, it will be scanned again
+            // once the distance to it improves
+            continue;
+        }
+        for edge in g.edges(node) {
+            let Some(cost) = edge_cost(edge) else {
+                continue;
+            };
+            let next = g.to_index(edge.target());
+            let next_dist = dist[i] + cost;
+            if next_dist < dist[next] {
+                dist[next] = next_dist;
+            } else {
+                continue;
+            }
+
+            subtree.clear();
+            subtree.push(next);
+            while let Some(j) = subtree.pop() {
+                if j == i {
+                    return Some(tree_cycle(g, &pred, edge));
+                }
+                subtree.append(&mut children[j]);
+                if j != next {
+                    in_tree[j] = false;
+                }
+            }
+
+            if let Some(prev) = pred[next] {
+                children[g.to_index(prev.source())].retain(|child| *child != next);
+            }
+            pred[next] = Some(edge);
+            children[i].push(next);
+            in_tree[next] = true;
+            if !in_queue[next] {
+                in_queue[next] = true;
+                queue.push_back(edge.target());
+            }
+        }
+    }
+    None
+}
+
+/// Cycle formed by the edge closing the path in the shortest path tree
+/// from its target to its source
+fn tree_cycle<G>(g: G, pred: &[Option<G::EdgeRef>], closing: G::EdgeRef) -> Vec<G::EdgeRef>
+where
+    G: IntoEdges + NodeIndexable,
+{
+    let mut cycle = vec![closing];
+    let mut node = closing.source();
+    while node != closing.target() {
+        let edge = pred[g.to_index(node)].expect("node is in the subtree");
+        cycle.push(edge);
+        node = edge.source();
+    }
+    cycle.reverse();
+    cycle
+}
@@ -0,0 +1,77 @@
+use aceton_core::Asset;
+use num::BigUint;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr};
+use tlb_ton::MsgAddress;
+
+use crate::StonfiPool;
+
+const BASE_URL: &str = "https://api.ston.fi/v1";
+
+#[derive(Default)]
+pub struct StonfiHTTPClient {
+    client: Client,
+}
+
+impl StonfiHTTPClient {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    pub async fn get_pools(&self) -> anyhow::Result<Vec<Pool>> {
+        Ok(self
+            .client
+            .get(format!("{BASE_URL}/pools"))
+            .send()
+            .await?
+            .json::<PoolList>()
+            .await?
+            .pool_list)
+    }
+}
+
+#[derive(Deserialize)]
+struct PoolList {
+    pool_list: Vec<Pool>,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct Pool {
+    pub address: MsgAddress,
+    pub router_address: MsgAddress,
+    /// Jetton master of the first asset
+    pub token0_address: MsgAddress,
+    /// Jetton master of the second asset
+    pub token1_address: MsgAddress,
+    #[serde_as(as = "DisplayFromStr")]
+    pub reserve0: BigUint,
+    #[serde_as(as = "DisplayFromStr")]
+    pub reserve1: BigUint,
+    #[serde_as(as = "DisplayFromStr")]
+    pub lp_fee: u16,
+    #[serde_as(as = "DisplayFromStr")]
+    pub protocol_fee: u16,
+    #[serde(default)]
+    pub deprecated: bool,
+}
+
+impl Pool {
+    /// Converts to [`StonfiPool`], treating `pton` jetton as native TON
+    pub fn into_pool(self, pton: MsgAddress) -> StonfiPool {
+        let asset = |master| {
+            if master == pton {
+                return Asset::Native;
+            }
+            Asset::Jetton(master)
+        };
+        StonfiPool {
+            address: self.address,
+            assets: [asset(self.token0_address), asset(self.token1_address)],
+            reserves: [self.reserve0, self.reserve1],
+            lp_fee: self.lp_fee,
+            protocol_fee: self.protocol_fee,
+        }
+    }
+}
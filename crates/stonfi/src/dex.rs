@@ -0,0 +1,253 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::Arc,
+};
+
+use aceton_core::{
+    ton_utils::{
+        contract::TonContract,
+        jetton::{JettonMasterI, JettonTransfer},
+        transactions::TransactionMessage,
+    },
+    Asset, Dex, DexBody, DexPayout, DexPool, SwapPath,
+};
+use aceton_utils::RateLimiter;
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use futures::lock::Mutex;
+use lazy_static::lazy_static;
+use num::{BigUint, One};
+use tlb_ton::MsgAddress;
+use tonlibjson_client::ton::TonClient;
+use tracing::{debug, instrument};
+
+use crate::{
+    api::StonfiHTTPClient, StonfiPool, StonfiPoolI, StonfiSwap, STONFI_PTON_V1_MAINNET_ADDRESS,
+    STONFI_ROUTER_V1_MAINNET_ADDRESS,
+};
+
+pub struct StonFi {
+    ton_client: TonClient,
+    ton_limiter: Option<Arc<RateLimiter>>,
+    api: StonfiHTTPClient,
+    router: MsgAddress,
+    /// Proxy TON jetton master
+    pton: MsgAddress,
+    /// (jetton_master, owner) -> jetton_wallet
+    jetton_wallets: Mutex<HashMap<(MsgAddress, MsgAddress), MsgAddress>>,
+}
+
+impl StonFi {
+    pub fn new(
+        ton_client: TonClient,
+        router: MsgAddress,
+        pton: MsgAddress,
+        http_client: reqwest::Client,
+    ) -> Self {
+        Self {
+            ton_client,
+            ton_limiter: None,
+            router,
+            pton,
+            api: StonfiHTTPClient::new(http_client),
+            jetton_wallets: Default::default(),
+        }
+    }
+
+    /// v1 router and proxy TON on mainnet
+    pub fn mainnet(ton_client: TonClient, http_client: reqwest::Client) -> Self {
+        Self::new(
+            ton_client,
+            STONFI_ROUTER_V1_MAINNET_ADDRESS,
+            STONFI_PTON_V1_MAINNET_ADDRESS,
+            http_client,
+        )
+    }
+
+    /// Limits get-method calls to TON client
+    pub fn with_ton_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.ton_limiter = Some(limiter);
+        self
+    }
+
+    fn contract(&self, address: MsgAddress) -> TonContract {
+        TonContract::new(self.ton_client.clone(), address).with_limiter(self.ton_limiter.clone())
+    }
+
+    #[instrument(skip(self))]
+    async fn jetton_wallet_address(
+        &self,
+        master: MsgAddress,
+        owner: MsgAddress,
+    ) -> anyhow::Result<MsgAddress> {
+        let mut jetton_wallets = self.jetton_wallets.lock().await;
+        Ok(match jetton_wallets.entry((master, owner)) {
+            Entry::Occupied(entry) => *entry.get(),
+            Entry::Vacant(entry) => {
+                let master = self.contract(master);
+                let address = master.get_wallet_address(owner).await?;
+                debug!(%address, "resolved jetton wallet address");
+                *entry.insert(address)
+            }
+        })
+    }
+
+    /// Builds body for a swap through a single pool
+    async fn make_swap(
+        &self,
+        query_id: u64,
+        sender: MsgAddress,
+        asset_in: Asset,
+        amount_in: BigUint,
+        asset_out: Asset,
+        min_out: BigUint,
+    ) -> anyhow::Result<DexBody<JettonTransfer<(), StonfiSwap>>> {
+        let forward_payload = StonfiSwap {
+            ask_wallet: self
+                .jetton_wallet_address(self.jetton_master(asset_out)?, self.router)
+                .await?,
+            min_out,
+            to: sender,
+            referral: None,
+        };
+
+        let (dst, forward_ton_amount) = match asset_in {
+            // proxy TON wallet of the router mints pTONs on incoming TONs
+            Asset::Native => (
+                self.jetton_wallet_address(self.pton, self.router).await?,
+                TON_SWAP_FORWARD_GAS.clone(),
+            ),
+            Asset::Jetton(master) => (
+                self.jetton_wallet_address(master, sender).await?,
+                JETTON_SWAP_FORWARD_GAS.clone(),
+            ),
+            Asset::ExtraCurrency { .. } => {
+                return Err(anyhow!("extra currencies are not supported"))
+            }
+        };
+
+        let gas = Self::gas(asset_in);
+        Ok(DexBody {
+            dst,
+            value: match asset_in {
+                Asset::Native => &gas + &amount_in,
+                _ => gas.clone(),
+            },
+            gas,
+            body: JettonTransfer {
+                query_id,
+                amount: amount_in,
+                dst: self.router,
+                response_dst: sender,
+                custom_payload: None,
+                forward_ton_amount,
+                forward_payload,
+            },
+        })
+    }
+
+    fn gas(asset_in: Asset) -> BigUint {
+        match asset_in {
+            Asset::Native => TON_SWAP_FORWARD_GAS.clone(),
+            _ => JETTON_SWAP_GAS.clone(),
+        }
+    }
+
+    /// Returns jetton master representing the asset in the router
+    fn jetton_master(&self, asset: Asset) -> anyhow::Result<MsgAddress> {
+        match asset {
+            Asset::Native => Ok(self.pton),
+            Asset::Jetton(master) => Ok(master),
+            Asset::ExtraCurrency { .. } => Err(anyhow!("extra currencies are not supported")),
+        }
+    }
+}
+
+lazy_static! {
+    // TON -> jetton: forwarded to proxy TON wallet as is
+    static ref TON_SWAP_FORWARD_GAS: BigUint = 215_000_000u32.into();
+    // jetton -> jetton: 0.22 TON with 0.175 TON forwarded to the router
+    static ref JETTON_SWAP_GAS: BigUint = 220_000_000u32.into();
+    static ref JETTON_SWAP_FORWARD_GAS: BigUint = 175_000_000u32.into();
+}
+
+#[async_trait]
+impl Dex for StonFi {
+    type Pool = StonfiPool;
+    type Body = JettonTransfer<(), StonfiSwap>;
+
+    #[instrument(skip(self))]
+    async fn get_pools(&self) -> anyhow::Result<Vec<Self::Pool>> {
+        Ok(self
+            .api
+            .get_pools()
+            .await?
+            .into_iter()
+            .filter(|pool| pool.router_address == self.router && !pool.deprecated)
+            .map(|pool| pool.into_pool(self.pton))
+            .filter(|pool| pool.reserves().into_iter().all(|r| r > &BigUint::one()))
+            .collect())
+    }
+
+    #[instrument(skip_all, fields(%pool.address))]
+    async fn update_pool(&self, pool: &mut Self::Pool) -> anyhow::Result<bool> {
+        let pool_contract = self.contract(pool.address);
+        let data = pool_contract.get_pool_data().await?;
+        let is_updated = pool.reserves != data.reserves;
+        pool.reserves = data.reserves;
+        pool.lp_fee = data.lp_fee;
+        pool.protocol_fee = data.protocol_fee;
+        if is_updated {
+            debug!("pool updated");
+        }
+        Ok(is_updated)
+    }
+
+    /// Sum over hops, since each of them is sent in its own message
+    fn estimate_gas(&self, path: &SwapPath<&Self::Pool>) -> BigUint {
+        path.iter_steps()
+            .map(|step| Self::gas(step.asset_in()))
+            .sum()
+    }
+
+    /// v1 router swaps through a single pool per message and pays out
+    /// with plain jetton transfers without forward payload, so that swaps
+    /// can not be chained. Each hop is sent as a separate message spending
+    /// `min_out` of the previous hop from the sender's balance.
+    async fn make_body(
+        &self,
+        query_id: u64,
+        sender: MsgAddress,
+        asset_in: Asset,
+        amount_in: BigUint,
+        steps: <Self::Pool as DexPool>::Step,
+    ) -> anyhow::Result<Vec<DexBody<Self::Body>>> {
+        let mut bodies = Vec::with_capacity(steps.hops());
+        let (mut asset_in, mut amount_in) = (asset_in, amount_in);
+        let mut step = Some(Box::new(steps));
+        while let Some(hop) = step {
+            let asset_out = hop
+                .asset_out(asset_in)
+                .with_context(|| format!("{asset_in} is not in the pool"))?;
+            bodies.push(
+                self.make_swap(
+                    query_id,
+                    sender,
+                    asset_in,
+                    amount_in,
+                    asset_out,
+                    hop.min_out.clone(),
+                )
+                .await?,
+            );
+            (asset_in, amount_in, step) = (asset_out, hop.min_out, hop.next);
+        }
+        Ok(bodies)
+    }
+
+    async fn parse_payout(&self, _msg: &TransactionMessage) -> anyhow::Result<Option<DexPayout>> {
+        // v1 router pays out with plain jetton transfers, which carry
+        // neither forward payload nor the result of the swap
+        Ok(None)
+    }
+}
@@ -0,0 +1,6 @@
+pub mod api;
+mod dex;
+mod pool;
+mod router;
+
+pub use self::{dex::*, pool::*, router::*};
@@ -0,0 +1,306 @@
+use aceton_core::{
+    ton_utils::{adapters::TvmBoxedStackEntryExt, contract::TonContractI},
+    Asset, DexPool,
+};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use impl_tools::autoimpl;
+use num::{rational::Ratio, BigUint, Integer};
+use tlb::Data;
+use tlb_ton::MsgAddress;
+
+/// Denominator of fees in basis points
+pub const FEE_DIVIDER: u16 = 10_000;
+
+pub struct StonfiPoolData {
+    pub reserves: [BigUint; 2],
+    /// Router's jetton wallets of pool assets
+    pub token_wallets: [MsgAddress; 2],
+    /// In basis points
+    pub lp_fee: u16,
+    /// In basis points
+    pub protocol_fee: u16,
+    /// In basis points
+    pub ref_fee: u16,
+    pub protocol_fee_address: MsgAddress,
+    pub collected_protocol_fees: [BigUint; 2],
+}
+
+pub struct ExpectedOutputs {
+    /// Amount of the other asset to receive
+    pub amount_out: BigUint,
+    pub protocol_fee_out: BigUint,
+    pub ref_fee_out: BigUint,
+}
+
+#[async_trait]
+pub trait StonfiPoolI: TonContractI {
+    async fn get_pool_data(&self) -> anyhow::Result<StonfiPoolData> {
+        let [reserve0, reserve1, wallet0, wallet1, lp_fee, protocol_fee, ref_fee, protocol_fee_address, collected0, collected1] =
+            self.get("get_pool_data", [].into())
+                .await??
+                .try_into()
+                .map_err(|stack| anyhow!("invalid output stack: {stack:?}"))?;
+
+        Ok(StonfiPoolData {
+            reserves: [reserve0.into_number()?, reserve1.into_number()?],
+            token_wallets: [
+                wallet0.parse_cell_fully_as::<_, Data>()?,
+                wallet1.parse_cell_fully_as::<_, Data>()?,
+            ],
+            lp_fee: lp_fee.into_number()?,
+            protocol_fee: protocol_fee.into_number()?,
+            ref_fee: ref_fee.into_number()?,
+            protocol_fee_address: protocol_fee_address.parse_cell_fully_as::<_, Data>()?,
+            collected_protocol_fees: [collected0.into_number()?, collected1.into_number()?],
+        })
+    }
+
+    /// Estimates swap of `amount_in` sent from router's `token_wallet`
+    async fn get_expected_outputs(
+        &self,
+        amount_in: BigUint,
+        token_wallet: MsgAddress,
+    ) -> anyhow::Result<ExpectedOutputs> {
+        let [amount_out, protocol_fee_out, ref_fee_out] = self
+            .get(
+                "get_expected_outputs",
+                [
+                    TvmBoxedStackEntryExt::from_number(amount_in),
+                    TvmBoxedStackEntryExt::store_cell_as::<_, Data>(token_wallet)?,
+                ]
+                .into(),
+            )
+            .await??
+            .try_into()
+            .map_err(|stack| anyhow!("invalid output stack: {stack:?}"))?;
+
+        Ok(ExpectedOutputs {
+            amount_out: amount_out.into_number()?,
+            protocol_fee_out: protocol_fee_out.into_number()?,
+            ref_fee_out: ref_fee_out.into_number()?,
+        })
+    }
+}
+
+impl<C> StonfiPoolI for C where C: TonContractI {}
+
+#[derive(Debug, Clone)]
+#[autoimpl(PartialEq ignore self.assets, self.reserves, self.lp_fee, self.protocol_fee)]
+#[autoimpl(Eq)]
+#[autoimpl(Hash ignore self.assets, self.reserves, self.lp_fee, self.protocol_fee)]
+pub struct StonfiPool {
+    pub address: MsgAddress,
+    pub assets: [Asset; 2],
+    pub reserves: [BigUint; 2],
+    /// In basis points, taken from the incoming asset
+    pub lp_fee: u16,
+    /// In basis points, taken from the outcoming asset
+    pub protocol_fee: u16,
+}
+
+impl DexPool for StonfiPool {
+    type ID = MsgAddress;
+    type Step = StonfiSwapStep;
+
+    #[inline]
+    fn id(&self) -> Self::ID {
+        self.address
+    }
+
+    #[inline]
+    fn address(&self) -> MsgAddress {
+        self.address
+    }
+
+    #[inline]
+    fn assets(&self) -> [Asset; 2] {
+        self.assets
+    }
+
+    #[inline]
+    fn reserves(&self) -> [&BigUint; 2] {
+        let [ref r0, ref r1] = &self.reserves;
+        [r0, r1]
+    }
+
+    #[inline]
+    fn trade_fees(&self) -> [Ratio<BigUint>; 2] {
+        [self.lp_fee, self.protocol_fee]
+            .map(|fee| Ratio::new((FEE_DIVIDER - fee).into(), FEE_DIVIDER.into()))
+    }
+
+    /// Same as `get_amount_out()` in v1 pool contract without referral:
+    /// protocol fee is rounded up
+    fn estimate_swap_out(&self, asset_in: Asset, amount_in: &BigUint) -> BigUint {
+        let [reserve_in, reserve_out] = self.reserves_in_out(asset_in);
+        if [amount_in, reserve_in, reserve_out]
+            .into_iter()
+            .any(|v| v == &BigUint::ZERO)
+        {
+            return BigUint::ZERO;
+        }
+
+        let amount_in_with_fee = amount_in * (FEE_DIVIDER - self.lp_fee);
+        let base_out =
+            &amount_in_with_fee * reserve_out / (reserve_in * FEE_DIVIDER + amount_in_with_fee);
+        let protocol_fee_out = Integer::div_ceil(
+            &(&base_out * self.protocol_fee),
+            &BigUint::from(FEE_DIVIDER),
+        );
+        base_out - protocol_fee_out
+    }
+
+    fn make_step(&self, amount_out_min: Option<BigUint>, next: Option<Self::Step>) -> Self::Step {
+        StonfiSwapStep {
+            pool: self.address,
+            assets: self.assets,
+            min_out: amount_out_min.unwrap_or(BigUint::ZERO),
+            next: next.map(Box::new),
+        }
+    }
+}
+
+/// Chain of swaps through single pools.
+/// v1 router swaps through one pool per message, so each hop is
+/// executed by its own message.
+pub struct StonfiSwapStep {
+    pub pool: MsgAddress,
+    pub assets: [Asset; 2],
+    pub min_out: BigUint,
+    pub next: Option<Box<StonfiSwapStep>>,
+}
+
+impl StonfiSwapStep {
+    /// Number of pools to swap through
+    pub fn hops(&self) -> usize {
+        1 + self.next.as_deref().map_or(0, StonfiSwapStep::hops)
+    }
+
+    pub fn asset_out(&self, asset_in: Asset) -> Option<Asset> {
+        match self.assets {
+            [a0, a1] if a0 == asset_in => Some(a1),
+            [a0, a1] if a1 == asset_in => Some(a0),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonlibjson_client::block::{SmcRunResult, TvmBoxedStackEntry};
+
+    use super::*;
+
+    const POOL: MsgAddress = MsgAddress {
+        workchain_id: 0,
+        address: [5; 32],
+    };
+    const TOKEN0_WALLET: MsgAddress = MsgAddress {
+        workchain_id: 0,
+        address: [6; 32],
+    };
+    const TOKEN1_WALLET: MsgAddress = MsgAddress {
+        workchain_id: 0,
+        address: [7; 32],
+    };
+
+    struct MockPool {
+        stack: Vec<TvmBoxedStackEntry>,
+    }
+
+    #[async_trait]
+    impl TonContractI for MockPool {
+        async fn run_get_method(
+            &self,
+            method: &str,
+            _stack: Vec<TvmBoxedStackEntry>,
+        ) -> anyhow::Result<SmcRunResult> {
+            if method != "get_pool_data" {
+                return Err(anyhow!("unknown get-method: {method}"));
+            }
+            Ok(SmcRunResult {
+                gas_used: 0,
+                stack: self.stack.clone(),
+                exit_code: 0,
+            })
+        }
+    }
+
+    fn pool() -> StonfiPool {
+        StonfiPool {
+            address: POOL,
+            assets: [Asset::Native, Asset::Jetton(MsgAddress::NULL)],
+            reserves: [10_000_000_000u64, 25_000_000_000u64].map(Into::into),
+            lp_fee: 20,
+            protocol_fee: 10,
+        }
+    }
+
+    #[tokio::test]
+    async fn get_pool_data() {
+        let contract = MockPool {
+            stack: [
+                TvmBoxedStackEntryExt::from_number(10_000_000_000u64),
+                TvmBoxedStackEntryExt::from_number(25_000_000_000u64),
+                TvmBoxedStackEntryExt::store_cell_as::<_, Data>(TOKEN0_WALLET).unwrap(),
+                TvmBoxedStackEntryExt::store_cell_as::<_, Data>(TOKEN1_WALLET).unwrap(),
+                TvmBoxedStackEntryExt::from_number(20),
+                TvmBoxedStackEntryExt::from_number(10),
+                TvmBoxedStackEntryExt::from_number(10),
+                TvmBoxedStackEntryExt::store_cell_as::<_, Data>(MsgAddress::NULL).unwrap(),
+                TvmBoxedStackEntryExt::from_number(1_000),
+                TvmBoxedStackEntryExt::from_number(2_000),
+            ]
+            .into(),
+        };
+
+        let data = contract.get_pool_data().await.unwrap();
+        assert_eq!(data.reserves, pool().reserves);
+        assert_eq!(data.token_wallets, [TOKEN0_WALLET, TOKEN1_WALLET]);
+        assert_eq!((data.lp_fee, data.protocol_fee, data.ref_fee), (20, 10, 10));
+        assert_eq!(
+            data.collected_protocol_fees,
+            [1_000u32, 2_000u32].map(Into::into)
+        );
+    }
+
+    #[test]
+    fn estimate_swap_out() {
+        let p = pool();
+        assert_eq!(
+            p.estimate_swap_out(p.assets[0], &1_000_000_000u32.into()),
+            2_266_325_694u32.into(),
+        );
+        assert_eq!(
+            p.estimate_swap_out(p.assets[1], &1_000_000_000u32.into()),
+            383_491_806u32.into(),
+        );
+        // protocol fee is rounded up
+        assert_eq!(p.estimate_swap_out(p.assets[0], &3u32.into()), 6u32.into());
+    }
+
+    #[test]
+    fn step_asset_out() {
+        let p = pool();
+        let step = p.make_step(None, None);
+        assert_eq!(step.asset_out(p.assets[0]), Some(p.assets[1]));
+        assert_eq!(step.asset_out(p.assets[1]), Some(p.assets[0]));
+        assert_eq!(
+            step.asset_out(Asset::ExtraCurrency { currency_id: 1 }),
+            None
+        );
+    }
+
+    #[test]
+    fn step_chains_hops() {
+        let p = pool();
+        let step = p.make_step(
+            Some(10u32.into()),
+            Some(p.make_step(Some(20u32.into()), None)),
+        );
+        assert_eq!(step.hops(), 2);
+        assert_eq!(step.min_out, BigUint::from(10u32));
+        assert_eq!(step.next.unwrap().min_out, BigUint::from(20u32));
+    }
+}
@@ -0,0 +1,282 @@
+use aceton_core::ton_utils::{adapters::TvmBoxedStackEntryExt, contract::TonContractI};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use hex_literal::hex;
+use num::BigUint;
+use tlb::{
+    BitReaderExt, BitWriterExt, CellBuilder, CellBuilderError, CellDeserialize, CellParser,
+    CellParserError, CellSerialize, ConstU32, Data,
+};
+use tlb_ton::{Coins, MsgAddress};
+
+/// EQB3ncyBUTjZUA5EnFKR5_EnOMI9V1tTEAAPaiU71gc4TiUt
+pub const STONFI_ROUTER_V1_MAINNET_ADDRESS: MsgAddress = MsgAddress {
+    workchain_id: 0,
+    address: hex!("779dcc815138d9500e449c5291e7f12738c23d575b5310000f6a253bd607384e"),
+};
+
+/// Proxy TON jetton master used by v1 router to swap TONs:
+/// EQCM3B12QK1e4yZSf8GtBRT0aLMNyEsBc_DhVfRRtOEffLez
+pub const STONFI_PTON_V1_MAINNET_ADDRESS: MsgAddress = MsgAddress {
+    workchain_id: 0,
+    address: hex!("8cdc1d7640ad5ee326527fc1ad0514f468b30dc84b0173f0e155f451b4e11f7c"),
+};
+
+#[async_trait]
+pub trait StonfiRouterI: TonContractI {
+    /// Returns address of the pool for given router's jetton wallets
+    async fn get_pool_address(&self, token_wallets: [MsgAddress; 2]) -> anyhow::Result<MsgAddress> {
+        let [pool] = self
+            .get(
+                "get_pool_address",
+                [
+                    TvmBoxedStackEntryExt::store_cell_as::<_, Data>(token_wallets[0])?,
+                    TvmBoxedStackEntryExt::store_cell_as::<_, Data>(token_wallets[1])?,
+                ]
+                .into(),
+            )
+            .await??
+            .try_into()
+            .map_err(|stack| anyhow!("invalid output stack: {stack:?}"))?;
+        pool.parse_cell_fully_as::<_, Data>()
+    }
+}
+
+impl<C> StonfiRouterI for C where C: TonContractI {}
+
+const SWAP_TAG: u32 = 0x25938561;
+
+/// Forward payload of the jetton transfer to the router:
+/// swap#25938561 token_wallet1:MsgAddress min_out:Coins to_address:MsgAddress
+/// referral_address:(Maybe MsgAddress) = ForwardPayload;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StonfiSwap {
+    /// Router's jetton wallet of the asset to receive
+    pub ask_wallet: MsgAddress,
+    /// Swap is refunded if the output is less than this
+    pub min_out: BigUint,
+    /// Recepient of the output
+    pub to: MsgAddress,
+    pub referral: Option<MsgAddress>,
+}
+
+impl CellSerialize for StonfiSwap {
+    fn store(&self, builder: &mut CellBuilder) -> Result<(), CellBuilderError> {
+        builder
+            .pack(SWAP_TAG)?
+            .pack(self.ask_wallet)?
+            .pack_as::<_, &Coins>(&self.min_out)?
+            .pack(self.to)?
+            .pack(self.referral)?;
+        Ok(())
+    }
+}
+
+impl<'de> CellDeserialize<'de> for StonfiSwap {
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        parser.unpack::<ConstU32<SWAP_TAG>>()?;
+        Ok(Self {
+            ask_wallet: parser.unpack()?,
+            min_out: parser.unpack_as::<_, Coins>()?,
+            to: parser.unpack()?,
+            referral: parser.unpack()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aceton_core::ton_utils::jetton::{JettonTransfer, JettonTransferNotification};
+    use tlb::{unpack_bytes, Cell, CellSerializeExt, Ref};
+    use tlb_ton::BoC;
+
+    use super::*;
+
+    const OWNER: MsgAddress = MsgAddress {
+        workchain_id: 0,
+        address: [1; 32],
+    };
+    const ASK_WALLET: MsgAddress = MsgAddress {
+        workchain_id: 0,
+        address: [3; 32],
+    };
+    const REFERRAL: MsgAddress = MsgAddress {
+        workchain_id: 0,
+        address: [4; 32],
+    };
+
+    fn swap(referral: Option<MsgAddress>) -> StonfiSwap {
+        StonfiSwap {
+            ask_wallet: ASK_WALLET,
+            min_out: 1_990_000u32.into(),
+            to: OWNER,
+            referral,
+        }
+    }
+
+    fn swap_cell(referral: Option<MsgAddress>) -> Cell {
+        let mut builder = Cell::builder();
+        builder
+            .pack(0x25938561_u32)
+            .unwrap()
+            .pack(ASK_WALLET)
+            .unwrap()
+            .pack_as::<_, Coins>(BigUint::from(1_990_000_u32))
+            .unwrap()
+            .pack(OWNER)
+            .unwrap();
+        match referral {
+            // just$1 MsgAddress
+            Some(referral) => builder.pack(true).unwrap().pack(referral).unwrap(),
+            // nothing$0
+            None => builder.pack(false).unwrap(),
+        };
+        builder.into_cell()
+    }
+
+    #[test]
+    fn swap_layout() {
+        for referral in [None, Some(REFERRAL)] {
+            let expected = swap_cell(referral);
+            assert_eq!(swap(referral).to_cell().unwrap(), expected);
+            assert_eq!(
+                expected.parse_fully::<StonfiSwap>().unwrap(),
+                swap(referral)
+            );
+        }
+    }
+
+    #[test]
+    fn transfer_to_router_layout() {
+        let transfer: JettonTransfer<(), StonfiSwap> = JettonTransfer {
+            query_id: 42,
+            amount: 1_000_000_000u32.into(),
+            dst: STONFI_ROUTER_V1_MAINNET_ADDRESS,
+            response_dst: OWNER,
+            custom_payload: None,
+            forward_ton_amount: 215_000_000u32.into(),
+            forward_payload: swap(None),
+        };
+
+        let mut expected = Cell::builder();
+        expected
+            .pack(0x0f8a7ea5_u32)
+            .unwrap()
+            .pack(42_u64)
+            .unwrap()
+            .pack_as::<_, Coins>(BigUint::from(1_000_000_000_u32))
+            .unwrap()
+            .pack(STONFI_ROUTER_V1_MAINNET_ADDRESS)
+            .unwrap()
+            .pack(OWNER)
+            .unwrap()
+            // custom_payload: nothing$0
+            .pack(false)
+            .unwrap()
+            .pack_as::<_, Coins>(BigUint::from(215_000_000_u32))
+            .unwrap()
+            // forward_payload: right$1 ^Cell
+            .pack(true)
+            .unwrap()
+            .store_as::<_, Ref>(swap_cell(None))
+            .unwrap();
+        let expected = expected.into_cell();
+        assert_eq!(transfer.to_cell().unwrap(), expected);
+
+        let parsed: JettonTransfer<(), StonfiSwap> = expected.parse_fully().unwrap();
+        assert_eq!(parsed.query_id, 42);
+        assert_eq!(parsed.amount, transfer.amount);
+        assert_eq!(parsed.dst, STONFI_ROUTER_V1_MAINNET_ADDRESS);
+        assert_eq!(parsed.response_dst, OWNER);
+        assert_eq!(parsed.forward_ton_amount, transfer.forward_ton_amount);
+        assert_eq!(parsed.forward_payload, swap(None));
+    }
+
+    const ROUTER_SWAPS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/router_swaps");
+
+    /// Reads transfer notifications with swap payloads received by the
+    /// mainnet router, captured by an ignored test. Empty if not captured.
+    fn captured_swaps() -> Vec<Cell> {
+        let dir = match std::fs::read_dir(ROUTER_SWAPS) {
+            Ok(dir) => dir,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                eprintln!("skipping: {ROUTER_SWAPS} is not captured yet");
+                return Vec::new();
+            }
+            Err(err) => panic!("{ROUTER_SWAPS}: {err}"),
+        };
+        dir.map(|entry| {
+            let path = entry.unwrap().path();
+            let boc: BoC = unpack_bytes(std::fs::read(&path).unwrap())
+                .unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+            Cell::clone(boc.single_root().unwrap())
+        })
+        .collect()
+    }
+
+    fn swap_payload(notification: &Cell) -> Option<Cell> {
+        if notification.parser().unpack::<u32>().ok() != Some(0x7362d09c) {
+            return None;
+        }
+        let notification: JettonTransferNotification<Cell> = notification.parse_fully().unwrap();
+        let payload = Cell::clone(&notification.forward_payload);
+        (payload.parser().unpack::<u32>().ok() == Some(SWAP_TAG)).then_some(payload)
+    }
+
+    #[test]
+    fn swap_matches_captured() {
+        for notification in captured_swaps() {
+            let payload = swap_payload(&notification).expect("swap notification");
+            let swap: StonfiSwap = payload.parse_fully().unwrap();
+            assert_eq!(swap.to_cell().unwrap(), payload);
+        }
+    }
+
+    /// Captures swap payloads of transfer notifications received by the
+    /// mainnet router and checks that they are serialized back as is
+    #[tokio::test]
+    #[ignore = "queries mainnet liteservers, overwrites the fixture"]
+    async fn capture_router_swaps() {
+        use aceton_core::ton_utils::{contract::TonContract, transactions::AccountTransactionsI};
+        use tlb_ton::BagOfCells;
+        use tonlibjson_client::ton::TonClientBuilder;
+        use url::Url;
+
+        let mut ton = TonClientBuilder::from_config_url(
+            Url::parse("https://ton.org/global-config.json").unwrap(),
+            std::time::Duration::from_secs(60),
+        )
+        .build()
+        .await
+        .unwrap();
+        ton.ready().await.unwrap();
+
+        let router = TonContract::new(ton, STONFI_ROUTER_V1_MAINNET_ADDRESS);
+        let last_lt = router.last_transaction_lt().await.unwrap();
+        // roughly the last hundred blocks of the shard
+        let txs = router
+            .transactions_after(last_lt.saturating_sub(100_000_000))
+            .await
+            .unwrap();
+
+        let _ = std::fs::remove_dir_all(ROUTER_SWAPS);
+        std::fs::create_dir_all(ROUTER_SWAPS).unwrap();
+        let mut captured = 0;
+        for body in txs.into_iter().filter_map(|tx| tx.in_msg?.body) {
+            let Some(payload) = swap_payload(&body) else {
+                continue;
+            };
+            let swap: StonfiSwap = payload.parse_fully().unwrap();
+            assert_eq!(swap.to_cell().unwrap(), payload);
+            std::fs::write(
+                format!("{ROUTER_SWAPS}/{captured}.boc"),
+                BagOfCells::from_root(Cell::clone(&body))
+                    .pack(true)
+                    .unwrap(),
+            )
+            .unwrap();
+            captured += 1;
+        }
+        assert!(captured > 0, "no swaps to capture");
+    }
+}
@@ -0,0 +1,329 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::{future, TryStreamExt};
+use impl_tools::autoimpl;
+use tlb_ton::MsgAddress;
+use tonlibjson_client::ton::TonClient;
+
+const MASTERCHAIN: i32 = -1;
+/// Masterchain consists of a single shard
+const MASTERCHAIN_SHARD: i64 = i64::MIN;
+
+/// Max number of masterchain blocks to catch up with, changes made
+/// further behind are not collected
+const MAX_BLOCKS_BEHIND: u32 = 16;
+
+/// (workchain, shard) -> seqno of the last shard block
+type ShardSeqnos = HashMap<(i32, i64), u32>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockId {
+    pub workchain: i32,
+    pub shard: i64,
+    pub seqno: u32,
+}
+
+impl BlockId {
+    pub const fn masterchain(seqno: u32) -> Self {
+        Self {
+            workchain: MASTERCHAIN,
+            shard: MASTERCHAIN_SHARD,
+            seqno,
+        }
+    }
+}
+
+#[async_trait]
+#[autoimpl(for<T: trait + ?Sized> &T, Arc<T>)]
+pub trait BlocksI {
+    async fn last_masterchain_seqno(&self) -> anyhow::Result<u32>;
+
+    /// Returns the last blocks of workchain shards which are referenced
+    /// by given masterchain block
+    async fn shard_blocks(&self, masterchain_seqno: u32) -> anyhow::Result<Vec<BlockId>>;
+
+    /// Returns accounts which have transactions in the block
+    async fn block_accounts(&self, block: BlockId) -> anyhow::Result<HashSet<MsgAddress>>;
+}
+
+#[async_trait]
+impl BlocksI for TonClient {
+    async fn last_masterchain_seqno(&self) -> anyhow::Result<u32> {
+        Ok(self.get_masterchain_info().await?.last.seqno as u32)
+    }
+
+    async fn shard_blocks(&self, masterchain_seqno: u32) -> anyhow::Result<Vec<BlockId>> {
+        let block = self
+            .look_up_block_by_seqno(MASTERCHAIN, MASTERCHAIN_SHARD, masterchain_seqno as i32)
+            .await?;
+        Ok(self
+            .get_shards_by_block_id(block)
+            .await?
+            .into_iter()
+            .map(|block| BlockId {
+                workchain: block.workchain,
+                shard: block.shard,
+                seqno: block.seqno as u32,
+            })
+            .collect())
+    }
+
+    async fn block_accounts(&self, block: BlockId) -> anyhow::Result<HashSet<MsgAddress>> {
+        let block_id = self
+            .look_up_block_by_seqno(block.workchain, block.shard, block.seqno as i32)
+            .await?;
+        self.get_block_tx_id_stream(&block_id, false)
+            .and_then(|tx| future::ready(parse_account_id(block.workchain, &tx.account)))
+            .try_collect()
+            .await
+    }
+}
+
+/// Accounts in blocks are identified by base64-encoded hash part
+/// of their address
+fn parse_account_id(workchain: i32, account: &str) -> anyhow::Result<MsgAddress> {
+    Ok(MsgAddress {
+        workchain_id: workchain,
+        address: STANDARD
+            .decode(account)
+            .context("base64")?
+            .try_into()
+            .map_err(|id: Vec<u8>| anyhow!("invalid account ID length: {}", id.len()))?,
+    })
+}
+
+/// Accounts which had transactions since the previous
+/// [poll](BlockSubscription::poll)
+#[derive(Debug)]
+pub struct ChangedAccounts {
+    /// Seqno of the last masterchain block
+    pub seqno: u32,
+    /// `None` if changes are unknown, so that all accounts should be
+    /// considered changed: on the first poll, after falling too far behind
+    /// or when shards were split or merged
+    pub accounts: Option<HashSet<MsgAddress>>,
+}
+
+/// Follows new masterchain blocks along with shard blocks committed
+/// into them
+pub struct BlockSubscription<C> {
+    client: C,
+    /// Seqno of the last seen masterchain block along with seqnos of
+    /// shard blocks it references
+    last: Option<(u32, ShardSeqnos)>,
+}
+
+impl<C> BlockSubscription<C>
+where
+    C: BlocksI + Sync,
+{
+    pub fn new(client: C) -> Self {
+        Self { client, last: None }
+    }
+
+    /// Returns accounts changed since the previous poll, or `None` if
+    /// there is no new masterchain block yet
+    pub async fn poll(&mut self) -> anyhow::Result<Option<ChangedAccounts>> {
+        let seqno = self.client.last_masterchain_seqno().await?;
+        if self
+            .last
+            .as_ref()
+            .is_some_and(|(last_seqno, _)| seqno <= *last_seqno)
+        {
+            return Ok(None);
+        }
+        let shards = self.shard_seqnos(seqno).await?;
+        let accounts = match &self.last {
+            Some((last_seqno, last_shards)) if seqno - last_seqno <= MAX_BLOCKS_BEHIND => {
+                self.changed_accounts((*last_seqno, last_shards), (seqno, &shards))
+                    .await?
+            }
+            _ => None,
+        };
+        self.last = Some((seqno, shards));
+        Ok(Some(ChangedAccounts { seqno, accounts }))
+    }
+
+    async fn shard_seqnos(&self, masterchain_seqno: u32) -> anyhow::Result<ShardSeqnos> {
+        Ok(self
+            .client
+            .shard_blocks(masterchain_seqno)
+            .await?
+            .into_iter()
+            .map(|block| ((block.workchain, block.shard), block.seqno))
+            .collect())
+    }
+
+    /// Collects accounts of all masterchain and shard blocks after the
+    /// last seen ones up to the new ones
+    async fn changed_accounts(
+        &self,
+        (last_seqno, last_shards): (u32, &ShardSeqnos),
+        (seqno, shards): (u32, &ShardSeqnos),
+    ) -> anyhow::Result<Option<HashSet<MsgAddress>>> {
+        let mut blocks: Vec<_> = (last_seqno + 1..=seqno).map(BlockId::masterchain).collect();
+        for (&(workchain, shard), &shard_seqno) in shards {
+            // shards were split or merged
+            let Some(last_shard_seqno) = last_shards.get(&(workchain, shard)) else {
+                return Ok(None);
+            };
+            blocks.extend((last_shard_seqno + 1..=shard_seqno).map(|seqno| BlockId {
+                workchain,
+                shard,
+                seqno,
+            }));
+        }
+        Ok(Some(
+            future::try_join_all(
+                blocks
+                    .into_iter()
+                    .map(|block| self.client.block_accounts(block)),
+            )
+            .await?
+            .into_iter()
+            .flatten()
+            .collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    const SHARD: i64 = i64::MIN;
+
+    fn account(id: u8) -> MsgAddress {
+        MsgAddress {
+            workchain_id: 0,
+            address: [id; 32],
+        }
+    }
+
+    fn shard_block(shard: i64, seqno: u32) -> BlockId {
+        BlockId {
+            workchain: 0,
+            shard,
+            seqno,
+        }
+    }
+
+    #[derive(Default)]
+    struct MockBlocks {
+        seqno: Mutex<u32>,
+        /// masterchain seqno -> shard blocks
+        shards: HashMap<u32, Vec<BlockId>>,
+        accounts: HashMap<BlockId, HashSet<MsgAddress>>,
+    }
+
+    impl MockBlocks {
+        fn with_accounts(mut self, block: BlockId, ids: impl IntoIterator<Item = u8>) -> Self {
+            self.accounts
+                .insert(block, ids.into_iter().map(account).collect());
+            self
+        }
+
+        fn advance(&self, seqno: u32) {
+            *self.seqno.lock().unwrap() = seqno;
+        }
+    }
+
+    #[async_trait]
+    impl BlocksI for MockBlocks {
+        async fn last_masterchain_seqno(&self) -> anyhow::Result<u32> {
+            Ok(*self.seqno.lock().unwrap())
+        }
+
+        async fn shard_blocks(&self, masterchain_seqno: u32) -> anyhow::Result<Vec<BlockId>> {
+            self.shards
+                .get(&masterchain_seqno)
+                .cloned()
+                .context("unknown masterchain block")
+        }
+
+        async fn block_accounts(&self, block: BlockId) -> anyhow::Result<HashSet<MsgAddress>> {
+            Ok(self.accounts.get(&block).cloned().unwrap_or_default())
+        }
+    }
+
+    #[tokio::test]
+    async fn first_poll_requires_resync() {
+        let blocks = MockBlocks {
+            seqno: 1.into(),
+            shards: [(1, vec![shard_block(SHARD, 10)])].into(),
+            ..Default::default()
+        };
+        let mut subscription = BlockSubscription::new(blocks);
+
+        let changed = subscription.poll().await.unwrap().unwrap();
+        assert_eq!(changed.seqno, 1);
+        assert!(changed.accounts.is_none());
+        assert!(subscription.poll().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn collects_accounts_of_skipped_shard_blocks() {
+        let blocks = MockBlocks {
+            seqno: 1.into(),
+            shards: [
+                (1, vec![shard_block(SHARD, 10)]),
+                (2, vec![shard_block(SHARD, 12)]),
+            ]
+            .into(),
+            ..Default::default()
+        }
+        .with_accounts(shard_block(SHARD, 10), [1])
+        .with_accounts(shard_block(SHARD, 11), [2])
+        .with_accounts(shard_block(SHARD, 12), [3])
+        .with_accounts(BlockId::masterchain(2), [4]);
+        let mut subscription = BlockSubscription::new(&blocks);
+        subscription.poll().await.unwrap();
+
+        blocks.advance(2);
+        let changed = subscription.poll().await.unwrap().unwrap();
+        assert_eq!(changed.seqno, 2);
+        assert_eq!(
+            changed.accounts.unwrap(),
+            [2, 3, 4].into_iter().map(account).collect()
+        );
+    }
+
+    #[tokio::test]
+    async fn shard_split_requires_resync() {
+        let blocks = MockBlocks {
+            seqno: 1.into(),
+            shards: [
+                (1, vec![shard_block(SHARD, 10)]),
+                (
+                    2,
+                    vec![
+                        shard_block(0x4000_0000_0000_0000, 11),
+                        shard_block(-0x4000_0000_0000_0000, 11),
+                    ],
+                ),
+            ]
+            .into(),
+            ..Default::default()
+        }
+        .with_accounts(shard_block(0x4000_0000_0000_0000, 11), [1]);
+        let mut subscription = BlockSubscription::new(&blocks);
+        subscription.poll().await.unwrap();
+
+        blocks.advance(2);
+        assert!(subscription
+            .poll()
+            .await
+            .unwrap()
+            .unwrap()
+            .accounts
+            .is_none());
+    }
+}
@@ -1,3 +1,6 @@
+use std::sync::Arc;
+
+use aceton_utils::RateLimiter;
 use async_trait::async_trait;
 use thiserror::Error as ThisError;
 use tlb_ton::MsgAddress;
@@ -6,22 +9,97 @@ use tonlibjson_client::{
     ton::TonClient,
 };
 
+/// Get-method exited with non-zero exit code
 #[derive(Debug, ThisError)]
-#[error("exit code: {0}")] // TODO
-pub struct TonContractError(i32);
-
-impl TonContractError {
-    // pub const fn new(exit_code: i32) -> Self {
-    //     match exit_code {
-    //         0 | 1 => ,
-    //         _ => Self(exit_code),
-    //     }
-    // }
-    // fn to_str(&self) -> &'static str {
-    //     match self {
-
-    //     }
-    // }
+#[error("{exit_code} (gas used: {gas_used})")]
+pub struct TonContractError {
+    pub exit_code: ExitCode,
+    pub gas_used: u64,
+    /// Stack left by the get-method, which might contain arguments
+    /// passed to `throw_arg()`
+    pub stack: Vec<TvmBoxedStackEntry>,
+}
+
+/// Exit code of TVM: either standard or thrown by the contract itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ThisError)]
+pub enum ExitCode {
+    #[error("stack underflow")]
+    StackUnderflow,
+    #[error("stack overflow")]
+    StackOverflow,
+    #[error("integer overflow")]
+    IntegerOverflow,
+    #[error("integer out of expected range")]
+    RangeCheck,
+    #[error("invalid opcode")]
+    InvalidOpcode,
+    #[error("type check error")]
+    TypeCheck,
+    #[error("cell overflow")]
+    CellOverflow,
+    #[error("cell underflow")]
+    CellUnderflow,
+    #[error("dictionary error")]
+    Dictionary,
+    /// Also thrown by FunC on call of a missing get-method
+    #[error("unknown error, get-method might be missing")]
+    Unknown,
+    #[error("fatal error")]
+    Fatal,
+    #[error("out of gas")]
+    OutOfGas,
+    #[error("virtualization error")]
+    Virtualization,
+    /// Reported by liteservers for accounts without code
+    #[error("contract is not initialized")]
+    Uninitialized,
+    /// Thrown by the contract with `throw()`
+    #[error("contract error: {0}")]
+    Contract(i32),
+}
+
+impl ExitCode {
+    pub const fn from_code(code: i32) -> Self {
+        match code {
+            2 => Self::StackUnderflow,
+            3 => Self::StackOverflow,
+            4 => Self::IntegerOverflow,
+            5 => Self::RangeCheck,
+            6 => Self::InvalidOpcode,
+            7 => Self::TypeCheck,
+            8 => Self::CellOverflow,
+            9 => Self::CellUnderflow,
+            10 => Self::Dictionary,
+            11 => Self::Unknown,
+            12 => Self::Fatal,
+            // -14 is reported when gas limit is exceeded before
+            // the exception handler is called
+            13 | -14 => Self::OutOfGas,
+            14 => Self::Virtualization,
+            -13 => Self::Uninitialized,
+            code => Self::Contract(code),
+        }
+    }
+
+    pub const fn code(&self) -> i32 {
+        match self {
+            Self::StackUnderflow => 2,
+            Self::StackOverflow => 3,
+            Self::IntegerOverflow => 4,
+            Self::RangeCheck => 5,
+            Self::InvalidOpcode => 6,
+            Self::TypeCheck => 7,
+            Self::CellOverflow => 8,
+            Self::CellUnderflow => 9,
+            Self::Dictionary => 10,
+            Self::Unknown => 11,
+            Self::Fatal => 12,
+            Self::OutOfGas => 13,
+            Self::Virtualization => 14,
+            Self::Uninitialized => -13,
+            Self::Contract(code) => *code,
+        }
+    }
 }
 
 #[async_trait]
@@ -38,29 +116,51 @@ pub trait TonContractI {
         stack: Vec<TvmBoxedStackEntry>,
     ) -> anyhow::Result<Result<Vec<TvmBoxedStackEntry>, TonContractError>> {
         let SmcRunResult {
-            stack, exit_code, ..
+            stack,
+            exit_code,
+            gas_used,
+            ..
         } = self.run_get_method(method, stack).await?;
         Ok(match exit_code {
             0 | 1 => Ok(stack),
-            _ => Err(TonContractError(exit_code)),
+            _ => Err(TonContractError {
+                exit_code: ExitCode::from_code(exit_code),
+                gas_used: gas_used as u64,
+                stack,
+            }),
         })
     }
 }
 
 pub struct TonContract {
     address: MsgAddress,
-    // TODO: use emulator?
     client: TonClient,
+    limiter: Option<Arc<RateLimiter>>,
 }
 
 impl TonContract {
     pub fn new(client: TonClient, address: MsgAddress) -> Self {
-        Self { address, client }
+        Self {
+            address,
+            client,
+            limiter: None,
+        }
+    }
+
+    /// Limits get-method calls, the limiter is usually shared between
+    /// all contracts using the same client
+    pub fn with_limiter(mut self, limiter: impl Into<Option<Arc<RateLimiter>>>) -> Self {
+        self.limiter = limiter.into();
+        self
     }
 
     pub fn address(&self) -> MsgAddress {
         self.address
     }
+
+    pub fn client(&self) -> &TonClient {
+        &self.client
+    }
 }
 
 #[async_trait]
@@ -70,8 +170,63 @@ impl TonContractI for TonContract {
         method: &str,
         stack: Vec<TvmBoxedStackEntry>,
     ) -> anyhow::Result<SmcRunResult> {
-        self.client
-            .run_get_method(self.address.to_base64_std(), method.to_string(), stack)
+        let result =
+            self.client
+                .run_get_method(self.address.to_base64_std(), method.to_string(), stack);
+        match &self.limiter {
+            Some(limiter) => limiter.run(result).await,
+            None => result.await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Contract which get-methods fail as if they were missing
+    struct MockContract;
+
+    #[async_trait]
+    impl TonContractI for MockContract {
+        async fn run_get_method(
+            &self,
+            _method: &str,
+            _stack: Vec<TvmBoxedStackEntry>,
+        ) -> anyhow::Result<SmcRunResult> {
+            Ok(SmcRunResult {
+                gas_used: 1234,
+                stack: Vec::new(),
+                exit_code: 11,
+            })
+        }
+    }
+
+    #[test]
+    fn exit_code_roundtrip() {
+        for code in (-20..20).chain([100, 0xffff]) {
+            let exit_code = ExitCode::from_code(code);
+            // -14 is an alias of out of gas
+            if code != -14 {
+                assert_eq!(exit_code.code(), code);
+            }
+        }
+        assert_eq!(ExitCode::from_code(-14), ExitCode::OutOfGas);
+        assert_eq!(ExitCode::from_code(100), ExitCode::Contract(100));
+    }
+
+    #[tokio::test]
+    async fn get_keeps_failure_details() {
+        let err = MockContract
+            .get("missing", Vec::new())
             .await
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.exit_code, ExitCode::Unknown);
+        assert_eq!(err.gas_used, 1234);
+        assert_eq!(
+            err.to_string(),
+            "unknown error, get-method might be missing (gas used: 1234)"
+        );
     }
 }
@@ -0,0 +1,356 @@
+#[cfg(feature = "emulator")]
+mod native;
+mod stack;
+
+#[cfg(feature = "emulator")]
+pub use self::native::*;
+pub use self::stack::*;
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use impl_tools::autoimpl;
+use serde::{Deserialize, Serialize};
+use tlb_ton::MsgAddress;
+use tonlibjson_client::{
+    block::{SmcRunResult, TvmBoxedStackEntry},
+    ton::TonClient,
+};
+
+use crate::contract::{ExitCode, TonContractI};
+
+/// ID of get-method used by TVM to dispatch the call
+pub fn method_id(name: &str) -> u32 {
+    (crc16_xmodem(name.as_bytes()) as u32) | 0x10000
+}
+
+fn crc16_xmodem(data: &[u8]) -> u16 {
+    data.iter().fold(0, |mut crc, &byte| {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// State of the account which is enough to run its get-methods
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountState {
+    pub address: MsgAddress,
+    /// Base64-encoded BoC, empty for uninitialized accounts
+    pub code: String,
+    /// Base64-encoded BoC
+    pub data: String,
+    /// nanoTONs
+    pub balance: u64,
+    pub last_transaction_lt: u64,
+}
+
+impl AccountState {
+    pub fn is_initialized(&self) -> bool {
+        !self.code.is_empty()
+    }
+}
+
+#[async_trait]
+#[autoimpl(for<T: trait + ?Sized> &T, Arc<T>)]
+pub trait AccountStateI {
+    async fn account_state(&self, address: MsgAddress) -> anyhow::Result<AccountState>;
+}
+
+#[async_trait]
+impl AccountStateI for TonClient {
+    async fn account_state(&self, address: MsgAddress) -> anyhow::Result<AccountState> {
+        let state = self.raw_get_account_state(&address.to_string()).await?;
+        Ok(AccountState {
+            address,
+            code: state.code,
+            data: state.data,
+            balance: state.balance as u64,
+            last_transaction_lt: state.last_transaction_id.map_or(0, |tx_id| tx_id.lt as u64),
+        })
+    }
+}
+
+/// Snapshot of the account, which never changes
+#[async_trait]
+impl AccountStateI for AccountState {
+    async fn account_state(&self, address: MsgAddress) -> anyhow::Result<AccountState> {
+        if address != self.address {
+            return Err(anyhow!("no snapshot of {address}"));
+        }
+        Ok(self.clone())
+    }
+}
+
+/// Result of get-method run by [`TvmEmulatorI`]
+#[derive(Debug, Clone)]
+pub struct EmulatedRun {
+    pub exit_code: i32,
+    pub gas_used: u64,
+    pub stack: VmStack,
+}
+
+/// Runs get-methods locally against given account state
+pub trait TvmEmulatorI {
+    fn run_get_method(
+        &self,
+        state: &AccountState,
+        method_id: u32,
+        stack: VmStack,
+    ) -> anyhow::Result<EmulatedRun>;
+}
+
+/// [`TonContractI`] which runs get-methods in local TVM emulator against
+/// cached state of the account.
+///
+/// The state is fetched on the first call and then refreshed only when
+/// the last transaction of the account changes.
+pub struct EmulatedContract<S, E> {
+    address: MsgAddress,
+    source: S,
+    emulator: E,
+    state: Mutex<Option<Arc<AccountState>>>,
+}
+
+impl<S, E> EmulatedContract<S, E>
+where
+    S: AccountStateI + Sync,
+{
+    pub fn new(source: S, emulator: E, address: MsgAddress) -> Self {
+        Self {
+            address,
+            source,
+            emulator,
+            state: Mutex::new(None),
+        }
+    }
+
+    pub fn address(&self) -> MsgAddress {
+        self.address
+    }
+
+    /// Logical time of the last transaction of the cached state
+    pub fn last_transaction_lt(&self) -> Option<u64> {
+        self.cached().map(|state| state.last_transaction_lt)
+    }
+
+    /// Returns cached state of the account, fetching it if missing
+    pub async fn state(&self) -> anyhow::Result<Arc<AccountState>> {
+        if let Some(state) = self.cached() {
+            return Ok(state);
+        }
+        self.fetch().await
+    }
+
+    /// Fetches the state of the account, returns whether it has changed
+    /// since the cached one
+    pub async fn refresh(&self) -> anyhow::Result<bool> {
+        let last_lt = self.last_transaction_lt();
+        let state = self.fetch().await?;
+        Ok(last_lt != Some(state.last_transaction_lt))
+    }
+
+    /// Refreshes the state only if the cached one is older than given
+    /// last transaction of the account, which is usually known from
+    /// transactions or blocks, so that no request is needed otherwise
+    pub async fn refresh_if_behind(&self, last_transaction_lt: u64) -> anyhow::Result<bool> {
+        if self
+            .last_transaction_lt()
+            .is_some_and(|lt| lt >= last_transaction_lt)
+        {
+            return Ok(false);
+        }
+        self.refresh().await
+    }
+
+    fn cached(&self) -> Option<Arc<AccountState>> {
+        self.state.lock().unwrap().clone()
+    }
+
+    async fn fetch(&self) -> anyhow::Result<Arc<AccountState>> {
+        let state = Arc::new(self.source.account_state(self.address).await?);
+        let mut cached = self.state.lock().unwrap();
+        // keep the newer one if fetched concurrently
+        if let Some(cached) = cached
+            .as_ref()
+            .filter(|cached| cached.last_transaction_lt > state.last_transaction_lt)
+        {
+            return Ok(cached.clone());
+        }
+        *cached = Some(state.clone());
+        Ok(state)
+    }
+}
+
+#[async_trait]
+impl<S, E> TonContractI for EmulatedContract<S, E>
+where
+    S: AccountStateI + Send + Sync,
+    E: TvmEmulatorI + Send + Sync,
+{
+    async fn run_get_method(
+        &self,
+        method: &str,
+        stack: Vec<TvmBoxedStackEntry>,
+    ) -> anyhow::Result<SmcRunResult> {
+        let state = self.state().await?;
+        if !state.is_initialized() {
+            // the same as reported by liteservers
+            return Ok(SmcRunResult {
+                gas_used: 0,
+                stack: Vec::new(),
+                exit_code: ExitCode::Uninitialized.code(),
+            });
+        }
+        let run = self.emulator.run_get_method(
+            &state,
+            method_id(method),
+            stack.as_slice().try_into()?,
+        )?;
+        Ok(SmcRunResult {
+            gas_used: run.gas_used as _,
+            stack: run.stack.try_into()?,
+            exit_code: run.exit_code,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::adapters::TvmBoxedStackEntryExt;
+
+    use super::*;
+
+    #[test]
+    fn method_ids() {
+        assert_eq!(method_id("seqno"), 85143);
+        assert_eq!(method_id("get_wallet_address"), 103289);
+    }
+
+    /// Counts fetches of the state, which changes on each [`Self::transact`]
+    struct FakeSource {
+        state: Mutex<AccountState>,
+        fetches: AtomicUsize,
+    }
+
+    impl FakeSource {
+        fn new(code: &str) -> Self {
+            Self {
+                state: Mutex::new(AccountState {
+                    address: MsgAddress::NULL,
+                    code: code.to_string(),
+                    data: String::new(),
+                    balance: 0,
+                    last_transaction_lt: 1,
+                }),
+                fetches: AtomicUsize::new(0),
+            }
+        }
+
+        fn transact(&self) -> u64 {
+            let mut state = self.state.lock().unwrap();
+            state.last_transaction_lt += 1;
+            state.balance += 1;
+            state.last_transaction_lt
+        }
+
+        fn fetches(&self) -> usize {
+            self.fetches.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl AccountStateI for FakeSource {
+        async fn account_state(&self, _address: MsgAddress) -> anyhow::Result<AccountState> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            Ok(self.state.lock().unwrap().clone())
+        }
+    }
+
+    /// Pushes account balance and the method ID onto the stack
+    struct FakeEmulator;
+
+    impl TvmEmulatorI for FakeEmulator {
+        fn run_get_method(
+            &self,
+            state: &AccountState,
+            method_id: u32,
+            mut stack: VmStack,
+        ) -> anyhow::Result<EmulatedRun> {
+            stack.0.push(VmStackValue::Int(state.balance.into()));
+            stack.0.push(VmStackValue::Int(method_id.into()));
+            Ok(EmulatedRun {
+                exit_code: 0,
+                gas_used: 100,
+                stack,
+            })
+        }
+    }
+
+    async fn balance<S: AccountStateI + Send + Sync>(
+        contract: &EmulatedContract<S, FakeEmulator>,
+    ) -> u64 {
+        let stack = contract
+            .get("seqno", vec![TvmBoxedStackEntry::from_number(5)])
+            .await
+            .unwrap()
+            .unwrap();
+        let [arg, balance, method_id] = stack.as_slice() else {
+            panic!("invalid stack: {stack:?}");
+        };
+        assert_eq!(arg.into_number::<u32>().unwrap(), 5);
+        assert_eq!(method_id.into_number::<u32>().unwrap(), 85143);
+        balance.into_number().unwrap()
+    }
+
+    #[tokio::test]
+    async fn refreshes_only_on_new_transactions() {
+        let source = FakeSource::new("code");
+        let contract = EmulatedContract::new(&source, FakeEmulator, MsgAddress::NULL);
+
+        assert_eq!(balance(&contract).await, 0);
+        assert_eq!(balance(&contract).await, 0);
+        assert_eq!(source.fetches(), 1);
+
+        assert!(!contract.refresh_if_behind(1).await.unwrap());
+        assert_eq!(source.fetches(), 1);
+
+        let lt = source.transact();
+        // stale until refreshed
+        assert_eq!(balance(&contract).await, 0);
+        assert!(contract.refresh_if_behind(lt).await.unwrap());
+        assert_eq!(source.fetches(), 2);
+        assert_eq!(balance(&contract).await, 1);
+
+        assert!(!contract.refresh().await.unwrap());
+        assert_eq!(contract.last_transaction_lt(), Some(lt));
+    }
+
+    #[tokio::test]
+    async fn runs_against_snapshot() {
+        let snapshot = FakeSource::new("code").state.into_inner().unwrap();
+        let contract = EmulatedContract::new(snapshot, FakeEmulator, MsgAddress::NULL);
+        assert_eq!(balance(&contract).await, 0);
+    }
+
+    #[tokio::test]
+    async fn uninitialized_account() {
+        let source = FakeSource::new("");
+        let contract = EmulatedContract::new(&source, FakeEmulator, MsgAddress::NULL);
+        let err = contract
+            .get("seqno", Vec::new())
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.exit_code, ExitCode::Uninitialized);
+    }
+}
@@ -0,0 +1,72 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Deserialize;
+use tlb::{unpack_bytes, CellSerializeExt};
+use tlb_ton::BoC;
+use tonlibjson_sys::TvmEmulator;
+
+use super::{AccountState, EmulatedRun, TvmEmulatorI, VmStack};
+
+/// Get-methods do not need randomness, so that seed is fixed
+const RAND_SEED: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// [`TvmEmulatorI`] backed by emulator from tonlibjson
+#[derive(Debug, Clone, Default)]
+pub struct NativeTvm {
+    /// Base64-encoded BoC of blockchain config, which is needed only by
+    /// get-methods reading config params
+    config: String,
+}
+
+impl NativeTvm {
+    pub fn with_config(mut self, config: impl Into<String>) -> Self {
+        self.config = config.into();
+        self
+    }
+}
+
+#[derive(Deserialize)]
+struct RunGetMethodResponse {
+    success: bool,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    vm_exit_code: i32,
+    #[serde(default)]
+    gas_used: String,
+    #[serde(default)]
+    stack: String,
+}
+
+impl TvmEmulatorI for NativeTvm {
+    fn run_get_method(
+        &self,
+        state: &AccountState,
+        method_id: u32,
+        stack: VmStack,
+    ) -> anyhow::Result<EmulatedRun> {
+        let emulator = TvmEmulator::new(&state.code, &state.data, 0)?;
+        emulator.set_c7(
+            &state.address.to_string(),
+            SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as u32,
+            state.balance,
+            RAND_SEED,
+            &self.config,
+        )?;
+        let stack = STANDARD.encode(BoC::from_root(stack.to_cell()?).pack(true)?);
+        let response: RunGetMethodResponse =
+            serde_json::from_str(&emulator.run_get_method(method_id as i32, &stack)?)?;
+        if !response.success {
+            return Err(anyhow!("emulator: {}", response.error.unwrap_or_default()));
+        }
+
+        let stack: BoC = unpack_bytes(STANDARD.decode(response.stack).context("base64")?)?;
+        Ok(EmulatedRun {
+            exit_code: response.vm_exit_code,
+            gas_used: response.gas_used.parse().context("gas used")?,
+            stack: stack.single_root().context("single root")?.parse_fully()?,
+        })
+    }
+}
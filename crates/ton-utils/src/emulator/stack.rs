@@ -0,0 +1,280 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use num::{bigint::Sign, BigInt, BigUint, One, ToPrimitive};
+use tlb::{
+    BitReaderExt, BitWriterExt, Cell, CellBuilder, CellBuilderError, CellDeserialize, CellParser,
+    CellParserError, CellSerialize, Error, NBits, Ref,
+};
+use tlb_ton::BoC;
+use tonlibjson_client::block::{TvmBoxedStackEntry, TvmCell, TvmStackEntryCell};
+
+use crate::adapters::TvmBoxedStackEntryExt;
+
+/// vm_stack#_ depth:(## 24) stack:(VmStackList depth) = VmStack;
+/// vm_stk_cons#_ {n:#} rest:^(VmStackList n) tos:VmStackValue = VmStackList (n + 1);
+/// vm_stk_nil#_ = VmStackList 0;
+///
+/// Values are ordered from the bottom to the top of the stack
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VmStack(pub Vec<VmStackValue>);
+
+impl CellSerialize for VmStack {
+    fn store(&self, builder: &mut CellBuilder) -> Result<(), CellBuilderError> {
+        builder
+            .pack_as::<_, NBits<24>>(self.0.len() as u32)?
+            .store(&VmStackList(&self.0))?;
+        Ok(())
+    }
+}
+
+impl<'de> CellDeserialize<'de> for VmStack {
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        let depth: u32 = parser.unpack_as::<_, NBits<24>>()?;
+        let mut values = Vec::with_capacity(depth as usize);
+        if depth > 0 {
+            let VmStackCons { mut rest, tos } = parser.parse()?;
+            values.push(tos);
+            for _ in 1..depth {
+                let cons: VmStackCons = rest.parse_fully().map_err(Error::custom)?;
+                values.push(cons.tos);
+                rest = cons.rest;
+            }
+        }
+        // collected from the top
+        values.reverse();
+        Ok(Self(values))
+    }
+}
+
+struct VmStackList<'a>(&'a [VmStackValue]);
+
+impl<'a> CellSerialize for VmStackList<'a> {
+    fn store(&self, builder: &mut CellBuilder) -> Result<(), CellBuilderError> {
+        let Some((tos, rest)) = self.0.split_last() else {
+            return Ok(());
+        };
+        builder.store_as::<_, Ref>(&VmStackList(rest))?.store(tos)?;
+        Ok(())
+    }
+}
+
+struct VmStackCons {
+    rest: Cell,
+    tos: VmStackValue,
+}
+
+impl<'de> CellDeserialize<'de> for VmStackCons {
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        Ok(Self {
+            rest: parser.parse_as::<_, Ref>()?,
+            tos: parser.parse()?,
+        })
+    }
+}
+
+/// vm_stk_null#00 = VmStackValue;
+/// vm_stk_tinyint#01 value:int64 = VmStackValue;
+/// vm_stk_int#0201_ value:int257 = VmStackValue;
+/// vm_stk_nan#02ff = VmStackValue;
+/// vm_stk_cell#03 cell:^Cell = VmStackValue;
+/// vm_stk_slice#04 _:VmCellSlice = VmStackValue;
+///
+/// Tuples and continuations are not supported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmStackValue {
+    Null,
+    Int(BigInt),
+    NaN,
+    Cell(Arc<Cell>),
+    /// Slice of the whole cell
+    Slice(Arc<Cell>),
+}
+
+impl VmStackValue {
+    const NULL_TAG: u8 = 0x00;
+    const TINYINT_TAG: u8 = 0x01;
+    /// Common first byte of `int` and `nan`
+    const INT_TAG: u8 = 0x02;
+    const CELL_TAG: u8 = 0x03;
+    const SLICE_TAG: u8 = 0x04;
+}
+
+impl CellSerialize for VmStackValue {
+    fn store(&self, builder: &mut CellBuilder) -> Result<(), CellBuilderError> {
+        match self {
+            Self::Null => {
+                builder.pack(Self::NULL_TAG)?;
+            }
+            Self::Int(value) => match value.to_i64() {
+                Some(value) => {
+                    builder.pack(Self::TINYINT_TAG)?.pack(value)?;
+                }
+                None => {
+                    builder
+                        .pack(Self::INT_TAG)?
+                        .pack_as::<_, NBits<7>>(0u8)?
+                        .pack_as::<_, NBits<257>>(int257_to_bits(value))?;
+                }
+            },
+            Self::NaN => {
+                builder.pack(Self::INT_TAG)?.pack(0xffu8)?;
+            }
+            Self::Cell(cell) => {
+                builder
+                    .pack(Self::CELL_TAG)?
+                    .store_as::<_, Ref>(cell.as_ref())?;
+            }
+            Self::Slice(cell) => {
+                // vm_stk_slice is a cell along with bounds of the slice
+                // _ cell:^Cell st_bits:(## 10) end_bits:(## 10) { st_bits <= end_bits }
+                //   st_ref:(#<= 4) end_ref:(#<= 4) { st_ref <= end_ref } = VmCellSlice;
+                builder
+                    .pack(Self::SLICE_TAG)?
+                    .store_as::<_, Ref>(cell.as_ref())?
+                    .pack_as::<_, NBits<10>>(0u16)?
+                    .pack_as::<_, NBits<10>>(cell.data.len() as u16)?
+                    .pack_as::<_, NBits<3>>(0u8)?
+                    .pack_as::<_, NBits<3>>(cell.references.len() as u8)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Two's complement of `int257`, since [`NBits`] of [`BigInt`] does not
+/// sign-extend negative values
+fn int257_to_bits(value: &BigInt) -> BigUint {
+    let value = match value.sign() {
+        Sign::Minus => value + (BigInt::one() << 257),
+        _ => value.clone(),
+    };
+    value.into_parts().1
+}
+
+fn int257_from_bits(bits: BigUint) -> BigInt {
+    let value = BigInt::from(bits);
+    if value.bit(256) {
+        value - (BigInt::one() << 257)
+    } else {
+        value
+    }
+}
+
+impl<'de> CellDeserialize<'de> for VmStackValue {
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        Ok(match parser.unpack::<u8>()? {
+            Self::NULL_TAG => Self::Null,
+            Self::TINYINT_TAG => Self::Int(parser.unpack::<i64>()?.into()),
+            Self::INT_TAG => match parser.unpack_as::<u8, NBits<7>>()? {
+                0 => Self::Int(int257_from_bits(parser.unpack_as::<_, NBits<257>>()?)),
+                0x7f if parser.unpack::<bool>()? => Self::NaN,
+                _ => return Err(Error::custom("invalid int tag")),
+            },
+            Self::CELL_TAG => Self::Cell(Arc::new(parser.parse_as::<_, Ref>()?)),
+            Self::SLICE_TAG => {
+                let cell: Cell = parser.parse_as::<_, Ref>()?;
+                let st_bits = parser.unpack_as::<usize, NBits<10>>()?;
+                let end_bits = parser.unpack_as::<usize, NBits<10>>()?;
+                let st_ref = parser.unpack_as::<usize, NBits<3>>()?;
+                let end_ref = parser.unpack_as::<usize, NBits<3>>()?;
+                if st_bits > end_bits
+                    || end_bits > cell.data.len()
+                    || st_ref > end_ref
+                    || end_ref > cell.references.len()
+                {
+                    return Err(Error::custom("slice out of cell bounds"));
+                }
+                Self::Slice(Arc::new(Cell {
+                    data: cell.data[st_bits..end_bits].to_bitvec(),
+                    references: cell.references[st_ref..end_ref].to_vec(),
+                }))
+            }
+            tag => return Err(Error::custom(format!("unsupported tag: {tag:#04x}"))),
+        })
+    }
+}
+
+impl TryFrom<&TvmBoxedStackEntry> for VmStackValue {
+    type Error = anyhow::Error;
+
+    fn try_from(entry: &TvmBoxedStackEntry) -> Result<Self, Self::Error> {
+        Ok(match entry {
+            TvmBoxedStackEntry::TvmStackEntryNumber(_) => Self::Int(entry.into_number()?),
+            TvmBoxedStackEntry::TvmStackEntryCell(_) => Self::Cell(entry.into_cell()?),
+            TvmBoxedStackEntry::TvmStackEntrySlice(_) => Self::Slice(entry.into_cell()?),
+            _ => return Err(anyhow!("unsupported stack entry")),
+        })
+    }
+}
+
+impl TryFrom<VmStackValue> for TvmBoxedStackEntry {
+    type Error = anyhow::Error;
+
+    fn try_from(value: VmStackValue) -> Result<Self, Self::Error> {
+        Ok(match value {
+            VmStackValue::Int(value) => Self::from_number(value),
+            VmStackValue::Cell(cell) => Self::TvmStackEntryCell(TvmStackEntryCell {
+                cell: TvmCell {
+                    bytes: STANDARD.encode(BoC::from_root(cell).pack(true)?),
+                },
+            }),
+            VmStackValue::Slice(cell) => Self::from_cell(cell)?,
+            VmStackValue::Null | VmStackValue::NaN => {
+                return Err(anyhow!("unsupported stack value: {value:?}"))
+            }
+        })
+    }
+}
+
+impl TryFrom<&[TvmBoxedStackEntry]> for VmStack {
+    type Error = anyhow::Error;
+
+    fn try_from(entries: &[TvmBoxedStackEntry]) -> Result<Self, Self::Error> {
+        entries
+            .iter()
+            .map(TryInto::try_into)
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl TryFrom<VmStack> for Vec<TvmBoxedStackEntry> {
+    type Error = anyhow::Error;
+
+    fn try_from(stack: VmStack) -> Result<Self, Self::Error> {
+        stack.0.into_iter().map(TryInto::try_into).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tlb::CellSerializeExt;
+
+    use super::*;
+
+    #[test]
+    fn stack_roundtrip() {
+        let cell = Arc::new(().to_cell().unwrap());
+        let stack = VmStack(vec![
+            VmStackValue::Int(7.into()),
+            VmStackValue::Int(-BigInt::from(u64::MAX) * 3),
+            VmStackValue::Null,
+            VmStackValue::NaN,
+            VmStackValue::Cell(cell),
+            VmStackValue::Slice(Arc::new(VmStack::default().to_cell().unwrap())),
+        ]);
+
+        let parsed: VmStack = stack.to_cell().unwrap().parse_fully().unwrap();
+        assert_eq!(parsed, stack);
+    }
+
+    #[test]
+    fn empty_stack() {
+        let cell = VmStack::default().to_cell().unwrap();
+        assert_eq!(cell.data.len(), 24);
+        assert!(cell.references.is_empty());
+        assert_eq!(cell.parse_fully::<VmStack>().unwrap(), VmStack::default());
+    }
+}
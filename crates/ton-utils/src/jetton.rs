@@ -0,0 +1,382 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use num::BigUint;
+use tlb::{
+    BitReaderExt, BitWriterExt, Cell, CellBuilder, CellBuilderError, CellDeserialize, CellParser,
+    CellParserError, CellSerialize, ConstU32, Data, Ref,
+};
+use tlb_ton::{Coins, MsgAddress};
+
+use crate::{adapters::TvmBoxedStackEntryExt, contract::TonContractI};
+
+/// [TEP-74](https://github.com/ton-blockchain/TEPs/blob/master/text/0074-jettons-standard.md)
+/// jetton master get-methods
+#[async_trait]
+pub trait JettonMasterI: TonContractI {
+    async fn get_wallet_address(&self, owner: MsgAddress) -> anyhow::Result<MsgAddress> {
+        let [address] = self
+            .get(
+                "get_wallet_address",
+                [TvmBoxedStackEntryExt::store_cell_as::<_, Data>(owner)?].into(),
+            )
+            .await??
+            .try_into()
+            .map_err(|stack| anyhow!("invalid output stack: {stack:?}"))?;
+        address.parse_cell_fully_as::<_, Data>()
+    }
+}
+
+impl<C> JettonMasterI for C where C: TonContractI {}
+
+pub struct JettonWalletData {
+    pub balance: BigUint,
+    pub owner: MsgAddress,
+    pub master: MsgAddress,
+    pub wallet_code: Arc<Cell>,
+}
+
+/// [TEP-74](https://github.com/ton-blockchain/TEPs/blob/master/text/0074-jettons-standard.md)
+/// jetton wallet get-methods
+#[async_trait]
+pub trait JettonWalletI: TonContractI {
+    async fn get_wallet_data(&self) -> anyhow::Result<JettonWalletData> {
+        let [balance, owner, master, wallet_code] = self
+            .get("get_wallet_data", [].into())
+            .await??
+            .try_into()
+            .map_err(|stack| anyhow!("invalid output stack: {stack:?}"))?;
+
+        Ok(JettonWalletData {
+            balance: balance.into_number()?,
+            owner: owner.parse_cell_fully_as::<_, Data>()?,
+            master: master.parse_cell_fully_as::<_, Data>()?,
+            wallet_code: wallet_code.into_cell()?,
+        })
+    }
+}
+
+impl<C> JettonWalletI for C where C: TonContractI {}
+
+const JETTON_TRANSFER_TAG: u32 = 0x0f8a7ea5;
+
+/// transfer#0f8a7ea5 query_id:uint64 amount:(VarUInteger 16) destination:MsgAddress
+/// response_destination:MsgAddress custom_payload:(Maybe ^Cell)
+/// forward_ton_amount:(VarUInteger 16) forward_payload:(Either Cell ^Cell)
+/// = InternalMsgBody;
+pub struct JettonTransfer<C, P> {
+    pub query_id: u64,
+    /// Amount of jettons to transfer
+    pub amount: BigUint,
+    /// Owner of the destination jetton wallet
+    pub dst: MsgAddress,
+    /// Address to return excess TONs to
+    pub response_dst: MsgAddress,
+    pub custom_payload: Option<C>,
+    /// Amount of TONs to attach to the transfer notification
+    /// sent to `dst`
+    pub forward_ton_amount: BigUint,
+    /// Always stored as a reference
+    pub forward_payload: P,
+}
+
+impl<C, P> CellSerialize for JettonTransfer<C, P>
+where
+    C: CellSerialize,
+    P: CellSerialize,
+{
+    fn store(&self, builder: &mut CellBuilder) -> Result<(), CellBuilderError> {
+        builder
+            .pack(JETTON_TRANSFER_TAG)?
+            .pack(self.query_id)?
+            .pack_as::<_, &Coins>(&self.amount)?
+            .pack(self.dst)?
+            .pack(self.response_dst)?
+            .store_as::<_, Option<Ref>>(self.custom_payload.as_ref())?
+            .pack_as::<_, &Coins>(&self.forward_ton_amount)?
+            // right$1
+            .pack(true)?
+            .store_as::<_, Ref>(&self.forward_payload)?;
+        Ok(())
+    }
+}
+
+impl<'de, C, P> CellDeserialize<'de> for JettonTransfer<C, P>
+where
+    C: CellDeserialize<'de>,
+    P: CellDeserialize<'de>,
+{
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        parser.unpack::<ConstU32<JETTON_TRANSFER_TAG>>()?;
+        Ok(Self {
+            query_id: parser.unpack()?,
+            amount: parser.unpack_as::<_, Coins>()?,
+            dst: parser.unpack()?,
+            response_dst: parser.unpack()?,
+            custom_payload: parser.parse_as::<_, Option<Ref>>()?,
+            forward_ton_amount: parser.unpack_as::<_, Coins>()?,
+            forward_payload: if parser.unpack()? {
+                parser.parse_as::<_, Ref>()?
+            } else {
+                parser.parse()?
+            },
+        })
+    }
+}
+
+const JETTON_TRANSFER_NOTIFICATION_TAG: u32 = 0x7362d09c;
+
+/// transfer_notification#7362d09c query_id:uint64 amount:(VarUInteger 16)
+/// sender:MsgAddress forward_payload:(Either Cell ^Cell)
+/// = InternalMsgBody;
+pub struct JettonTransferNotification<P> {
+    pub query_id: u64,
+    /// Amount of received jettons
+    pub amount: BigUint,
+    /// Owner of the sender jetton wallet
+    pub sender: MsgAddress,
+    /// Always stored as a reference
+    pub forward_payload: P,
+}
+
+impl<P> CellSerialize for JettonTransferNotification<P>
+where
+    P: CellSerialize,
+{
+    fn store(&self, builder: &mut CellBuilder) -> Result<(), CellBuilderError> {
+        builder
+            .pack(JETTON_TRANSFER_NOTIFICATION_TAG)?
+            .pack(self.query_id)?
+            .pack_as::<_, &Coins>(&self.amount)?
+            .pack(self.sender)?
+            // right$1
+            .pack(true)?
+            .store_as::<_, Ref>(&self.forward_payload)?;
+        Ok(())
+    }
+}
+
+impl<'de, P> CellDeserialize<'de> for JettonTransferNotification<P>
+where
+    P: CellDeserialize<'de>,
+{
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        parser.unpack::<ConstU32<JETTON_TRANSFER_NOTIFICATION_TAG>>()?;
+        Ok(Self {
+            query_id: parser.unpack()?,
+            amount: parser.unpack_as::<_, Coins>()?,
+            sender: parser.unpack()?,
+            forward_payload: if parser.unpack()? {
+                parser.parse_as::<_, Ref>()?
+            } else {
+                parser.parse()?
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tlb::CellSerializeExt;
+    use tonlibjson_client::block::{SmcRunResult, TvmBoxedStackEntry};
+
+    use super::*;
+
+    const OWNER: MsgAddress = MsgAddress {
+        workchain_id: 0,
+        address: [1; 32],
+    };
+    const MASTER: MsgAddress = MsgAddress {
+        workchain_id: 0,
+        address: [2; 32],
+    };
+    const JETTON_WALLET: MsgAddress = MsgAddress {
+        workchain_id: 0,
+        address: [3; 32],
+    };
+
+    /// Contract which answers a single get-method with a fixed stack
+    struct MockContract {
+        method: &'static str,
+        stack: Vec<TvmBoxedStackEntry>,
+    }
+
+    #[async_trait]
+    impl TonContractI for MockContract {
+        async fn run_get_method(
+            &self,
+            method: &str,
+            _stack: Vec<TvmBoxedStackEntry>,
+        ) -> anyhow::Result<SmcRunResult> {
+            if method != self.method {
+                return Err(anyhow!("unknown get-method: {method}"));
+            }
+            Ok(SmcRunResult {
+                gas_used: 0,
+                stack: self.stack.clone(),
+                exit_code: 0,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn get_wallet_address() {
+        let master = MockContract {
+            method: "get_wallet_address",
+            stack: [TvmBoxedStackEntryExt::store_cell_as::<_, Data>(JETTON_WALLET).unwrap()].into(),
+        };
+        assert_eq!(
+            master.get_wallet_address(OWNER).await.unwrap(),
+            JETTON_WALLET
+        );
+    }
+
+    #[tokio::test]
+    async fn get_wallet_data() {
+        let wallet = MockContract {
+            method: "get_wallet_data",
+            stack: [
+                TvmBoxedStackEntryExt::from_number(1_000_000u64),
+                TvmBoxedStackEntryExt::store_cell_as::<_, Data>(OWNER).unwrap(),
+                TvmBoxedStackEntryExt::store_cell_as::<_, Data>(MASTER).unwrap(),
+                TvmBoxedStackEntryExt::store_cell(()).unwrap(),
+            ]
+            .into(),
+        };
+        let data = wallet.get_wallet_data().await.unwrap();
+        assert_eq!(data.balance, 1_000_000u64.into());
+        assert_eq!(data.owner, OWNER);
+        assert_eq!(data.master, MASTER);
+    }
+
+    fn payload() -> Cell {
+        let mut builder = Cell::builder();
+        builder.pack(0xdeadbeef_u32).unwrap();
+        builder.into_cell()
+    }
+
+    fn transfer_header(builder: &mut CellBuilder) -> Result<&mut CellBuilder, CellBuilderError> {
+        builder
+            .pack(0x0f8a7ea5_u32)?
+            .pack(7_u64)?
+            .pack_as::<_, Coins>(BigUint::from(1_000_000_u32))?
+            .pack(OWNER)?
+            .pack(JETTON_WALLET)
+    }
+
+    #[test]
+    fn transfer_stores_forward_payload_as_ref() {
+        let transfer = JettonTransfer::<Cell, Cell> {
+            query_id: 7,
+            amount: 1_000_000_u32.into(),
+            dst: OWNER,
+            response_dst: JETTON_WALLET,
+            custom_payload: None,
+            forward_ton_amount: 50_000_000_u32.into(),
+            forward_payload: payload(),
+        };
+
+        let mut expected = Cell::builder();
+        transfer_header(&mut expected)
+            .unwrap()
+            // custom_payload: nothing$0
+            .pack(false)
+            .unwrap()
+            .pack_as::<_, Coins>(BigUint::from(50_000_000_u32))
+            .unwrap()
+            // forward_payload: right$1 ^Cell
+            .pack(true)
+            .unwrap()
+            .store_as::<_, Ref>(payload())
+            .unwrap();
+        let expected = expected.into_cell();
+        assert_eq!(transfer.to_cell().unwrap(), expected);
+
+        let parsed: JettonTransfer<Cell, Cell> = expected.parse_fully().unwrap();
+        assert_eq!(parsed.query_id, 7);
+        assert_eq!(parsed.amount, transfer.amount);
+        assert_eq!(parsed.dst, OWNER);
+        assert_eq!(parsed.response_dst, JETTON_WALLET);
+        assert!(parsed.custom_payload.is_none());
+        assert_eq!(parsed.forward_ton_amount, transfer.forward_ton_amount);
+        assert_eq!(parsed.forward_payload, payload());
+    }
+
+    #[test]
+    fn transfer_parses_inline_forward_payload() {
+        let mut cell = Cell::builder();
+        transfer_header(&mut cell)
+            .unwrap()
+            // custom_payload: just$1 ^Cell
+            .pack(true)
+            .unwrap()
+            .store_as::<_, Ref>(payload())
+            .unwrap()
+            .pack_as::<_, Coins>(BigUint::from(1_u8))
+            .unwrap()
+            // forward_payload: left$0 Cell
+            .pack(false)
+            .unwrap()
+            .store(payload())
+            .unwrap();
+
+        let parsed: JettonTransfer<Cell, Cell> = cell.into_cell().parse_fully().unwrap();
+        assert_eq!(parsed.custom_payload, Some(payload()));
+        assert_eq!(parsed.forward_ton_amount, 1_u8.into());
+        assert_eq!(parsed.forward_payload, payload());
+    }
+
+    fn notification_header(
+        builder: &mut CellBuilder,
+    ) -> Result<&mut CellBuilder, CellBuilderError> {
+        builder
+            .pack(0x7362d09c_u32)?
+            .pack(7_u64)?
+            .pack_as::<_, Coins>(BigUint::from(1_000_000_u32))?
+            .pack(OWNER)
+    }
+
+    #[test]
+    fn transfer_notification_stores_forward_payload_as_ref() {
+        let notification = JettonTransferNotification {
+            query_id: 7,
+            amount: 1_000_000_u32.into(),
+            sender: OWNER,
+            forward_payload: payload(),
+        };
+
+        let mut expected = Cell::builder();
+        notification_header(&mut expected)
+            .unwrap()
+            // forward_payload: right$1 ^Cell
+            .pack(true)
+            .unwrap()
+            .store_as::<_, Ref>(payload())
+            .unwrap();
+        let expected = expected.into_cell();
+        assert_eq!(notification.to_cell().unwrap(), expected);
+
+        let parsed: JettonTransferNotification<Cell> = expected.parse_fully().unwrap();
+        assert_eq!(parsed.query_id, 7);
+        assert_eq!(parsed.amount, notification.amount);
+        assert_eq!(parsed.sender, OWNER);
+        assert_eq!(parsed.forward_payload, payload());
+    }
+
+    #[test]
+    fn transfer_notification_parses_inline_forward_payload() {
+        let mut cell = Cell::builder();
+        notification_header(&mut cell)
+            .unwrap()
+            // forward_payload: left$0 Cell
+            .pack(false)
+            .unwrap()
+            .store(payload())
+            .unwrap();
+
+        let parsed: JettonTransferNotification<Cell> = cell.into_cell().parse_fully().unwrap();
+        assert_eq!(parsed.amount, 1_000_000_u32.into());
+        assert_eq!(parsed.forward_payload, payload());
+    }
+}
@@ -1,3 +1,7 @@
 pub mod adapters;
+pub mod blocks;
 pub mod contract;
+pub mod emulator;
+pub mod jetton;
+pub mod transactions;
 pub mod wallet;
@@ -0,0 +1,160 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use num::BigUint;
+use tlb::{unpack_bytes, Cell};
+use tlb_ton::{BoC, MsgAddress};
+use tonlibjson_client::block::{
+    AccountAddress, InternalTransactionId, MsgData, MsgDataRaw, RawMessage, RawTransaction,
+};
+
+use crate::contract::TonContract;
+
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    pub lt: u64,
+    /// Unix time
+    pub utime: u64,
+    pub in_msg: Option<TransactionMessage>,
+    pub out_msgs: Vec<TransactionMessage>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TransactionMessage {
+    /// `None` for inbound external messages
+    pub src: Option<MsgAddress>,
+    /// `None` for outbound external messages
+    pub dst: Option<MsgAddress>,
+    /// Attached nanoTONs
+    pub value: BigUint,
+    pub body: Option<Arc<Cell>>,
+}
+
+#[async_trait]
+pub trait AccountTransactionsI {
+    /// Returns logical time of the last transaction of the account,
+    /// or 0 if there were none
+    async fn last_transaction_lt(&self) -> anyhow::Result<u64>;
+
+    /// Returns transactions of the account with logical time greater than
+    /// `after_lt` in the ascending order
+    async fn transactions_after(&self, after_lt: u64) -> anyhow::Result<Vec<Transaction>>;
+}
+
+#[async_trait]
+impl AccountTransactionsI for TonContract {
+    async fn last_transaction_lt(&self) -> anyhow::Result<u64> {
+        Ok(self
+            .last_transaction_id()
+            .await?
+            .map_or(0, |tx_id| tx_id.lt as u64))
+    }
+
+    async fn transactions_after(&self, after_lt: u64) -> anyhow::Result<Vec<Transaction>> {
+        let address = self.address().to_string();
+        let mut from_tx = self.last_transaction_id().await?;
+        let mut txs = Vec::new();
+        'pages: while let Some(tx_id) = from_tx.take() {
+            if tx_id.lt as u64 <= after_lt {
+                break;
+            }
+            let page = self.client().raw_get_transactions(&address, &tx_id).await?;
+            // from the newest to the oldest
+            for tx in page.transactions {
+                if tx.transaction_id.lt as u64 <= after_lt {
+                    break 'pages;
+                }
+                txs.push(tx.try_into()?);
+            }
+            from_tx = page.previous_transaction_id;
+        }
+        txs.reverse();
+        Ok(txs)
+    }
+}
+
+impl TonContract {
+    /// Returns the newest transaction of the account matching `f` among
+    /// those made at or after `since` unix time
+    pub async fn last_transaction_matching(
+        &self,
+        since: u64,
+        f: impl Fn(&Transaction) -> bool + Send,
+    ) -> anyhow::Result<Option<Transaction>> {
+        let address = self.address().to_string();
+        let mut from_tx = self.last_transaction_id().await?;
+        while let Some(tx_id) = from_tx.take() {
+            let page = self.client().raw_get_transactions(&address, &tx_id).await?;
+            // from the newest to the oldest
+            for tx in page.transactions {
+                let tx: Transaction = tx.try_into()?;
+                if tx.utime < since {
+                    return Ok(None);
+                }
+                if f(&tx) {
+                    return Ok(Some(tx));
+                }
+            }
+            from_tx = page.previous_transaction_id;
+        }
+        Ok(None)
+    }
+
+    async fn last_transaction_id(&self) -> anyhow::Result<Option<InternalTransactionId>> {
+        Ok(self
+            .client()
+            .raw_get_account_state(&self.address().to_string())
+            .await?
+            .last_transaction_id)
+    }
+}
+
+impl TryFrom<RawTransaction> for Transaction {
+    type Error = anyhow::Error;
+
+    fn try_from(tx: RawTransaction) -> Result<Self, Self::Error> {
+        Ok(Self {
+            lt: tx.transaction_id.lt as u64,
+            utime: tx.utime as u64,
+            in_msg: tx.in_msg.map(TryInto::try_into).transpose()?,
+            out_msgs: tx
+                .out_msgs
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl TryFrom<RawMessage> for TransactionMessage {
+    type Error = anyhow::Error;
+
+    fn try_from(msg: RawMessage) -> Result<Self, Self::Error> {
+        Ok(Self {
+            src: parse_account_address(msg.source)?,
+            dst: parse_account_address(msg.destination)?,
+            value: (msg.value as u64).into(),
+            body: match msg.msg_data {
+                MsgData::MsgDataRaw(MsgDataRaw { body, .. }) => {
+                    let boc: BoC = unpack_bytes(STANDARD.decode(body).context("base64")?)?;
+                    Some(boc.single_root().context("single root")?.clone())
+                }
+                _ => None,
+            },
+        })
+    }
+}
+
+fn parse_account_address(address: AccountAddress) -> anyhow::Result<Option<MsgAddress>> {
+    address
+        .account_address
+        .filter(|address| !address.is_empty())
+        .map(|address| {
+            address
+                .parse()
+                .map_err(|_| anyhow!("invalid address: {address}"))
+        })
+        .transpose()
+}
@@ -16,3 +16,36 @@ pub trait WalletI: TonContractI {
 }
 
 impl<C> WalletI for C where C: TonContractI {}
+
+#[async_trait]
+pub trait HighloadWalletV3I: TonContractI {
+    /// Returns whether message with given query ID was processed
+    /// within the last timeout
+    async fn processed(&self, query_id: u32, need_clean: bool) -> anyhow::Result<bool> {
+        let [processed] = self
+            .get(
+                "processed?",
+                [
+                    TvmBoxedStackEntryExt::from_number(query_id),
+                    TvmBoxedStackEntryExt::from_number(-(need_clean as i8)),
+                ]
+                .into(),
+            )
+            .await??
+            .try_into()
+            .map_err(|stack| anyhow!("invalid output stack size: {stack:?}"))?;
+        Ok(processed.into_number::<i8>()? != 0)
+    }
+
+    /// Returns lifetime of external messages in seconds
+    async fn get_timeout(&self) -> anyhow::Result<u32> {
+        let [timeout] = self
+            .get("get_timeout", [].into())
+            .await??
+            .try_into()
+            .map_err(|stack| anyhow!("invalid output stack size: {stack:?}"))?;
+        timeout.into_number()
+    }
+}
+
+impl<C> HighloadWalletV3I for C where C: TonContractI {}
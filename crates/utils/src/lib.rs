@@ -1,3 +1,4 @@
+mod limiter;
 mod num;
 
-pub use self::num::*;
+pub use self::{limiter::*, num::*};
@@ -0,0 +1,180 @@
+use core::{future::Future, time::Duration};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{Semaphore, SemaphorePermit},
+    time::{self, Instant},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Max number of requests in flight
+    pub concurrency: usize,
+    /// Max number of requests per second on average
+    pub rate: f64,
+    /// Max number of requests which can be sent at once after being idle
+    pub burst: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 32,
+            rate: 50.0,
+            burst: 50,
+        }
+    }
+}
+
+/// Limits both the number of concurrent requests and their rate, which
+/// is enforced by token bucket
+pub struct RateLimiter {
+    semaphore: Semaphore,
+    bucket: Mutex<TokenBucket>,
+}
+
+impl RateLimiter {
+    pub fn new(cfg: &RateLimitConfig) -> Self {
+        Self {
+            semaphore: Semaphore::new(cfg.concurrency),
+            bucket: Mutex::new(TokenBucket::new(cfg.rate, cfg.burst, Instant::now())),
+        }
+    }
+
+    /// Waits until one more request is allowed, it stays in flight until
+    /// the returned permit is dropped
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        let permit = self
+            .semaphore
+            .acquire()
+            .await
+            .expect("semaphore is never closed");
+        loop {
+            let wait = self.bucket.lock().unwrap().take(Instant::now());
+            let Err(wait) = wait else {
+                return permit;
+            };
+            time::sleep(wait).await;
+        }
+    }
+
+    /// Runs the future once it is allowed to
+    pub async fn run<F>(&self, f: F) -> F::Output
+    where
+        F: Future,
+    {
+        let _permit = self.acquire().await;
+        f.await
+    }
+}
+
+struct TokenBucket {
+    /// Tokens added per second
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, capacity: u32, now: Instant) -> Self {
+        Self {
+            rate,
+            capacity: capacity.max(1).into(),
+            tokens: capacity.max(1).into(),
+            updated_at: now,
+        }
+    }
+
+    /// Takes a token if there is one, otherwise returns how long to wait
+    /// for it
+    fn take(&mut self, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.updated_at);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate).min(self.capacity);
+        self.updated_at = now;
+        if self.tokens < 1.0 {
+            return Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate));
+        }
+        self.tokens -= 1.0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+
+    #[test]
+    fn token_bucket_refills_at_rate() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(10.0, 2, now);
+        assert!(bucket.take(now).is_ok());
+        assert!(bucket.take(now).is_ok());
+        let wait = bucket.take(now).unwrap_err();
+        assert!((wait.as_secs_f64() - 0.1).abs() < 1e-6);
+
+        let later = now + Duration::from_millis(150);
+        assert!(bucket.take(later).is_ok());
+        assert!(bucket.take(later).is_err());
+
+        // does not accumulate more than capacity
+        let much_later = later + Duration::from_secs(10);
+        assert!(bucket.take(much_later).is_ok());
+        assert!(bucket.take(much_later).is_ok());
+        assert!(bucket.take(much_later).is_err());
+    }
+
+    #[tokio::test]
+    async fn limits_concurrency() {
+        let limiter = Arc::new(RateLimiter::new(&RateLimitConfig {
+            concurrency: 2,
+            rate: 1000.0,
+            burst: 1000,
+        }));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+
+        let tasks: Vec<_> = (0..10)
+            .map(|_| {
+                let (limiter, in_flight, max_in_flight) =
+                    (limiter.clone(), in_flight.clone(), max_in_flight.clone());
+                tokio::spawn(async move {
+                    limiter
+                        .run(async {
+                            let n = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                            max_in_flight.fetch_max(n, Ordering::SeqCst);
+                            time::sleep(Duration::from_millis(10)).await;
+                            in_flight.fetch_sub(1, Ordering::SeqCst);
+                        })
+                        .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn limits_rate() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            concurrency: 100,
+            rate: 100.0,
+            burst: 5,
+        });
+        let start = Instant::now();
+        for _ in 0..15 {
+            limiter.run(async {}).await;
+        }
+        // first 5 at once, then 10 more at 100 per second
+        assert!(start.elapsed() >= Duration::from_millis(99));
+    }
+}
@@ -1,22 +1,25 @@
-use std::{borrow::Cow, fmt::Display, marker::PhantomData, ops::Div};
+use std::{borrow::Cow, fmt::Display, marker::PhantomData};
 
-use num::{pow::Pow, rational::Ratio, Integer, Num};
+use num::{pow::Pow, rational::Ratio, FromPrimitive, Integer, Num};
 use serde::{de, Deserialize, Deserializer};
 use serde_with::{DeserializeAs, Same};
 
 pub struct Percent<T = Same>(PhantomData<T>);
 
-impl<'de, T, As> DeserializeAs<'de, T> for Percent<As>
+impl<'de, T, As> DeserializeAs<'de, Ratio<T>> for Percent<As>
 where
-    As: DeserializeAs<'de, T>,
-    T: From<u8> + Div<Output = T>,
+    As: DeserializeAs<'de, Ratio<T>>,
+    T: Integer + Clone + FromPrimitive,
 {
-    fn deserialize_as<D>(deserializer: D) -> Result<T, D::Error>
+    fn deserialize_as<D>(deserializer: D) -> Result<Ratio<T>, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let v: T = As::deserialize_as(deserializer)?;
-        Ok(v / T::from(100u8))
+        let v = As::deserialize_as(deserializer)?;
+        // `FromPrimitive` is not implemented for `Ratio<T>` with unsigned `T`,
+        // so divide it by the integer instead
+        let hundred = T::from_u8(100).ok_or_else(|| de::Error::custom("100 out of range"))?;
+        Ok(v / hundred)
     }
 }
 
@@ -48,6 +51,32 @@ where
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
+    use serde::de::value::{BorrowedStrDeserializer, Error};
+
+    use super::*;
+
+    fn percent(s: &str) -> Ratio<BigUint> {
+        Percent::<DecimalFloatStrAsRatio>::deserialize_as(BorrowedStrDeserializer::<Error>::new(s))
+            .unwrap()
+    }
+
+    #[test]
+    fn percent_as_ratio() {
+        for (s, numer, denom) in [
+            ("0", 0u32, 1u32),
+            ("1", 1, 100),
+            ("0.5", 1, 200),
+            ("12.25", 49, 400),
+            ("100", 1, 1),
+        ] {
+            assert_eq!(percent(s), Ratio::new(numer.into(), denom.into()), "{s}");
+        }
+    }
+}
+
 // pub struct PercentAsRatio;
 
 // impl<'de> DeserializeAs<'de, Ratio<BigUint>> for PercentAsRatio {